@@ -8,11 +8,22 @@ try {
     console.error('[AudioDevices] Failed to load native module:', e);
 }
 
-const { getInputDevices, getOutputDevices } = NativeModule || {};
+const { getInputDevices, getOutputDevices, watchDevices } = NativeModule || {};
 
 export interface AudioDevice {
     id: string;
     name: string;
+    isDefault?: boolean;
+    sampleRates?: number[];
+    channels?: number;
+    sampleFormats?: string[];
+    hostApi?: string;
+}
+
+export interface AudioDeviceChange {
+    type: 'added' | 'removed' | 'defaultChanged';
+    direction: 'input' | 'output';
+    device: AudioDevice;
 }
 
 export class AudioDevices {
@@ -41,4 +52,22 @@ export class AudioDevices {
             return [];
         }
     }
+
+    /**
+     * Get notified when devices are added / removed or a default changes.
+     * Returns a function that stops watching.
+     */
+    public static watch(callback: (change: AudioDeviceChange) => void): () => void {
+        if (!watchDevices) {
+            console.warn('[AudioDevices] Native functionality not available');
+            return () => {};
+        }
+        try {
+            const subscription = watchDevices(callback);
+            return () => subscription.unsubscribe();
+        } catch (e) {
+            console.error('[AudioDevices] Failed to watch devices:', e);
+            return () => {};
+        }
+    }
 }
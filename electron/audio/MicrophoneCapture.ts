@@ -40,6 +40,13 @@ export class MicrophoneCapture extends EventEmitter {
         return this.monitor?.getSampleRate() || 16000;
     }
 
+    /**
+     * Native capture statistics (frames, drops, ring buffer, DSP latency), null before init
+     */
+    public getStats(): any {
+        return this.monitor?.getStats() ?? null;
+    }
+
     /**
      * Start capturing microphone audio
      */
@@ -66,14 +73,31 @@ export class MicrophoneCapture extends EventEmitter {
         try {
             console.log('[MicrophoneCapture] Starting native capture...');
 
-            this.monitor.start((chunk: Uint8Array) => {
+            this.monitor.start((frame: { pcm: Uint8Array; timestamp: number; sequence: number; kind: string; rms: number }) => {
+                const chunk = frame?.pcm;
                 if (chunk && chunk.length > 0) {
+                    this.emit('frame', { ...frame, pcm: Buffer.from(chunk) });
                     // Debug: log occasionally
                     if (Math.random() < 0.05) {
                         console.log(`[MicrophoneCapture] Emitting chunk: ${chunk.length} bytes to JS`);
                     }
                     this.emit('data', Buffer.from(chunk));
                 }
+            }, (event: { type: 'speechStart' | 'speechEnd'; timestamp: number; peakLevel: number; peakRms: number; durationMs: number }) => {
+                // Native VAD: drives the UI speaking indicator
+                this.emit(event.type, event);
+            }, (status: { code: string; state: string; message: string; fatal: boolean; timestamp: number; device?: string }) => {
+                // Native errors and lifecycle changes; fatal means no more audio until stop() + start()
+                // or until the capture moves to another device (deviceChanged)
+                this.emit('status', status);
+                if (status.code === 'deviceChanged') {
+                    console.log(`[MicrophoneCapture] ${status.message}`);
+                    this.emit('deviceChanged', { device: status.device, message: status.message });
+                }
+                if (status.fatal) {
+                    console.error(`[MicrophoneCapture] Capture failed (${status.code}): ${status.message}`);
+                    this.emit('error', new Error(`${status.code}: ${status.message}`));
+                }
             });
 
             this.isRecording = true;
@@ -36,6 +36,13 @@ export class SystemAudioCapture extends EventEmitter {
         return 16000;
     }
 
+    /**
+     * Native capture statistics (frames, drops, ring buffer, DSP latency), null before init
+     */
+    public getStats(): any {
+        return this.monitor?.getStats() ?? null;
+    }
+
     /**
      * Start capturing audio
      */
@@ -63,16 +70,33 @@ export class SystemAudioCapture extends EventEmitter {
         try {
             console.log('[SystemAudioCapture] Starting native capture...');
 
-            this.monitor.start((chunk: Uint8Array) => {
-                // The native module sends raw PCM bytes (Uint8Array)
+            this.monitor.start((frame: { pcm: Uint8Array; timestamp: number; sequence: number; kind: string; rms: number }) => {
+                // The native module sends PCM bytes plus frame metadata
+                const chunk = frame?.pcm;
                 if (chunk && chunk.length > 0) {
                     const buffer = Buffer.from(chunk);
+                    this.emit('frame', { ...frame, pcm: buffer });
                     if (Math.random() < 0.05) {
                         const prefix = buffer.slice(0, 10).toString('hex');
                         console.log(`[SystemAudioCapture] Chunk: ${buffer.length}b, Rate: ${this.detectedSampleRate}, Data(hex): ${prefix}...`);
                     }
                     this.emit('data', buffer);
                 }
+            }, (event: { type: 'speechStart' | 'speechEnd'; timestamp: number; peakLevel: number; peakRms: number; durationMs: number }) => {
+                // Native VAD: drives the UI speaking indicator
+                this.emit(event.type, event);
+            }, (status: { code: string; state: string; message: string; fatal: boolean; timestamp: number; device?: string }) => {
+                // Native errors and lifecycle changes; fatal means no more audio until stop() + start()
+                // or until the capture moves to another device (deviceChanged)
+                this.emit('status', status);
+                if (status.code === 'deviceChanged') {
+                    console.log(`[SystemAudioCapture] ${status.message}`);
+                    this.emit('deviceChanged', { device: status.device, message: status.message });
+                }
+                if (status.fatal) {
+                    console.error(`[SystemAudioCapture] Capture failed (${status.code}): ${status.message}`);
+                    this.emit('error', new Error(`${status.code}: ${status.message}`));
+                }
             });
 
             this.isRecording = true;
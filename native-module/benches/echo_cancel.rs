@@ -0,0 +1,77 @@
+// Echo canceller cost benchmark
+//
+// Runs the default canceller config (2048 taps at 16kHz, 6144 at 48kHz) on
+// a far end with a room echo and reports the time spent per second of
+// audio. The canceller must stay well under real time on the mic DSP thread.
+// Run with: cargo bench --bench echo_cancel
+//
+// The crate is a cdylib whose N-API symbols only resolve inside Node, so the
+// modules under test are compiled straight into this binary.
+
+use std::f64::consts::PI;
+use std::time::{Duration, Instant};
+
+#[allow(dead_code)]
+#[path = "../src/audio_config.rs"]
+mod audio_config;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/echo_cancel.rs"]
+mod echo_cancel;
+
+use audio_config::OutputFormat;
+use echo_cancel::{EchoCancelConfig, EchoCanceller, EchoReference};
+
+const SECONDS: usize = 10;
+
+/// Sparse room impulse response: (delay seconds, gain)
+const ECHO_PATH: [(f64, f64); 3] = [(0.010, 0.5), (0.013, -0.2), (0.021, 0.1)];
+
+/// Far-end signal: a few unrelated partials
+fn far_end(t: f64) -> f64 {
+    [(220.0, 0.03), (530.0, 0.02), (1270.0, 0.02), (2910.0, 0.01)]
+        .iter()
+        .map(|(f, a)| a * (2.0 * PI * f * t).sin())
+        .sum()
+}
+
+fn to_i16(x: f64) -> i16 {
+    (x * 32768.0).clamp(-32768.0, 32767.0) as i16
+}
+
+/// Time spent in the canceller for SECONDS of audio
+fn run(format: OutputFormat) -> Duration {
+    let reference = EchoReference::with_sample_rate(format.sample_rate);
+    let mut canceller = EchoCanceller::new(EchoCancelConfig::for_format(format));
+    let rate = format.sample_rate as f64;
+    let frame_samples = format.frame_samples();
+    let base = Instant::now();
+
+    let mut cost = Duration::ZERO;
+    for f in 0..SECONDS * format.sample_rate as usize / frame_samples {
+        let first = f * frame_samples;
+        let at = base + Duration::from_secs_f64(first as f64 / rate);
+        let far: Vec<i16> = (first..first + frame_samples).map(|k| to_i16(far_end(k as f64 / rate))).collect();
+        reference.push(&far, at);
+
+        let mut mic: Vec<i16> = (first..first + frame_samples)
+            .map(|k| {
+                let t = k as f64 / rate;
+                to_i16(ECHO_PATH.iter().map(|(d, g)| g * far_end(t - d)).sum())
+            })
+            .collect();
+        let started = Instant::now();
+        canceller.process(&reference, &mut mic, at);
+        cost += started.elapsed();
+    }
+    cost
+}
+
+fn main() {
+    println!("{:<10} {:>10} {:>14}", "rate", "frame ms", "% real time");
+    for sample_rate in [16_000, 48_000] {
+        let format = OutputFormat { sample_rate, frame_ms: 20 };
+        let cost = run(format);
+        let realtime = cost.as_secs_f64() / SECONDS as f64;
+        println!("{:<10} {:>10} {:>14.2}", sample_rate, format.frame_ms, realtime * 100.0);
+    }
+}
@@ -0,0 +1,131 @@
+// Idle CPU benchmark: 1ms polling vs DataNotifier wakeup
+//
+// Simulates the DSP thread draining a ring buffer while the device is idle:
+// - "silent device": callback still delivers 10ms of zeros every 10ms
+// - "stalled device": no callbacks at all
+//
+// Reports consumer wakeups/sec and consumer thread CPU time.
+// Run with: cargo bench --bench idle_wakeup
+//
+// The crate is a cdylib whose N-API symbols only resolve inside Node, so the
+// modules under test are compiled straight into this binary.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ringbuf::traits::{Consumer, Producer, Split};
+use ringbuf::HeapRb;
+
+#[allow(dead_code)]
+#[path = "../src/audio_config.rs"]
+mod audio_config;
+#[allow(unused_imports)]
+#[path = "../src/wakeup.rs"]
+mod wakeup;
+
+use audio_config::{DSP_WAIT_TIMEOUT_MS, RING_BUFFER_SAMPLES};
+use wakeup::DataNotifier;
+
+const RUN_FOR: Duration = Duration::from_secs(3);
+const CALLBACK_SAMPLES: usize = 480; // 10ms at 48kHz
+
+#[derive(Clone, Copy)]
+enum Wakeup {
+    Poll1ms,
+    Notifier,
+}
+
+struct Report {
+    wakeups_per_sec: f64,
+    cpu_percent: Option<f64>,
+}
+
+/// CPU time consumed by the calling thread
+#[cfg(unix)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    let rc = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    (rc == 0).then(|| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+fn run(wakeup: Wakeup, device_delivers: bool) -> Report {
+    let (mut producer, mut consumer) = HeapRb::<f32>::new(RING_BUFFER_SAMPLES).split();
+    let notifier = DataNotifier::new();
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // Fake device callback
+    let producer_notifier = notifier.clone();
+    let producer_stop = stop.clone();
+    let device = thread::spawn(move || {
+        let silence = [0.0f32; CALLBACK_SAMPLES];
+        while !producer_stop.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(10));
+            if device_delivers {
+                let _ = producer.push_slice(&silence);
+                producer_notifier.notify();
+            }
+        }
+    });
+
+    // DSP thread
+    let dsp_stop = stop.clone();
+    let dsp = thread::spawn(move || {
+        notifier.register_current_thread();
+        let cpu_start = thread_cpu_time();
+        let start = Instant::now();
+        let mut wakeups = 0u64;
+        let timeout = Duration::from_millis(DSP_WAIT_TIMEOUT_MS);
+
+        while !dsp_stop.load(Ordering::Relaxed) {
+            let mut drained = false;
+            while consumer.try_pop().is_some() {
+                drained = true;
+            }
+            if !drained {
+                match wakeup {
+                    Wakeup::Poll1ms => thread::sleep(Duration::from_millis(1)),
+                    Wakeup::Notifier => {
+                        notifier.wait_timeout(timeout);
+                    }
+                }
+                wakeups += 1;
+            }
+        }
+
+        let elapsed = start.elapsed();
+        let cpu_percent = match (cpu_start, thread_cpu_time()) {
+            (Some(a), Some(b)) => Some((b - a).as_secs_f64() / elapsed.as_secs_f64() * 100.0),
+            _ => None,
+        };
+        Report {
+            wakeups_per_sec: wakeups as f64 / elapsed.as_secs_f64(),
+            cpu_percent,
+        }
+    });
+
+    thread::sleep(RUN_FOR);
+    stop.store(true, Ordering::SeqCst);
+    device.join().unwrap();
+    dsp.join().unwrap()
+}
+
+fn main() {
+    println!("{:<16} {:<16} {:>12} {:>10}", "device", "wakeup", "wakeups/s", "cpu %");
+    for (label, delivers) in [("silent device", true), ("stalled device", false)] {
+        for (name, wakeup) in [("poll 1ms", Wakeup::Poll1ms), ("notifier", Wakeup::Notifier)] {
+            let report = run(wakeup, delivers);
+            let cpu = report
+                .cpu_percent
+                .map(|c| format!("{:.3}", c))
+                .unwrap_or_else(|| "n/a".to_string());
+            println!("{:<16} {:<16} {:>12.1} {:>10}", label, name, report.wakeups_per_sec, cpu);
+        }
+    }
+}
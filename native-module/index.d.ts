@@ -3,21 +3,309 @@
 
 /* auto-generated by NAPI-RS */
 
+export interface CaptureOptions {
+  /** Output sample rate: 8000, 12000, 16000 (default), 24000, 32000, 44100 or 48000 */
+  sampleRate?: number
+  /** Frame duration in ms: 10, 20 (default), 30, 40 or 60 */
+  frameMs?: number
+  /** Noise suppression strength: "off" (default), "low", "moderate" or "high" */
+  noiseSuppression?: string
+  /**
+   * Automatic gain control (off unless given). Levels are normalized, so
+   * the silence gate's default threshold becomes at least the microphone
+   * one (100) for every capture; silenceGate.thresholdRms still wins
+   */
+  agc?: AgcOptions
+  /**
+   * Resampler preset: "fast" (linear, no anti-aliasing),
+   * "balanced" (default) or "high"
+   */
+  resamplerQuality?: string
+  /**
+   * How multi-channel input becomes mono: "channel" (default, only
+   * inputChannel), "average" (mean of all channels) or "loudest" (the
+   * most energetic channel, followed as it changes; for array mics)
+   */
+  channelMode?: string
+  /**
+   * Zero-based channel for channelMode "channel" (default 0; implies
+   * "channel" when channelMode is not given)
+   */
+  inputChannel?: number
+  /**
+   * Frame encoding: "pcm" (default, LINEAR16 buffers) or "opus"
+   * (one packet object per frame; needs an Opus sampleRate and frameMs,
+   * and a build with the "opus" cargo feature)
+   */
+  encoding?: string
+  /** Opus target bitrate in bits/second (default 24000) */
+  opusBitrate?: number
+  /**
+   * Seconds of unsuppressed output audio kept for getHistory()
+   * (default 0 = off, max 600)
+   */
+  historySeconds?: number
+  /**
+   * What the callback receives for PCM frames: "frame" (default,
+   * `{ pcm, timestamp, sequence, kind, rms }`) or "raw" (bare LINEAR16 bytes).
+   * `kind` is "speech", "preroll", "hangover" or "keepalive"
+   */
+  callbackMode?: string
+  /** Speech start / end detection for the start() event callback */
+  vad?: VadOptions
+  /**
+   * How the silence gate and VAD events detect speech: "rms" (default,
+   * fixed level threshold), "spectral" (adaptive noise floor and spectral
+   * shape; ignores music, typing and fans, keeps quiet talkers) or
+   * "neural" (ONNX model on CPU, needs vadModelPath and a 16000 sampleRate;
+   * only in builds with the neural-vad feature, which load ONNX Runtime
+   * 1.20 from the system or ORT_DYLIB_PATH)
+   */
+  speechDetector?: string
+  /** Silero VAD v5 ONNX model file for speechDetector "neural" */
+  vadModelPath?: string
+  /** Speech threshold of the silence gate (RMS detector) */
+  silenceGate?: SilenceGateOptions
+}
+export interface VadOptions {
+  /** RMS level (i16 scale) that starts speech (default 185, ~-45dBFS) */
+  startThreshold?: number
+  /** RMS level below which speech starts to end (default 100, ~-50dBFS) */
+  endThreshold?: number
+  /** Quiet time before speechEnd fires, ms (default 500) */
+  hangoverMs?: number
+}
+export interface SilenceGateOptions {
+  /** "fixed" (default) or "adaptive" (follows the background noise floor) */
+  mode?: string
+  /**
+   * Fixed mode threshold, RMS i16 scale (default 100 microphone, 30
+   * system, 100 for both with agc); used as given, also with agc
+   */
+  thresholdRms?: number
+  /** Adaptive: threshold above the noise floor, dB (default 9) */
+  marginDb?: number
+  /** Adaptive: lowest threshold, RMS (default 30) */
+  minRms?: number
+  /** Adaptive: highest threshold, RMS (default 2000) */
+  maxRms?: number
+  /**
+   * Suppressed audio sent ahead of a speech onset, ms (default 60, 0
+   * disables, max 1000). Only audio after the last keepalive is sent, so
+   * timestamps never go backwards.
+   */
+  prerollMs?: number
+}
+export interface AgcOptions {
+  /** Default true when the object is given */
+  enabled?: boolean
+  /** Speech level to steer towards, RMS dBFS (default -20) */
+  targetLevelDbfs?: number
+  /** Largest gain applied to quiet input, dB (default 30) */
+  maxGainDb?: number
+  /** Time constant for reducing gain, ms (default 20) */
+  attackMs?: number
+  /** Time constant for increasing gain, ms (default 1000) */
+  releaseMs?: number
+}
+export interface CaptureStats {
+  /**
+   * Current AGC gain in dB (0 when AGC is off).
+   * Staying near the configured maxGainDb means the input is very quiet.
+   */
+  agcGainDb: number
+  /**
+   * Average CPU time the speech detector spends per frame, ms.
+   * Compare with frameMs: the neural detector must stay well below it.
+   */
+  speechDetectorMs: number
+  /** Background level the silence gate measures, RMS i16 scale */
+  noiseFloorRms: number
+  /** Speech threshold the silence gate applies (fixed, or floor + margin) */
+  speechThresholdRms: number
+  /** Frames passed to the callback since start(), keepalives included */
+  framesEmitted: number
+  /** Generated silence frames among framesEmitted */
+  keepaliveFrames: number
+  /** Frames the silence gate dropped */
+  suppressedFrames: number
+  /**
+   * Input samples lost because the ring buffer was full.
+   * Growing means the DSP thread cannot keep up.
+   */
+  samplesDropped: number
+  /** Ring buffer fill level before the last drain (0..1) */
+  ringBufferFill: number
+  /** Rate the device delivers, Hz (0 before start()) */
+  inputSampleRate: number
+  /** Average time from draining the ring buffer to emitting its frames, ms */
+  dspLatencyMs: number
+}
 export interface AudioDeviceInfo {
   id: string
   name: string
+  /**
+   * The current system default device
+   * (the "default" input entry follows it and is never flagged)
+   */
+  isDefault: boolean
+  /** Sample rates the device accepts, ascending */
+  sampleRates: Array<number>
+  /** Most channels the device offers */
+  channels: number
+  /** Native sample formats, e.g. "f32", "i16" */
+  sampleFormats: Array<string>
+  /** "CoreAudio", "WASAPI", "ALSA" or "PulseAudio" */
+  hostApi: string
+}
+export interface FileAudioOptions {
+  /**
+   * "wav" (default), "f32" or "s16" for headerless little-endian PCM
+   */
+  format?: string
+  /** Sample rate of headerless PCM (ignored for WAV) */
+  sampleRate?: number
+  /** Channel count of headerless PCM (ignored for WAV) */
+  channels?: number
+  /**
+   * Pace playback like a live device (default true).
+   * false replays as fast as the pipeline can process.
+   */
+  realtime?: boolean
+  /** Suppression profile: "microphone" (default) or "system" */
+  profile?: string
+}
+export interface RecorderOptions {
+  /** "wav" or "flac" (default: from the file extension, else wav) */
+  format?: string
+  /**
+   * 1 (default): all captures mixed to mono.
+   * 2: microphone left, system audio right (needs both).
+   */
+  channels?: number
+}
+export interface RecordingInfo {
+  path: string
+  format: string
+  recording: boolean
+  /** File size so far */
+  bytesWritten: number
+  durationMs: number
+  /** Frames lost because the disk could not keep up */
+  droppedFrames: number
 }
 export declare function getInputDevices(): Array<AudioDeviceInfo>
 export declare function getOutputDevices(): Array<AudioDeviceInfo>
+/**
+ * Call `callback` with `{ type, direction, device }` whenever an input or
+ * output device is added or removed ("added" / "removed") or a default
+ * device changes ("defaultChanged", device is the new default).
+ * direction: "input" or "output"; device: AudioDeviceInfo
+ */
+export declare function watchDevices(callback: (...args: any[]) => any): DeviceSubscription
+/** Current time on the clock of frame timestamps (ms since a process-wide epoch) */
+export declare function getMonotonicTimeMs(): number
 export declare class SystemAudioCapture {
-  constructor(deviceId?: string | undefined | null)
+  constructor(deviceId?: string | undefined | null, options?: CaptureOptions | undefined | null)
   getSampleRate(): number
-  start(callback: (...args: any[]) => any): void
+  /**
+   * `vadCallback` receives speechStart / speechEnd events,
+   * `statusCallback` errors, state changes and device changes
+   */
+  start(callback: (...args: any[]) => any, vadCallback?: (...args: any[]) => any | undefined | null, statusCallback?: (...args: any[]) => any | undefined | null): void
   stop(): void
+  getStats(): CaptureStats
+  /** "idle", "starting", "running", "stalled", "stopped" or "failed" */
+  getState(): string
+  /**
+   * Unsuppressed output audio from `startSecondsAgo` to `endSecondsAgo`
+   * (default 0) before the newest sample, clipped to the history kept.
+   * format: "pcm" (default, LINEAR16) or "wav"
+   */
+  getHistory(startSecondsAgo: number, endSecondsAgo?: number | undefined | null, format?: string | undefined | null): Buffer
+  /** Seconds of audio currently held in the history (0 when off) */
+  getHistoryDuration(): number
 }
 export declare class MicrophoneCapture {
-  constructor(deviceId?: string | undefined | null)
+  constructor(deviceId?: string | undefined | null, options?: CaptureOptions | undefined | null)
+  getSampleRate(): number
+  /**
+   * `vadCallback` receives speechStart / speechEnd events,
+   * `statusCallback` errors, state changes and device changes
+   */
+  start(callback: (...args: any[]) => any, vadCallback?: (...args: any[]) => any | undefined | null, statusCallback?: (...args: any[]) => any | undefined | null): void
+  stop(): void
+  getStats(): CaptureStats
+  /** "idle", "starting", "running", "stalled", "stopped" or "failed" */
+  getState(): string
+  /**
+   * Unsuppressed output audio from `startSecondsAgo` to `endSecondsAgo`
+   * (default 0) before the newest sample, clipped to the history kept.
+   * format: "pcm" (default, LINEAR16) or "wav"
+   */
+  getHistory(startSecondsAgo: number, endSecondsAgo?: number | undefined | null, format?: string | undefined | null): Buffer
+  /** Seconds of audio currently held in the history (0 when off) */
+  getHistoryDuration(): number
+  /**
+   * Use a SystemAudioCapture's output as the far-end echo reference.
+   * Takes effect immediately, also while capturing.
+   * Both captures must use the same sampleRate.
+   */
+  setEchoReference(system: SystemAudioCapture): void
+  clearEchoReference(): void
+  /** Toggle echo cancellation (off by default; needs an echo reference) */
+  setEchoCancellation(enabled: boolean): void
+}
+export declare class FileAudioCapture {
+  constructor(path: string, options?: FileAudioOptions | undefined | null, captureOptions?: CaptureOptions | undefined | null)
   getSampleRate(): number
+  /**
+   * Replays the file from the beginning.
+   * `vadCallback` receives speechStart / speechEnd events,
+   * `statusCallback` errors and state changes
+   */
+  start(callback: (...args: any[]) => any, vadCallback?: (...args: any[]) => any | undefined | null, statusCallback?: (...args: any[]) => any | undefined | null): void
+  /** True once the whole file has been fed to the pipeline */
+  isFinished(): boolean
+  stop(): void
+  getStats(): CaptureStats
+  /** "idle", "starting", "running", "stalled", "stopped" or "failed" */
+  getState(): string
+  /**
+   * Unsuppressed output audio from `startSecondsAgo` to `endSecondsAgo`
+   * (default 0) before the newest sample, clipped to the history kept.
+   * format: "pcm" (default, LINEAR16) or "wav"
+   */
+  getHistory(startSecondsAgo: number, endSecondsAgo?: number | undefined | null, format?: string | undefined | null): Buffer
+  /** Seconds of audio currently held in the history (0 when off) */
+  getHistoryDuration(): number
+}
+/**
+ * Records one or both captures at their output sample rate, aligned by
+ * capture timestamps. Captures keep running while recordings start and stop.
+ */
+export declare class AudioRecorder {
+  constructor(path: string, options?: RecorderOptions | undefined | null)
+  addMicrophone(microphone: MicrophoneCapture): void
+  addSystemAudio(system: SystemAudioCapture): void
+  /** Create (or overwrite) the file and start writing */
+  start(): void
+  /** Finalize the file and return its final size and duration */
+  stop(): RecordingInfo
+  getInfo(): RecordingInfo
+}
+/**
+ * Microphone (left) and system audio (right) aligned by capture
+ * timestamps, delivered as interleaved stereo LINEAR16 frames
+ */
+export declare class StereoMixer {
+  constructor(microphone: MicrophoneCapture, system: SystemAudioCapture)
+  /** Callback receives frameMs of interleaved stereo per call */
   start(callback: (...args: any[]) => any): void
   stop(): void
 }
+/** Live device-list subscription returned by `watchDevices` */
+export declare class DeviceSubscription {
+  /** Stop watching (also releases the callback) */
+  unsubscribe(): void
+}
@@ -310,9 +310,15 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { SystemAudioCapture, MicrophoneCapture, getInputDevices, getOutputDevices } = nativeBinding
+const { SystemAudioCapture, MicrophoneCapture, FileAudioCapture, AudioRecorder, StereoMixer, DeviceSubscription, getInputDevices, getOutputDevices, watchDevices, getMonotonicTimeMs } = nativeBinding
 
 module.exports.SystemAudioCapture = SystemAudioCapture
 module.exports.MicrophoneCapture = MicrophoneCapture
+module.exports.FileAudioCapture = FileAudioCapture
+module.exports.AudioRecorder = AudioRecorder
+module.exports.StereoMixer = StereoMixer
+module.exports.DeviceSubscription = DeviceSubscription
 module.exports.getInputDevices = getInputDevices
 module.exports.getOutputDevices = getOutputDevices
+module.exports.watchDevices = watchDevices
+module.exports.getMonotonicTimeMs = getMonotonicTimeMs
@@ -0,0 +1,324 @@
+// Automatic Gain Control - normalize quiet system audio and distant talkers
+//
+// Runs on output frames after noise suppression and before the
+// silence gate, so gating and STT both see normalized levels.
+//
+// BEHAVIOUR:
+// - Noise floor: minimum of the (~40ms smoothed) frame power over the
+//   last ~2s, the same minimum tracking the spectral detector uses.
+//   Speech pauses keep it low, stationary noise (fans, hum) fills it.
+// - Level: speech power smoothed over ~100ms, taken only from frames
+//   SPEECH_MARGIN_DB above the noise floor and above GATE_DBFS
+// - Desired gain: target - level, clamped to [MIN_GAIN_DB, max_gain_db]
+// - Attack (gain going down) is fast so loud onsets don't clip;
+//   release (gain going up) is slow so pauses don't pump up the noise
+// - All other frames hold the current gain: silence and steady background
+//   noise never pull the gain up, whatever their absolute level
+// - Gain ramps linearly across each frame (no zipper noise) and a peak
+//   limiter keeps the output from clipping: it caps the whole ramp, from
+//   the first sample of the frame, and never changes the AGC's own gain
+
+use std::time::Duration;
+
+use crate::audio_config::OutputFormat;
+
+/// Largest attenuation the AGC applies to hot input
+const MIN_GAIN_DB: f32 = -12.0;
+
+/// Frames quieter than this hold the gain (~RMS 33 on the i16 scale)
+const GATE_DBFS: f32 = -60.0;
+
+/// Level above the tracked noise floor a frame needs to steer the gain
+const SPEECH_MARGIN_DB: f32 = 10.0;
+
+/// Noise floor = minimum over this window (sub-windows of a quarter each)
+const FLOOR_WINDOW: Duration = Duration::from_millis(2000);
+const FLOOR_SUBWINDOWS: usize = 4;
+
+/// Time constant of the frame power the floor is tracked on
+const FLOOR_POWER_SMOOTHING: Duration = Duration::from_millis(40);
+
+/// Smoothing of the speech level estimate per frame (~100ms)
+const LEVEL_SMOOTHING: f32 = 0.8;
+
+/// Output peak ceiling
+const LIMIT_PEAK: f32 = 32000.0;
+
+#[derive(Debug, Clone)]
+pub struct AgcConfig {
+    /// Level the AGC steers speech towards (RMS, dBFS)
+    pub target_level_dbfs: f32,
+    /// Largest gain applied to quiet input
+    pub max_gain_db: f32,
+    /// Time constant for reducing gain
+    pub attack: Duration,
+    /// Time constant for increasing gain
+    pub release: Duration,
+}
+
+impl Default for AgcConfig {
+    fn default() -> Self {
+        Self {
+            target_level_dbfs: -20.0,
+            max_gain_db: 30.0,
+            attack: Duration::from_millis(20),
+            release: Duration::from_millis(1000),
+        }
+    }
+}
+
+pub struct AutomaticGainControl {
+    config: AgcConfig,
+    /// Long-term AGC gain (before the limiter)
+    gain_db: f32,
+    /// Linear gain applied at the end of the last frame (after the limiter)
+    applied_gain: f32,
+    /// Smoothed mean square of recent speech frames (0 until the first one)
+    speech_power: f32,
+    noise_floor: NoiseFloor,
+    /// Per-frame smoothing coefficients derived from attack / release
+    attack_coeff: f32,
+    release_coeff: f32,
+}
+
+impl AutomaticGainControl {
+    pub fn new(config: AgcConfig, format: OutputFormat) -> Self {
+        println!("[AGC] Created with target={}dBFS, max_gain={}dB, attack={}ms, release={}ms",
+            config.target_level_dbfs,
+            config.max_gain_db,
+            config.attack.as_millis(),
+            config.release.as_millis()
+        );
+        let coeff = |tau: Duration| {
+            let tau_ms = tau.as_secs_f32() * 1000.0;
+            if tau_ms <= 0.0 {
+                1.0
+            } else {
+                1.0 - (-(format.frame_ms as f32) / tau_ms).exp()
+            }
+        };
+        Self {
+            attack_coeff: coeff(config.attack),
+            release_coeff: coeff(config.release),
+            config,
+            gain_db: 0.0,
+            applied_gain: 1.0,
+            speech_power: 0.0,
+            noise_floor: NoiseFloor::new(format.frame_ms as f32),
+        }
+    }
+
+    /// Apply gain to one frame in place
+    pub fn process(&mut self, frame: &mut [i16]) {
+        if frame.is_empty() {
+            return;
+        }
+        let power = mean_square(frame);
+        let floor = self.noise_floor.update(power);
+        if rms_dbfs(frame) > GATE_DBFS && power > floor * db_to_power(SPEECH_MARGIN_DB) {
+            self.speech_power = if self.speech_power == 0.0 {
+                power
+            } else {
+                LEVEL_SMOOTHING * self.speech_power + (1.0 - LEVEL_SMOOTHING) * power
+            };
+            let level_dbfs = linear_to_db(self.speech_power.sqrt() / 32768.0);
+            let desired = (self.config.target_level_dbfs - level_dbfs)
+                .clamp(MIN_GAIN_DB.min(self.config.max_gain_db), self.config.max_gain_db);
+            let coeff = if desired < self.gain_db {
+                self.attack_coeff
+            } else {
+                self.release_coeff
+            };
+            self.gain_db += coeff * (desired - self.gain_db);
+        }
+
+        // Peak limiter: both ends of the ramp stay below the frame's safe
+        // gain, so no sample of the frame goes over the ceiling
+        let peak = frame.iter().map(|&s| (s as f32).abs()).fold(0.0f32, f32::max);
+        let safe_gain = if peak > 0.0 { LIMIT_PEAK / peak } else { f32::MAX };
+        let start = self.applied_gain.min(safe_gain);
+        let end = db_to_linear(self.gain_db).min(safe_gain);
+        self.applied_gain = end;
+
+        let step = (end - start) / frame.len() as f32;
+        for (i, sample) in frame.iter_mut().enumerate() {
+            let g = start + step * (i + 1) as f32;
+            *sample = (*sample as f32 * g).clamp(-LIMIT_PEAK, LIMIT_PEAK) as i16;
+        }
+    }
+
+    /// Current gain in dB
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+}
+
+/// Minimum of the smoothed frame power over the last FLOOR_WINDOW
+struct NoiseFloor {
+    smoothing: f32,
+    smoothed: Option<f32>,
+    /// Minimum of each finished sub-window, and of the current one
+    subwindow_minima: Vec<f32>,
+    current_minimum: f32,
+    subwindow_frames: usize,
+    frames_in_subwindow: usize,
+}
+
+impl NoiseFloor {
+    fn new(frame_ms: f32) -> Self {
+        let frame_ms = frame_ms.max(1.0);
+        let subwindow_ms = FLOOR_WINDOW.as_secs_f32() * 1000.0 / FLOOR_SUBWINDOWS as f32;
+        Self {
+            smoothing: (-frame_ms / (FLOOR_POWER_SMOOTHING.as_secs_f32() * 1000.0)).exp(),
+            smoothed: None,
+            subwindow_minima: Vec::with_capacity(FLOOR_SUBWINDOWS),
+            current_minimum: f32::MAX,
+            subwindow_frames: ((subwindow_ms / frame_ms).round() as usize).max(1),
+            frames_in_subwindow: 0,
+        }
+    }
+
+    /// Floor from the frames before this one (f32::MAX until there is one),
+    /// then track `power`
+    fn update(&mut self, power: f32) -> f32 {
+        let floor = self.subwindow_minima.iter().fold(self.current_minimum, |a, &b| a.min(b));
+
+        let smoothed = match self.smoothed {
+            Some(previous) => self.smoothing * previous + (1.0 - self.smoothing) * power,
+            None => power,
+        };
+        self.smoothed = Some(smoothed);
+        self.current_minimum = self.current_minimum.min(smoothed);
+
+        self.frames_in_subwindow += 1;
+        if self.frames_in_subwindow >= self.subwindow_frames {
+            if self.subwindow_minima.len() == FLOOR_SUBWINDOWS {
+                self.subwindow_minima.remove(0);
+            }
+            self.subwindow_minima.push(self.current_minimum);
+            self.current_minimum = f32::MAX;
+            self.frames_in_subwindow = 0;
+        }
+        floor
+    }
+}
+
+fn mean_square(frame: &[i16]) -> f32 {
+    let sum: f64 = frame.iter().map(|&s| (s as f64) * (s as f64)).sum();
+    (sum / frame.len() as f64) as f32
+}
+
+fn rms_dbfs(frame: &[i16]) -> f32 {
+    linear_to_db(mean_square(frame).sqrt().max(1.0) / 32768.0)
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+fn db_to_power(db: f32) -> f32 {
+    10f32.powf(db / 10.0)
+}
+
+fn linear_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-6).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_config::FRAME_SAMPLES;
+    use crate::test_fixtures;
+
+    fn run(agc: &mut AutomaticGainControl, samples: &[f32]) -> Vec<i16> {
+        let mut out = test_fixtures::to_i16(samples);
+        for frame in out.chunks_exact_mut(FRAME_SAMPLES) {
+            agc.process(frame);
+        }
+        out
+    }
+
+    fn scaled(samples: &[f32], gain_db: f32) -> Vec<f32> {
+        samples.iter().map(|s| s * db_to_linear(gain_db)).collect()
+    }
+
+    /// RMS over frames above the gate (speech only), in dBFS
+    fn speech_level(samples: &[i16]) -> f32 {
+        let active: Vec<&[i16]> = samples.chunks_exact(FRAME_SAMPLES)
+            .filter(|f| rms_dbfs(f) > GATE_DBFS)
+            .collect();
+        let power: f32 = active.iter().map(|f| mean_square(f)).sum::<f32>() / active.len() as f32;
+        linear_to_db(power.sqrt() / 32768.0)
+    }
+
+    #[test]
+    fn test_quiet_speech_raised_towards_target() {
+        // ~-45 dBFS speech: 25 dB below the target
+        let speech = scaled(&test_fixtures::speech(8.0, 11), -25.0);
+        let mut agc = AutomaticGainControl::new(AgcConfig::default(), OutputFormat::default());
+        let out = run(&mut agc, &speech);
+
+        let before = speech_level(&test_fixtures::to_i16(&speech[speech.len() / 2..]));
+        let after = speech_level(&out[out.len() / 2..]);
+        let levels = format!("{:.1} dBFS -> {:.1} dBFS (gain {:.1} dB)", before, after, agc.gain_db());
+        assert!(after - before > 15.0, "{}", levels);
+        assert!((after - (-20.0)).abs() < 6.0, "{}", levels);
+    }
+
+    #[test]
+    fn test_gain_capped_at_max() {
+        let speech = scaled(&test_fixtures::speech(6.0, 12), -30.0);
+        let mut agc = AutomaticGainControl::new(AgcConfig {
+            max_gain_db: 12.0,
+            ..AgcConfig::default()
+        }, OutputFormat::default());
+        run(&mut agc, &speech);
+        assert!(agc.gain_db() <= 12.0 + 1e-3);
+        assert!(agc.gain_db() > 10.0, "gain {:.1} dB", agc.gain_db());
+    }
+
+    #[test]
+    fn test_attack_faster_than_release() {
+        let mut agc = AutomaticGainControl::new(AgcConfig::default(), OutputFormat::default());
+        let quiet = scaled(&test_fixtures::speech(3.0, 14), -30.0); // ~-50 dBFS speech
+        run(&mut agc, &quiet);
+        let raised = agc.gain_db();
+        assert!(raised > 20.0, "gain {:.1} dB", raised);
+
+        // A loud onset must be pulled down within ~100ms, without clipping:
+        // only the sine's peaks may reach the ceiling, not flattened tops
+        let loud: Vec<f32> = (0..FRAME_SAMPLES * 5)
+            .map(|i| 0.3 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16_000.0).sin())
+            .collect();
+        let out = run(&mut agc, &loud);
+        assert!(agc.gain_db() < 0.0, "gain still {:.1} dB", agc.gain_db());
+        let at_ceiling = out[..FRAME_SAMPLES].iter().filter(|&&s| (s as f32).abs() >= LIMIT_PEAK - 1.0).count();
+        assert!(at_ceiling <= 4, "{} samples of the onset frame at the ceiling", at_ceiling);
+    }
+
+    #[test]
+    fn test_silence_not_amplified() {
+        let mut agc = AutomaticGainControl::new(AgcConfig::default(), OutputFormat::default());
+        let noise: Vec<f32> = test_fixtures::fan(2.0, 13).iter().map(|s| s * 0.01).collect();
+        let out = run(&mut agc, &noise);
+        assert_eq!(agc.gain_db(), 0.0);
+        assert_eq!(out, test_fixtures::to_i16(&noise));
+    }
+
+    #[test]
+    fn test_steady_fan_noise_not_amplified() {
+        // Laptop fan at ~-50 dBFS: above GATE_DBFS, but no louder than its own floor
+        let fan = scaled(&test_fixtures::fan(4.0, 15), -24.0);
+        let mut agc = AutomaticGainControl::new(AgcConfig::default(), OutputFormat::default());
+        let out = run(&mut agc, &fan);
+        let level = speech_level(&out);
+        assert!(level > GATE_DBFS && level < -45.0, "fan at {:.1} dBFS", level);
+        assert!(agc.gain_db().abs() < 1.0, "gain {:.1} dB on fan noise", agc.gain_db());
+
+        // Speech over the same fan is still raised
+        let speech = scaled(&test_fixtures::speech(6.0, 16), -15.0);
+        let mixed: Vec<f32> = speech.iter().zip(fan.iter().cycle()).map(|(s, n)| s + n).collect();
+        run(&mut agc, &mixed);
+        assert!(agc.gain_db() > 8.0, "gain {:.1} dB on speech in fan noise", agc.gain_db());
+    }
+}
@@ -17,6 +17,36 @@ pub const FRAME_SAMPLES: usize = 320;
 // Legacy alias for compatibility during migration
 pub const CHUNK_SAMPLES: usize = FRAME_SAMPLES;
 
+/// Output sample rates a capture can be configured for
+/// (8kHz telephony up to 48kHz)
+pub const SUPPORTED_SAMPLE_RATES: [u32; 7] = [8_000, 12_000, 16_000, 24_000, 32_000, 44_100, 48_000];
+
+/// Frame durations a capture can be configured for
+pub const SUPPORTED_FRAME_MS: [u32; 5] = [10, 20, 30, 40, 60];
+
+/// Per-capture output format (defaults: SAMPLE_RATE, FRAME_MS)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputFormat {
+    pub sample_rate: u32,
+    pub frame_ms: u32,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self {
+            sample_rate: SAMPLE_RATE,
+            frame_ms: FRAME_MS,
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Samples per frame, e.g. 320 for 16kHz / 20ms
+    pub fn frame_samples(&self) -> usize {
+        (self.sample_rate as usize * self.frame_ms as usize) / 1000
+    }
+}
+
 /// VAD thresholds (for UI display only - does NOT gate STT audio)
 /// These match the Swift implementation values
 pub const VAD_START_RMS: f32 = 185.0;  // Speech start threshold (~-45dBFS)
@@ -28,10 +58,10 @@ pub const VAD_PREROLL_CHUNKS: usize = 3;
 /// VAD hangover duration in milliseconds
 pub const VAD_HANGOVER_MS: u128 = 500;
 
-/// DSP thread poll interval in milliseconds
-/// Lower = less latency, higher CPU
-/// 1ms is optimal for real-time audio
-pub const DSP_POLL_MS: u64 = 1;
+/// Longest the DSP thread parks without a producer signal
+/// Producers wake it on every push, so this only bounds how quickly
+/// a stop request or a stalled device is noticed
+pub const DSP_WAIT_TIMEOUT_MS: u64 = 100;
 
 /// Ring buffer size in samples
 /// 128KB worth of f32 samples = 32768 samples
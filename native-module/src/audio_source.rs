@@ -0,0 +1,37 @@
+// Audio Source Abstraction
+//
+// Every capture backend (cpal microphone, CoreAudio tap, ScreenCaptureKit,
+// WASAPI loopback, PulseAudio monitor) hands f32 samples to the DSP thread
+// through a lock-free SPSC ring buffer. This trait is the contract the shared
+// capture pipeline relies on, so a new backend only has to implement it.
+
+use anyhow::Result;
+use ringbuf::HeapCons;
+
+use crate::stats::OverflowCounter;
+use crate::wakeup::DataNotifier;
+
+/// A source of f32 samples delivered through a ring buffer consumer
+pub trait AudioSource {
+    /// Sample rate of the samples in the ring buffer
+    fn sample_rate(&self) -> u32;
+
+    /// Number of interleaved channels in the ring buffer
+    fn channels(&self) -> u16;
+
+    /// Take ownership of the consumer for the DSP thread
+    /// Returns None if it was already taken
+    fn take_consumer(&mut self) -> Option<HeapCons<f32>>;
+
+    /// Notifier the producer signals after every push
+    fn data_notifier(&self) -> DataNotifier;
+
+    /// Counter the producer records samples dropped on overflow in
+    fn overflow_counter(&self) -> OverflowCounter;
+
+    /// Start delivering samples
+    fn start(&mut self) -> Result<()>;
+
+    /// Stop delivering samples
+    fn stop(&mut self) -> Result<()>;
+}
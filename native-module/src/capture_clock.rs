@@ -0,0 +1,157 @@
+// Capture Clock - monotonic capture timestamps for resampled audio
+//
+// Every capture counts samples on its device's clock, and device clocks
+// drift against each other (tens to hundreds of ppm). To line captures up
+// we map each capture's sample clock onto the shared monotonic clock
+// (Instant):
+//
+// - On every ring-buffer drain, observe (input seconds consumed, now)
+// - Fit wall = a + b * sample_time by exponentially weighted least squares
+//   (~OBSERVATION_WINDOW observations), which averages out scheduling
+//   jitter and follows clock skew without lag
+// - A sample's timestamp is the fitted wall time of its sample time
+//
+// Timestamps include the constant device + ring buffer latency, which is
+// similar for every capture and cancels out when aligning them.
+//
+// JS sees timestamps as milliseconds since a process-wide epoch
+// (monotonic_ms), so frames of different captures compare directly.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Origin of the millisecond timestamps handed to JS
+static MONOTONIC_EPOCH: OnceLock<Instant> = OnceLock::new();
+
+/// Effective number of observations in the fit (~10s at 10ms drains)
+const OBSERVATION_WINDOW: f64 = 1000.0;
+
+/// Observed sample-clock span needed before trusting the fitted skew
+const MIN_SKEW_SPAN_SECS: f64 = 1.0;
+
+/// Largest clock skew accepted from the fit (1%)
+const MAX_SKEW: f64 = 0.01;
+
+pub struct CaptureClock {
+    input_sample_rate: f64,
+    epoch: Option<Instant>,
+    /// Input frames observed so far
+    consumed: u64,
+    /// Exponentially weighted regression state (x: sample time, y: wall time)
+    weight: f64,
+    mean_x: f64,
+    mean_y: f64,
+    var_x: f64,
+    cov_xy: f64,
+    first_x: f64,
+}
+
+impl CaptureClock {
+    pub fn new(input_sample_rate: u32) -> Self {
+        Self {
+            input_sample_rate: input_sample_rate.max(1) as f64,
+            epoch: None,
+            consumed: 0,
+            weight: 0.0,
+            mean_x: 0.0,
+            mean_y: 0.0,
+            var_x: 0.0,
+            cov_xy: 0.0,
+            first_x: 0.0,
+        }
+    }
+
+    /// Record that `frames` more input frames had arrived by `now`
+    pub fn observe(&mut self, frames: usize, now: Instant) {
+        let epoch = *self.epoch.get_or_insert_with(|| {
+            now.checked_sub(Duration::from_secs_f64(frames as f64 / self.input_sample_rate))
+                .unwrap_or(now)
+        });
+        self.consumed += frames as u64;
+
+        let x = self.consumed as f64 / self.input_sample_rate;
+        let y = signed_secs(now, epoch);
+        if self.weight == 0.0 {
+            self.first_x = x;
+        }
+
+        let decay = 1.0 - 1.0 / OBSERVATION_WINDOW;
+        self.weight = decay * self.weight + 1.0;
+        let dx = x - self.mean_x;
+        self.mean_x += dx / self.weight;
+        let dy = y - self.mean_y;
+        self.mean_y += dy / self.weight;
+        self.var_x = decay * self.var_x + dx * (x - self.mean_x);
+        self.cov_xy = decay * self.cov_xy + dx * (y - self.mean_y);
+    }
+
+    /// Wall time of a point on the input sample clock (seconds since start).
+    /// None before the first observation.
+    pub fn timestamp(&self, sample_time: f64) -> Option<Instant> {
+        let epoch = self.epoch?;
+        let wall = self.mean_y + self.rate_ratio() * (sample_time - self.mean_x);
+        Some(offset(epoch, wall))
+    }
+
+    /// Wall seconds per sample-clock second (1.0 = device runs at its nominal rate)
+    pub fn rate_ratio(&self) -> f64 {
+        let span = self.consumed as f64 / self.input_sample_rate - self.first_x;
+        if span < MIN_SKEW_SPAN_SECS || self.var_x <= 0.0 {
+            return 1.0;
+        }
+        (self.cov_xy / self.var_x).clamp(1.0 - MAX_SKEW, 1.0 + MAX_SKEW)
+    }
+}
+
+/// Milliseconds from the process-wide epoch to `t`
+pub fn monotonic_ms(t: Instant) -> f64 {
+    let epoch = *MONOTONIC_EPOCH.get_or_init(Instant::now);
+    signed_secs(t, epoch) * 1000.0
+}
+
+fn signed_secs(t: Instant, epoch: Instant) -> f64 {
+    if t >= epoch {
+        (t - epoch).as_secs_f64()
+    } else {
+        -(epoch - t).as_secs_f64()
+    }
+}
+
+fn offset(epoch: Instant, secs: f64) -> Instant {
+    if secs >= 0.0 {
+        epoch + Duration::from_secs_f64(secs)
+    } else {
+        epoch.checked_sub(Duration::from_secs_f64(-secs)).unwrap_or(epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::Noise;
+
+    #[test]
+    fn test_tracks_skewed_device_despite_jitter() {
+        // Device nominally 48kHz but really running 300ppm fast,
+        // drained every ~10ms with up to +-4ms scheduling jitter
+        let true_rate = 48_000.0 * 1.0003;
+        let base = Instant::now();
+        let mut clock = CaptureClock::new(48_000);
+        let mut rng = Noise::new(51);
+        let mut delivered = 0u64;
+
+        for step in 1..=6000 {
+            let wall = step as f64 * 0.010;
+            let available = (wall * true_rate) as u64;
+            let jitter = 0.004 * rng.next().abs() as f64;
+            clock.observe((available - delivered) as usize, base + Duration::from_secs_f64(wall + jitter));
+            delivered = available;
+        }
+
+        assert!((clock.rate_ratio() - 1.0 / 1.0003).abs() < 2e-5, "ratio {}", clock.rate_ratio());
+        // Sample captured at wall time 55s sits at 55 * 1.0003 on the device clock
+        let stamped = clock.timestamp(55.0 * 1.0003).unwrap();
+        let error_ms = (signed_secs(stamped, base) - 55.0) * 1000.0;
+        assert!(error_ms.abs() < 3.0, "timestamp off by {:.2}ms", error_ms);
+    }
+}
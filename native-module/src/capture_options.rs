@@ -0,0 +1,452 @@
+// Capture Options - per-capture DSP settings from JS
+//
+// Every capture class takes an optional `CaptureOptions` object as its last
+// constructor argument. Options are validated once, in the constructor, into
+// `CaptureSettings`; each start() applies them to its PipelineConfig.
+
+use std::time::Duration;
+
+use napi::{Error, Status};
+
+use crate::agc::AgcConfig;
+use crate::audio_config::{OutputFormat, SUPPORTED_FRAME_MS, SUPPORTED_SAMPLE_RATES};
+use crate::downmix::ChannelMode;
+use crate::history::MAX_HISTORY_SECONDS;
+use crate::noise_suppression::NoiseSuppressionLevel;
+use crate::pipeline::PipelineConfig;
+use crate::silence_suppression::{
+    SilenceSuppressionConfig, ThresholdMode, ADAPTIVE_MARGIN_DB, ADAPTIVE_MAX_RMS, ADAPTIVE_MIN_RMS,
+    MAX_PREROLL,
+};
+use crate::speech_detector::SpeechDetectorKind;
+use crate::streaming_resampler::ResamplerQuality;
+use crate::vad::VadConfig;
+
+#[napi(object)]
+#[derive(Default)]
+pub struct CaptureOptions {
+    /// Output sample rate: 8000, 12000, 16000 (default), 24000, 32000, 44100 or 48000
+    pub sample_rate: Option<u32>,
+    /// Frame duration in ms: 10, 20 (default), 30, 40 or 60
+    pub frame_ms: Option<u32>,
+    /// Noise suppression strength: "off" (default), "low", "moderate" or "high"
+    pub noise_suppression: Option<String>,
+    /// Automatic gain control (off unless given). Levels are normalized, so
+    /// the silence gate's default threshold becomes at least the microphone
+    /// one (100) for every capture; silenceGate.thresholdRms still wins
+    pub agc: Option<AgcOptions>,
+    /// Resampler preset: "fast" (linear, no anti-aliasing),
+    /// "balanced" (default) or "high"
+    pub resampler_quality: Option<String>,
+    /// How multi-channel input becomes mono: "channel" (default, only
+    /// inputChannel), "average" (mean of all channels) or "loudest" (the
+    /// most energetic channel, followed as it changes; for array mics)
+    pub channel_mode: Option<String>,
+    /// Zero-based channel for channelMode "channel" (default 0; implies
+    /// "channel" when channelMode is not given)
+    pub input_channel: Option<u32>,
+    /// Frame encoding: "pcm" (default, LINEAR16 buffers) or "opus"
+    /// (one packet object per frame; needs an Opus sampleRate and frameMs,
+    /// and a build with the "opus" cargo feature)
+    pub encoding: Option<String>,
+    /// Opus target bitrate in bits/second (default 24000)
+    pub opus_bitrate: Option<u32>,
+    /// Seconds of unsuppressed output audio kept for getHistory()
+    /// (default 0 = off, max 600)
+    pub history_seconds: Option<u32>,
+    /// What the callback receives for PCM frames: "frame" (default,
+    /// `{ pcm, timestamp, sequence, kind, rms }`) or "raw" (bare LINEAR16 bytes)
+    pub callback_mode: Option<String>,
+    /// Speech start / end detection for the start() event callback
+    pub vad: Option<VadOptions>,
+    /// How the silence gate and VAD events detect speech: "rms" (default,
+    /// fixed level threshold), "spectral" (adaptive noise floor and spectral
+    /// shape; ignores music, typing and fans, keeps quiet talkers) or
+    /// "neural" (ONNX model on CPU, needs vadModelPath and a 16000 sampleRate;
+    /// only in builds with the neural-vad feature, which load ONNX Runtime
+    /// 1.20 from the system or ORT_DYLIB_PATH)
+    pub speech_detector: Option<String>,
+    /// Silero VAD v5 ONNX model file for speechDetector "neural"
+    pub vad_model_path: Option<String>,
+    /// Speech threshold of the silence gate (RMS detector)
+    pub silence_gate: Option<SilenceGateOptions>,
+}
+
+/// How frames are handed to the JS callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameEncoding {
+    /// Little-endian LINEAR16 buffers
+    Pcm,
+    /// One Opus packet plus metadata per frame
+    #[cfg(feature = "opus")]
+    Opus { bitrate: u32 },
+}
+
+/// Shape of the PCM callback argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallbackMode {
+    /// Object with the PCM buffer and frame metadata
+    Frame,
+    /// Legacy: bare LINEAR16 bytes
+    Raw,
+}
+
+#[napi(object)]
+#[derive(Default)]
+pub struct AgcOptions {
+    /// Default true when the object is given
+    pub enabled: Option<bool>,
+    /// Speech level to steer towards, RMS dBFS (default -20)
+    pub target_level_dbfs: Option<f64>,
+    /// Largest gain applied to quiet input, dB (default 30)
+    pub max_gain_db: Option<f64>,
+    /// Time constant for reducing gain, ms (default 20)
+    pub attack_ms: Option<u32>,
+    /// Time constant for increasing gain, ms (default 1000)
+    pub release_ms: Option<u32>,
+}
+
+#[napi(object)]
+#[derive(Default)]
+pub struct VadOptions {
+    /// RMS level (i16 scale) that starts speech (default 185, ~-45dBFS)
+    pub start_threshold: Option<f64>,
+    /// RMS level below which speech starts to end (default 100, ~-50dBFS)
+    pub end_threshold: Option<f64>,
+    /// Quiet time before speechEnd fires, ms (default 500)
+    pub hangover_ms: Option<u32>,
+}
+
+#[napi(object)]
+#[derive(Default)]
+pub struct SilenceGateOptions {
+    /// "fixed" (default) or "adaptive" (follows the background noise floor)
+    pub mode: Option<String>,
+    /// Fixed mode threshold, RMS i16 scale (default 100 microphone, 30
+    /// system, 100 for both with agc); used as given, also with agc
+    pub threshold_rms: Option<f64>,
+    /// Adaptive: threshold above the noise floor, dB (default 9)
+    pub margin_db: Option<f64>,
+    /// Adaptive: lowest threshold, RMS (default 30)
+    pub min_rms: Option<f64>,
+    /// Adaptive: highest threshold, RMS (default 2000)
+    pub max_rms: Option<f64>,
+    /// Suppressed audio sent ahead of a speech onset, ms (default 60, 0
+    /// disables, max 1000). Only audio after the last keepalive is sent, so
+    /// timestamps never go backwards.
+    pub preroll_ms: Option<u32>,
+}
+
+/// Validated silence gate options (None = profile default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SilenceGateSettings {
+    pub threshold_rms: Option<f32>,
+    pub mode: Option<ThresholdMode>,
+    pub preroll: Option<Duration>,
+}
+
+/// Validated capture options
+#[derive(Debug, Clone)]
+pub struct CaptureSettings {
+    pub format: OutputFormat,
+    pub noise_suppression: NoiseSuppressionLevel,
+    pub agc: Option<AgcConfig>,
+    pub resampler_quality: ResamplerQuality,
+    pub channel_mode: ChannelMode,
+    pub encoding: FrameEncoding,
+    pub callback_mode: CallbackMode,
+    pub history: Option<Duration>,
+    pub vad: VadConfig,
+    pub speech_detector: SpeechDetectorKind,
+    pub silence_gate: SilenceGateSettings,
+}
+
+impl CaptureSettings {
+    pub fn from_options(options: Option<CaptureOptions>) -> napi::Result<Self> {
+        let options = options.unwrap_or_default();
+
+        let defaults = OutputFormat::default();
+        let format = OutputFormat {
+            sample_rate: options.sample_rate.unwrap_or(defaults.sample_rate),
+            frame_ms: options.frame_ms.unwrap_or(defaults.frame_ms),
+        };
+        if !SUPPORTED_SAMPLE_RATES.contains(&format.sample_rate) {
+            return Err(Error::new(
+                Status::InvalidArg,
+                format!("Unsupported sampleRate {} (supported: {:?})", format.sample_rate, SUPPORTED_SAMPLE_RATES),
+            ));
+        }
+        if !SUPPORTED_FRAME_MS.contains(&format.frame_ms) {
+            return Err(Error::new(
+                Status::InvalidArg,
+                format!("Unsupported frameMs {} (supported: {:?})", format.frame_ms, SUPPORTED_FRAME_MS),
+            ));
+        }
+
+        let noise_suppression = match options.noise_suppression.as_deref() {
+            None => NoiseSuppressionLevel::Off,
+            Some(value) => NoiseSuppressionLevel::parse(value).ok_or_else(|| {
+                Error::new(Status::InvalidArg, format!("Unknown noiseSuppression level: {}", value))
+            })?,
+        };
+
+        let agc = match options.agc {
+            Some(agc) if agc.enabled.unwrap_or(true) => Some(agc_config(agc)?),
+            _ => None,
+        };
+
+        let resampler_quality = match options.resampler_quality.as_deref() {
+            None => ResamplerQuality::Balanced,
+            Some(value) => ResamplerQuality::parse(value).ok_or_else(|| {
+                Error::new(Status::InvalidArg, format!("Unknown resamplerQuality: {}", value))
+            })?,
+        };
+
+        let channel_mode = match (options.channel_mode.as_deref(), options.input_channel) {
+            (None | Some("channel"), None) => ChannelMode::default(),
+            (None | Some("channel"), Some(channel)) => ChannelMode::Channel(channel as usize),
+            (Some("average"), None) => ChannelMode::Average,
+            (Some("loudest"), None) => ChannelMode::Loudest,
+            (Some(mode @ ("average" | "loudest")), Some(_)) => {
+                return Err(Error::new(
+                    Status::InvalidArg,
+                    format!("inputChannel only applies to channelMode \"channel\", not \"{}\"", mode),
+                ));
+            }
+            (Some(other), _) => {
+                return Err(Error::new(Status::InvalidArg, format!("Unknown channelMode: {}", other)));
+            }
+        };
+
+        let encoding = match options.encoding.as_deref() {
+            None | Some("pcm") => FrameEncoding::Pcm,
+            Some("opus") => opus_encoding(format, options.opus_bitrate)?,
+            Some(other) => {
+                return Err(Error::new(Status::InvalidArg, format!("Unknown encoding: {}", other)));
+            }
+        };
+
+        let callback_mode = match options.callback_mode.as_deref() {
+            None | Some("frame") => CallbackMode::Frame,
+            Some("raw") => CallbackMode::Raw,
+            Some(other) => {
+                return Err(Error::new(Status::InvalidArg, format!("Unknown callbackMode: {}", other)));
+            }
+        };
+
+        let history = match options.history_seconds {
+            None | Some(0) => None,
+            Some(seconds) if seconds <= MAX_HISTORY_SECONDS => Some(Duration::from_secs(seconds as u64)),
+            Some(_) => {
+                return Err(Error::new(
+                    Status::InvalidArg,
+                    format!("historySeconds must be at most {}", MAX_HISTORY_SECONDS),
+                ));
+            }
+        };
+
+        let vad = match options.vad {
+            Some(vad) => vad_config(vad)?,
+            None => VadConfig::default(),
+        };
+
+        let speech_detector = match options.speech_detector.as_deref() {
+            None => SpeechDetectorKind::Rms,
+            Some("neural") => neural_detector(format, options.vad_model_path)?,
+            Some(value) => SpeechDetectorKind::parse(value).ok_or_else(|| {
+                Error::new(Status::InvalidArg, format!("Unknown speechDetector: {}", value))
+            })?,
+        };
+
+        let silence_gate = match options.silence_gate {
+            Some(gate) => silence_gate_settings(gate)?,
+            None => SilenceGateSettings::default(),
+        };
+
+        Ok(Self {
+            format,
+            noise_suppression,
+            agc,
+            resampler_quality,
+            channel_mode,
+            encoding,
+            callback_mode,
+            history,
+            vad,
+            speech_detector,
+            silence_gate,
+        })
+    }
+
+    /// Overlay these settings on a profile's defaults
+    pub fn apply(&self, config: PipelineConfig) -> PipelineConfig {
+        PipelineConfig {
+            format: self.format,
+            noise_suppression: self.noise_suppression,
+            agc: self.agc.clone(),
+            resampler_quality: self.resampler_quality,
+            channel_mode: self.channel_mode,
+            vad: self.vad,
+            suppression: SilenceSuppressionConfig {
+                detector: self.speech_detector.clone(),
+                speech_threshold_rms: self.speech_threshold_rms(&config.suppression),
+                threshold: self.silence_gate.mode.unwrap_or(config.suppression.threshold),
+                preroll: self.silence_gate.preroll.unwrap_or(config.suppression.preroll),
+                ..config.suppression
+            },
+            ..config
+        }
+    }
+
+    /// The caller's threshold, else the profile's (raised with AGC on:
+    /// levels are normalized, so quiet profiles no longer need a lowered one)
+    fn speech_threshold_rms(&self, profile: &SilenceSuppressionConfig) -> f32 {
+        match self.silence_gate.threshold_rms {
+            Some(threshold_rms) => threshold_rms,
+            None if self.agc.is_some() => profile.speech_threshold_rms
+                .max(SilenceSuppressionConfig::for_microphone().speech_threshold_rms),
+            None => profile.speech_threshold_rms,
+        }
+    }
+}
+
+fn agc_config(options: AgcOptions) -> napi::Result<AgcConfig> {
+    let defaults = AgcConfig::default();
+    let config = AgcConfig {
+        target_level_dbfs: options.target_level_dbfs.map_or(defaults.target_level_dbfs, |v| v as f32),
+        max_gain_db: options.max_gain_db.map_or(defaults.max_gain_db, |v| v as f32),
+        attack: options.attack_ms.map_or(defaults.attack, |ms| Duration::from_millis(ms as u64)),
+        release: options.release_ms.map_or(defaults.release, |ms| Duration::from_millis(ms as u64)),
+    };
+
+    if !(-60.0..=0.0).contains(&config.target_level_dbfs) {
+        return Err(Error::new(Status::InvalidArg, "agc.targetLevelDbfs must be between -60 and 0"));
+    }
+    if !(0.0..=60.0).contains(&config.max_gain_db) {
+        return Err(Error::new(Status::InvalidArg, "agc.maxGainDb must be between 0 and 60"));
+    }
+    Ok(config)
+}
+
+fn vad_config(options: VadOptions) -> napi::Result<VadConfig> {
+    let defaults = VadConfig::default();
+    let config = VadConfig {
+        start_threshold: options.start_threshold.map_or(defaults.start_threshold, |v| v as f32),
+        end_threshold: options.end_threshold.map_or(defaults.end_threshold, |v| v as f32),
+        hangover: options.hangover_ms.map_or(defaults.hangover, |ms| Duration::from_millis(ms as u64)),
+    };
+
+    if !(0.0..=32767.0).contains(&config.start_threshold) {
+        return Err(Error::new(Status::InvalidArg, "vad.startThreshold must be between 0 and 32767"));
+    }
+    if !(0.0..=config.start_threshold).contains(&config.end_threshold) {
+        return Err(Error::new(Status::InvalidArg, "vad.endThreshold must be between 0 and vad.startThreshold"));
+    }
+    Ok(config)
+}
+
+fn silence_gate_settings(options: SilenceGateOptions) -> napi::Result<SilenceGateSettings> {
+    let threshold_rms = options.threshold_rms.map(|v| v as f32);
+    if threshold_rms.is_some_and(|v| !(0.0..=32767.0).contains(&v)) {
+        return Err(Error::new(Status::InvalidArg, "silenceGate.thresholdRms must be between 0 and 32767"));
+    }
+
+    let mode = match options.mode.as_deref() {
+        None => None,
+        Some("fixed") => Some(ThresholdMode::Fixed),
+        Some("adaptive") => {
+            let margin_db = options.margin_db.map_or(ADAPTIVE_MARGIN_DB, |v| v as f32);
+            let min_rms = options.min_rms.map_or(ADAPTIVE_MIN_RMS, |v| v as f32);
+            let max_rms = options.max_rms.map_or(ADAPTIVE_MAX_RMS, |v| v as f32);
+            if !(0.0..=40.0).contains(&margin_db) {
+                return Err(Error::new(Status::InvalidArg, "silenceGate.marginDb must be between 0 and 40"));
+            }
+            if !(min_rms >= 0.0 && min_rms <= max_rms && max_rms <= 32767.0) {
+                return Err(Error::new(
+                    Status::InvalidArg,
+                    "silenceGate needs 0 <= minRms <= maxRms <= 32767",
+                ));
+            }
+            Some(ThresholdMode::Adaptive { margin_db, min_rms, max_rms })
+        }
+        Some(other) => {
+            return Err(Error::new(Status::InvalidArg, format!("Unknown silenceGate.mode: {}", other)));
+        }
+    };
+
+    let preroll = options.preroll_ms.map(|ms| Duration::from_millis(ms as u64));
+    if preroll.is_some_and(|p| p > MAX_PREROLL) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            format!("silenceGate.prerollMs must be at most {}", MAX_PREROLL.as_millis()),
+        ));
+    }
+    Ok(SilenceGateSettings { threshold_rms, mode, preroll })
+}
+
+#[cfg(feature = "neural-vad")]
+fn neural_detector(format: OutputFormat, model_path: Option<String>) -> napi::Result<SpeechDetectorKind> {
+    use crate::neural_vad::{SileroModel, MODEL_SAMPLE_RATE};
+
+    if format.sample_rate != MODEL_SAMPLE_RATE {
+        return Err(Error::new(
+            Status::InvalidArg,
+            format!("speechDetector \"neural\" needs sampleRate {}", MODEL_SAMPLE_RATE),
+        ));
+    }
+    let path = model_path.ok_or_else(|| {
+        Error::new(Status::InvalidArg, "speechDetector \"neural\" needs vadModelPath")
+    })?;
+    let model = SileroModel::load(std::path::Path::new(&path))
+        .map_err(|e| Error::new(Status::InvalidArg, e.to_string()))?;
+    Ok(SpeechDetectorKind::Neural(std::sync::Arc::new(model)))
+}
+
+#[cfg(not(feature = "neural-vad"))]
+fn neural_detector(_format: OutputFormat, _model_path: Option<String>) -> napi::Result<SpeechDetectorKind> {
+    Err(Error::new(Status::InvalidArg, "This build does not include the neural VAD"))
+}
+
+#[cfg(feature = "opus")]
+fn opus_encoding(format: OutputFormat, bitrate: Option<u32>) -> napi::Result<FrameEncoding> {
+    crate::opus_encoder::validate_format(format)
+        .map_err(|e| Error::new(Status::InvalidArg, e.to_string()))?;
+    let bitrate = bitrate.unwrap_or(crate::opus_encoder::DEFAULT_OPUS_BITRATE);
+    if !(6_000..=510_000).contains(&bitrate) {
+        return Err(Error::new(Status::InvalidArg, "opusBitrate must be between 6000 and 510000"));
+    }
+    Ok(FrameEncoding::Opus { bitrate })
+}
+
+#[cfg(not(feature = "opus"))]
+fn opus_encoding(_format: OutputFormat, _bitrate: Option<u32>) -> napi::Result<FrameEncoding> {
+    Err(Error::new(Status::InvalidArg, "This build does not include Opus support (build with --features opus)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system_threshold(options: CaptureOptions) -> f32 {
+        let settings = CaptureSettings::from_options(Some(options)).unwrap();
+        settings.apply(PipelineConfig::for_system_audio()).suppression.speech_threshold_rms
+    }
+
+    #[test]
+    fn test_agc_raises_only_the_default_threshold() {
+        let agc = || Some(AgcOptions::default());
+        let gate = |threshold_rms: f64| Some(SilenceGateOptions {
+            threshold_rms: Some(threshold_rms),
+            ..Default::default()
+        });
+
+        assert_eq!(system_threshold(CaptureOptions::default()), 30.0);
+        assert_eq!(system_threshold(CaptureOptions { agc: agc(), ..Default::default() }), 100.0);
+        assert_eq!(system_threshold(CaptureOptions { silence_gate: gate(40.0), ..Default::default() }), 40.0);
+        // An explicit threshold is kept with AGC on
+        assert_eq!(
+            system_threshold(CaptureOptions { agc: agc(), silence_gate: gate(40.0), ..Default::default() }),
+            40.0
+        );
+    }
+}
@@ -0,0 +1,250 @@
+// Capture Status - typed errors and lifecycle state for JS
+//
+// Backends and the DSP thread report problems through a StatusReporter
+// instead of only logging them. Each capture owns one reporter for its whole
+// life (across start / stop); the JS status callback given to start()
+// receives every event as `{ code, state, message, fatal, timestamp }`,
+// plus `device` for deviceChanged.
+//
+// Lifecycle:
+//   idle -> starting -> running <-> stalled -> stopped
+//   any state -> failed (fatal error; start() again to recover)
+//
+// Reports can come from device callbacks. They take a short lock and queue
+// the event on a non-blocking threadsafe function, so only rare paths
+// (errors, state changes) may report.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Lifecycle state of a capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureState {
+    Idle,
+    Starting,
+    Running,
+    /// Running, but the source has stopped delivering audio
+    Stalled,
+    Stopped,
+    /// A fatal error stopped the audio; start() again to recover
+    Failed,
+}
+
+impl CaptureState {
+    const ALL: [CaptureState; 6] = [
+        CaptureState::Idle,
+        CaptureState::Starting,
+        CaptureState::Running,
+        CaptureState::Stalled,
+        CaptureState::Stopped,
+        CaptureState::Failed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaptureState::Idle => "idle",
+            CaptureState::Starting => "starting",
+            CaptureState::Running => "running",
+            CaptureState::Stalled => "stalled",
+            CaptureState::Stopped => "stopped",
+            CaptureState::Failed => "failed",
+        }
+    }
+
+    fn from_index(index: u8) -> Self {
+        Self::ALL.get(index as usize).copied().unwrap_or(CaptureState::Idle)
+    }
+}
+
+/// What a status event is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusCode {
+    /// The lifecycle state changed (see the event's state)
+    StateChanged,
+    /// The device was unplugged or disabled
+    DeviceLost,
+    /// The OS refused access (microphone / screen recording permission)
+    PermissionDenied,
+    /// Input samples were dropped because the DSP thread fell behind
+    BufferOverflow,
+    /// The requested device or backend failed and another one is used
+    BackendFallback,
+    /// No audio arrived from the source for a while
+    StreamStalled,
+    /// Any other backend error
+    StreamError,
+    /// The capture moved to another device (default changed, device lost or back)
+    DeviceChanged,
+    /// Frames could not be encoded and were dropped (their sequence numbers are skipped)
+    EncodeFailed,
+}
+
+impl StatusCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatusCode::StateChanged => "stateChanged",
+            StatusCode::DeviceLost => "deviceLost",
+            StatusCode::PermissionDenied => "permissionDenied",
+            StatusCode::BufferOverflow => "bufferOverflow",
+            StatusCode::BackendFallback => "backendFallback",
+            StatusCode::StreamStalled => "streamStalled",
+            StatusCode::StreamError => "streamError",
+            StatusCode::DeviceChanged => "deviceChanged",
+            StatusCode::EncodeFailed => "encodeFailed",
+        }
+    }
+
+    /// Best guess for a backend error that only comes as text
+    pub fn classify(message: &str) -> Self {
+        let message = message.to_lowercase();
+        let mentions = |words: &[&str]| words.iter().any(|w| message.contains(w));
+        if mentions(&["permission", "denied", "not authorized", "not permitted"]) {
+            StatusCode::PermissionDenied
+        } else if mentions(&["not found", "not available", "disconnected", "unplugged", "invalidated"]) {
+            StatusCode::DeviceLost
+        } else {
+            StatusCode::StreamError
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusEvent {
+    pub code: StatusCode,
+    /// State after the event
+    pub state: CaptureState,
+    pub message: String,
+    /// The capture stopped delivering audio because of this event
+    pub fatal: bool,
+    /// Device now captured from (deviceChanged only)
+    pub device: Option<String>,
+    pub timestamp: Instant,
+}
+
+/// Receives every status event of a capture
+pub type StatusSink = Box<dyn FnMut(StatusEvent) + Send>;
+
+struct StatusInner {
+    state: AtomicU8,
+    sink: Mutex<Option<StatusSink>>,
+}
+
+/// Cloneable handle shared by a capture, its source and its DSP thread
+#[derive(Clone)]
+pub struct StatusReporter {
+    inner: Arc<StatusInner>,
+}
+
+impl Default for StatusReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatusReporter {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(StatusInner {
+                state: AtomicU8::new(CaptureState::Idle as u8),
+                sink: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Replace the JS callback (None keeps tracking state silently)
+    pub fn set_sink(&self, sink: Option<StatusSink>) {
+        *self.inner.sink.lock().unwrap_or_else(|e| e.into_inner()) = sink;
+    }
+
+    pub fn state(&self) -> CaptureState {
+        CaptureState::from_index(self.inner.state.load(Ordering::Acquire))
+    }
+
+    /// Move to `state`, reporting StateChanged if it differs
+    pub fn set_state(&self, state: CaptureState, message: impl Into<String>) {
+        let previous = self.inner.state.swap(state as u8, Ordering::AcqRel);
+        if previous != state as u8 {
+            self.send(StatusCode::StateChanged, state, message.into(), false);
+        }
+    }
+
+    /// The capture now records from `device`
+    pub fn device_changed(&self, device: &str, message: impl Into<String>) {
+        self.send_event(StatusEvent {
+            code: StatusCode::DeviceChanged,
+            state: self.state(),
+            message: message.into(),
+            fatal: false,
+            device: Some(device.to_string()),
+            timestamp: Instant::now(),
+        });
+    }
+
+    /// Move from `from` to `to` only if the capture is still in `from`
+    /// (e.g. a stall ending must not undo a failure)
+    pub fn transition(&self, from: CaptureState, to: CaptureState, message: impl Into<String>) -> bool {
+        let moved = self.inner.state
+            .compare_exchange(from as u8, to as u8, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+        if moved {
+            self.send(StatusCode::StateChanged, to, message.into(), false);
+        }
+        moved
+    }
+
+    /// A problem the capture keeps running through
+    pub fn report(&self, code: StatusCode, message: impl Into<String>) {
+        self.send(code, self.state(), message.into(), false);
+    }
+
+    /// A problem that stopped the audio: the capture moves to Failed
+    pub fn fail(&self, code: StatusCode, message: impl Into<String>) {
+        self.inner.state.store(CaptureState::Failed as u8, Ordering::Release);
+        self.send(code, CaptureState::Failed, message.into(), true);
+    }
+
+    fn send(&self, code: StatusCode, state: CaptureState, message: String, fatal: bool) {
+        self.send_event(StatusEvent { code, state, message, fatal, device: None, timestamp: Instant::now() });
+    }
+
+    fn send_event(&self, event: StatusEvent) {
+        let mut sink = self.inner.sink.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sink) = sink.as_mut() {
+            sink(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle_and_failures() {
+        let reporter = StatusReporter::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        reporter.set_sink(Some(Box::new(move |event: StatusEvent| tx.send(event).unwrap())));
+
+        reporter.set_state(CaptureState::Starting, "");
+        reporter.set_state(CaptureState::Running, "");
+        reporter.set_state(CaptureState::Running, "");
+        reporter.report(StatusCode::BufferOverflow, "480 samples dropped");
+        reporter.fail(StatusCode::DeviceLost, "unplugged");
+        // A stall ending after the failure keeps the capture failed
+        assert!(!reporter.transition(CaptureState::Stalled, CaptureState::Running, ""));
+
+        let events: Vec<_> = rx.try_iter().map(|e| (e.code, e.state, e.fatal)).collect();
+        assert_eq!(events, [
+            (StatusCode::StateChanged, CaptureState::Starting, false),
+            (StatusCode::StateChanged, CaptureState::Running, false),
+            (StatusCode::BufferOverflow, CaptureState::Running, false),
+            (StatusCode::DeviceLost, CaptureState::Failed, true),
+        ]);
+        assert_eq!(reporter.state(), CaptureState::Failed);
+
+        assert_eq!(StatusCode::classify("ScreenCaptureKit access denied"), StatusCode::PermissionDenied);
+        assert_eq!(StatusCode::classify("Input device not found: USB Mic"), StatusCode::DeviceLost);
+        assert_eq!(StatusCode::classify("Failed to start stream"), StatusCode::StreamError);
+    }
+}
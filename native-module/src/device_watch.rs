@@ -0,0 +1,958 @@
+// Device Watch - follow device changes without restarting the capture
+//
+// When the default device changes (AirPods connected) or the bound device
+// disappears (dock unplugged), the OS stream keeps recording the old device
+// or dies silently. A DeviceSupervisor per capture polls its DeviceHost and,
+// while capturing, opens the new device and hands it to the running
+// pipeline through a SourceSwitch: DSP state, resampler and callbacks stay.
+//
+// Which device is recorded (DeviceWatcher):
+// - No device requested: the default device, following its changes
+// - A device requested: that device while present, else the default;
+//   back to the requested device once it returns
+//
+// Sources are created, started and dropped on the supervisor thread only
+// (cpal streams must stay on the thread that built them). JS is told
+// through the status callback (code "deviceChanged"). A device that fails
+// to open is reported once and retried with a growing delay.
+//
+// Independently of any capture, a DeviceMonitor polls the input and output
+// lists and reports devices added / removed and default changes, with the
+// details a device picker needs (DeviceDescription). Details are only
+// queried when the cheap id / default snapshot changed.
+//
+// Enumeration is not free (a full cpal query, pactl processes on Linux), so
+// every poller of one device kind reads through a SharedDeviceHost: the id
+// / default snapshot is refreshed at most once per poll interval, however
+// many captures and monitors are running.
+
+use anyhow::{anyhow, Result};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio_source::AudioSource;
+use crate::capture_status::{CaptureState, StatusCode, StatusReporter};
+use crate::pipeline::{CapturePipeline, OutputFrame, PipelineConfig, SourceHandoff, SourceSwitch};
+
+/// How often a running capture checks its devices
+#[cfg(not(target_os = "linux"))]
+pub const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often a running capture checks its devices (each check runs pactl)
+#[cfg(target_os = "linux")]
+pub const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest wait before retrying a device that failed to open
+/// (the wait starts at the poll interval and doubles per failure)
+const MAX_OPEN_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// What a device is and supports, for device pickers
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceDescription {
+    pub id: String,
+    pub name: String,
+    /// The current system default device
+    pub is_default: bool,
+    /// Sample rates the device accepts, ascending
+    pub sample_rates: Vec<u32>,
+    /// Most channels the device offers
+    pub channels: u16,
+    /// Native sample formats, e.g. "f32", "i16"
+    pub sample_formats: Vec<String>,
+    /// Audio API the device is used through, e.g. "CoreAudio", "WASAPI"
+    pub host_api: String,
+}
+
+/// The devices of one kind (inputs or outputs) on a platform
+pub trait DeviceHost: Send {
+    /// Id of the current default device
+    fn default_device(&self) -> Option<String>;
+
+    /// Ids of the devices present right now
+    fn devices(&self) -> Result<Vec<String>>;
+
+    /// Every device present, with its capabilities (slower than `devices`)
+    fn describe(&self) -> Result<Vec<DeviceDescription>>;
+
+    /// Create (not start) a source recording `device`
+    fn open(&mut self, device: &str, status: &StatusReporter) -> Result<Box<dyn AudioSource>>;
+
+    /// The id `devices` lists for `id`, when `open` accepts other spellings
+    /// of a device (e.g. a PulseAudio monitor source for its sink)
+    fn canonical_id(&self, id: &str) -> String {
+        id.to_string()
+    }
+}
+
+/// Device ids and default of one host, as last enumerated
+pub struct DeviceCache {
+    max_age: Duration,
+    snapshot: Mutex<Option<DeviceSnapshot>>,
+}
+
+struct DeviceSnapshot {
+    taken: Instant,
+    devices: Vec<String>,
+    default: Option<String>,
+}
+
+impl DeviceCache {
+    /// Snapshots are reused for up to `max_age`
+    pub fn new(max_age: Duration) -> Self {
+        Self { max_age, snapshot: Mutex::new(None) }
+    }
+}
+
+/// A DeviceHost whose id / default queries go through a shared DeviceCache
+pub struct SharedDeviceHost<H> {
+    host: H,
+    cache: Arc<DeviceCache>,
+}
+
+impl<H: DeviceHost> SharedDeviceHost<H> {
+    pub fn new(host: H, cache: Arc<DeviceCache>) -> Self {
+        Self { host, cache }
+    }
+
+    /// Ids and default, enumerated again once the snapshot is too old
+    fn snapshot(&self) -> Result<(Vec<String>, Option<String>)> {
+        let mut snapshot = self.cache.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        match snapshot.as_ref() {
+            Some(taken) if taken.taken.elapsed() < self.cache.max_age => {
+                Ok((taken.devices.clone(), taken.default.clone()))
+            }
+            _ => {
+                // Errors are not cached: the next poll asks again
+                let devices = self.host.devices()?;
+                let default = self.host.default_device();
+                *snapshot = Some(DeviceSnapshot {
+                    taken: Instant::now(),
+                    devices: devices.clone(),
+                    default: default.clone(),
+                });
+                Ok((devices, default))
+            }
+        }
+    }
+}
+
+impl<H: DeviceHost> DeviceHost for SharedDeviceHost<H> {
+    fn default_device(&self) -> Option<String> {
+        self.snapshot().ok().and_then(|(_, default)| default)
+    }
+
+    fn devices(&self) -> Result<Vec<String>> {
+        self.snapshot().map(|(devices, _)| devices)
+    }
+
+    fn describe(&self) -> Result<Vec<DeviceDescription>> {
+        self.host.describe()
+    }
+
+    fn open(&mut self, device: &str, status: &StatusReporter) -> Result<Box<dyn AudioSource>> {
+        self.host.open(device, status)
+    }
+
+    fn canonical_id(&self, id: &str) -> String {
+        self.host.canonical_id(id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeReason {
+    /// Following the default device
+    DefaultChanged,
+    /// The recorded device disappeared
+    DeviceLost,
+    /// The requested device is back
+    DeviceReturned,
+}
+
+impl ChangeReason {
+    fn describe(&self) -> &'static str {
+        match self {
+            ChangeReason::DefaultChanged => "Default device changed",
+            ChangeReason::DeviceLost => "Device disconnected",
+            ChangeReason::DeviceReturned => "Requested device reconnected",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceChange {
+    pub device: String,
+    pub reason: ChangeReason,
+}
+
+/// Decides which device a capture should record
+pub struct DeviceWatcher {
+    requested: Option<String>,
+    bound: Option<String>,
+}
+
+impl DeviceWatcher {
+    /// `requested`: a device id, or None to follow the default device
+    pub fn new(requested: Option<String>) -> Self {
+        let requested = requested.filter(|id| !id.is_empty() && id != "default");
+        Self { requested, bound: None }
+    }
+
+    /// Device to record from now (None: no device at all)
+    pub fn target(&self, host: &dyn DeviceHost) -> Option<String> {
+        let devices = host.devices().unwrap_or_default();
+        self.pick(&devices, host.default_device())
+    }
+
+    /// Record that `device` is the one being captured
+    pub fn bind(&mut self, device: String) {
+        self.bound = Some(device);
+    }
+
+    /// The change to follow, if the bound device is no longer the right one
+    pub fn poll(&self, host: &dyn DeviceHost) -> Option<DeviceChange> {
+        // An enumeration error is not a lost device
+        let devices = host.devices().ok()?;
+        let device = self.pick(&devices, host.default_device())?;
+        if self.bound.as_ref() == Some(&device) {
+            return None;
+        }
+
+        let reason = match &self.bound {
+            Some(bound) if !devices.contains(bound) => ChangeReason::DeviceLost,
+            _ if self.requested.as_ref() == Some(&device) => ChangeReason::DeviceReturned,
+            _ => ChangeReason::DefaultChanged,
+        };
+        Some(DeviceChange { device, reason })
+    }
+
+    fn pick(&self, devices: &[String], default: Option<String>) -> Option<String> {
+        match &self.requested {
+            Some(id) if devices.contains(id) => Some(id.clone()),
+            _ => default,
+        }
+    }
+}
+
+enum Command {
+    Start(mpsc::Sender<Result<SourceHandoff>>),
+    Follow(SourceSwitch),
+    Stop(mpsc::Sender<()>),
+}
+
+/// Owns the sources of one capture on a dedicated thread
+pub struct DeviceSupervisor {
+    commands: Option<mpsc::Sender<Command>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl DeviceSupervisor {
+    /// `requested`: a device id, or None to follow the default device.
+    /// `status` receives deviceChanged and open failures.
+    pub fn spawn<H: DeviceHost + 'static>(
+        name: &'static str,
+        host: H,
+        requested: Option<String>,
+        status: StatusReporter,
+        poll_interval: Duration,
+    ) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        let requested = requested.map(|id| host.canonical_id(&id));
+        let thread = thread::spawn(move || {
+            let mut supervisor = Supervisor {
+                name,
+                host,
+                watcher: DeviceWatcher::new(requested),
+                status,
+                poll_interval,
+                source: None,
+                switch: None,
+                failed_open: None,
+            };
+            supervisor.run(receiver);
+        });
+        Self {
+            commands: Some(commands),
+            thread: Some(thread),
+        }
+    }
+
+    /// Start the current device and a pipeline that follows device changes
+    pub fn start_pipeline<F>(&self, config: PipelineConfig, emit: F) -> Result<CapturePipeline>
+    where
+        F: FnMut(OutputFrame) + Send + 'static,
+    {
+        let handoff = self.request(Command::Start)??;
+        let pipeline = CapturePipeline::spawn(handoff, config, emit);
+        self.send(Command::Follow(pipeline.source_switch()))?;
+        Ok(pipeline)
+    }
+
+    /// Stop and release the device (after the pipeline stopped)
+    pub fn stop(&self) {
+        let _ = self.request(Command::Stop);
+    }
+
+    fn send(&self, command: Command) -> Result<()> {
+        self.commands.as_ref()
+            .and_then(|commands| commands.send(command).ok())
+            .ok_or_else(|| anyhow!("Device supervisor is not running"))
+    }
+
+    fn request<T>(&self, command: impl FnOnce(mpsc::Sender<T>) -> Command) -> Result<T> {
+        let (reply, response) = mpsc::channel();
+        self.send(command(reply))?;
+        response.recv().map_err(|_| anyhow!("Device supervisor is not running"))
+    }
+}
+
+impl Drop for DeviceSupervisor {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread
+        self.commands = None;
+        if let Some(handle) = self.thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct Supervisor<H> {
+    name: &'static str,
+    host: H,
+    watcher: DeviceWatcher,
+    status: StatusReporter,
+    poll_interval: Duration,
+    source: Option<Box<dyn AudioSource>>,
+    /// Set while a pipeline is running on `source`
+    switch: Option<SourceSwitch>,
+    /// Last device that failed to open, retried after a backoff
+    failed_open: Option<FailedOpen>,
+}
+
+/// A device that failed to open (busy right after plugging in, ...)
+struct FailedOpen {
+    device: String,
+    failures: u32,
+    retry_at: Instant,
+}
+
+impl<H: DeviceHost> Supervisor<H> {
+    fn run(&mut self, commands: mpsc::Receiver<Command>) {
+        loop {
+            // Devices only matter while capturing
+            let command = if self.switch.is_some() {
+                match commands.recv_timeout(self.poll_interval) {
+                    Ok(command) => Some(command),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            } else {
+                match commands.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => break,
+                }
+            };
+
+            match command {
+                Some(Command::Start(reply)) => {
+                    let _ = reply.send(self.start());
+                }
+                Some(Command::Follow(switch)) => self.switch = Some(switch),
+                Some(Command::Stop(reply)) => {
+                    self.stop();
+                    let _ = reply.send(());
+                }
+                None => self.poll(),
+            }
+        }
+        self.stop();
+    }
+
+    fn start(&mut self) -> Result<SourceHandoff> {
+        self.stop();
+        let device = self.watcher.target(&self.host)
+            .ok_or_else(|| anyhow!("No device available"))?;
+        if let Some(requested) = self.watcher.requested.as_ref().filter(|&id| *id != device) {
+            self.status.report(
+                StatusCode::BackendFallback,
+                format!("Device {} is not available, using {}", requested, device),
+            );
+        }
+
+        let (source, handoff, device) = match self.open(&device) {
+            Ok((source, handoff)) => (source, handoff, device),
+            Err(e) => match self.host.default_device().filter(|default| *default != device) {
+                Some(default) => {
+                    println!("[{}] Failed: {}. Trying default...", self.name, e);
+                    self.status.report(
+                        StatusCode::BackendFallback,
+                        format!("Device failed ({}), using the default device", e),
+                    );
+                    let (source, handoff) = self.open(&default)?;
+                    (source, handoff, default)
+                }
+                None => return Err(e),
+            },
+        };
+
+        println!("[{}] Capturing from {}", self.name, device);
+        self.source = Some(source);
+        self.watcher.bind(device);
+        self.failed_open = None;
+        Ok(handoff)
+    }
+
+    /// Follow a device change while the pipeline runs
+    fn poll(&mut self) {
+        let Some(change) = self.watcher.poll(&self.host) else { return };
+        let now = Instant::now();
+        let failures = match &self.failed_open {
+            Some(failed) if failed.device == change.device => {
+                if now < failed.retry_at {
+                    return;
+                }
+                failed.failures
+            }
+            _ => 0,
+        };
+        println!("[{}] {}: switching to {}", self.name, change.reason.describe(), change.device);
+
+        match self.open(&change.device) {
+            Ok((source, handoff)) => {
+                if let Some(switch) = &self.switch {
+                    switch.switch(handoff);
+                }
+                if let Some(mut previous) = self.source.replace(source) {
+                    let _ = previous.stop();
+                }
+                self.watcher.bind(change.device.clone());
+                self.failed_open = None;
+
+                // Audio flows again after the old device failed
+                self.status.transition(CaptureState::Failed, CaptureState::Running, "Recovered on another device");
+                self.status.device_changed(
+                    &change.device,
+                    format!("{}, capturing from {}", change.reason.describe(), change.device),
+                );
+            }
+            Err(e) => {
+                eprintln!("[{}] Failed to open {}: {}", self.name, change.device, e);
+                // Reported once per device; retries only log
+                if failures == 0 {
+                    let message = format!("Failed to open {}: {}", change.device, e);
+                    self.status.report(StatusCode::classify(&message), message);
+                }
+                let delay = self.poll_interval
+                    .saturating_mul(1 << failures.min(16))
+                    .min(MAX_OPEN_RETRY_DELAY);
+                self.failed_open = Some(FailedOpen {
+                    device: change.device,
+                    failures: failures + 1,
+                    retry_at: now + delay,
+                });
+            }
+        }
+    }
+
+    fn open(&mut self, device: &str) -> Result<(Box<dyn AudioSource>, SourceHandoff)> {
+        let mut source = self.host.open(device, &self.status)?;
+        let handoff = SourceHandoff::start(source.as_mut())?;
+        Ok((source, handoff))
+    }
+
+    fn stop(&mut self) {
+        self.switch = None;
+        if let Some(mut source) = self.source.take() {
+            let _ = source.stop();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceDirection {
+    Input,
+    Output,
+}
+
+impl DeviceDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceDirection::Input => "input",
+            DeviceDirection::Output => "output",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceEventKind {
+    Added,
+    Removed,
+    /// The event's device is the new default
+    DefaultChanged,
+}
+
+impl DeviceEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceEventKind::Added => "added",
+            DeviceEventKind::Removed => "removed",
+            DeviceEventKind::DefaultChanged => "defaultChanged",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceEvent {
+    pub kind: DeviceEventKind,
+    pub direction: DeviceDirection,
+    pub device: DeviceDescription,
+}
+
+/// Turns one host's device list into added / removed / default events
+pub struct DeviceListWatcher<H> {
+    direction: DeviceDirection,
+    host: H,
+    /// Cheap snapshot compared on every poll
+    ids: Vec<String>,
+    default: Option<String>,
+    /// Devices as last described
+    known: Vec<DeviceDescription>,
+}
+
+impl<H: DeviceHost> DeviceListWatcher<H> {
+    /// Starts from the devices present now (they are not reported)
+    pub fn new(direction: DeviceDirection, host: H) -> Self {
+        let mut watcher = Self {
+            direction,
+            host,
+            ids: Vec::new(),
+            default: None,
+            known: Vec::new(),
+        };
+        watcher.poll();
+        watcher
+    }
+
+    /// Changes since the last poll
+    pub fn poll(&mut self) -> Vec<DeviceEvent> {
+        let Ok(ids) = self.host.devices() else { return Vec::new() };
+        let default = self.host.default_device();
+        if ids == self.ids && default == self.default {
+            return Vec::new();
+        }
+        let Ok(described) = self.host.describe() else { return Vec::new() };
+        self.ids = ids;
+        self.default = default;
+
+        let event = |kind, device: &DeviceDescription| DeviceEvent {
+            kind,
+            direction: self.direction,
+            device: device.clone(),
+        };
+        let listed = |list: &[DeviceDescription], id: &str| list.iter().any(|d| d.id == id);
+
+        let mut events: Vec<DeviceEvent> = self.known.iter()
+            .filter(|device| !listed(&described, &device.id))
+            .map(|device| event(DeviceEventKind::Removed, device))
+            .collect();
+        events.extend(described.iter()
+            .filter(|device| !listed(&self.known, &device.id))
+            .map(|device| event(DeviceEventKind::Added, device)));
+
+        let previous_default = self.known.iter().find(|d| d.is_default).map(|d| d.id.as_str());
+        if let Some(device) = described.iter().find(|d| d.is_default) {
+            if previous_default != Some(device.id.as_str()) {
+                events.push(event(DeviceEventKind::DefaultChanged, device));
+            }
+        }
+
+        self.known = described;
+        events
+    }
+}
+
+/// Receives device list changes (monitor thread)
+pub type DeviceEventSink = Box<dyn FnMut(DeviceEvent) + Send>;
+
+/// Polls the input and output device lists on a thread of its own
+pub struct DeviceMonitor {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl DeviceMonitor {
+    pub fn spawn<I, O>(inputs: I, outputs: O, poll_interval: Duration, mut sink: DeviceEventSink) -> Self
+    where
+        I: DeviceHost + 'static,
+        O: DeviceHost + 'static,
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            let mut inputs = DeviceListWatcher::new(DeviceDirection::Input, inputs);
+            let mut outputs = DeviceListWatcher::new(DeviceDirection::Output, outputs);
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(poll_interval) {
+                for event in inputs.poll().into_iter().chain(outputs.poll()) {
+                    sink(event);
+                }
+            }
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for DeviceMonitor {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread
+        self.stop = None;
+        if let Some(handle) = self.thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture_status::StatusEvent;
+    use crate::stats::OverflowCounter;
+    use crate::wakeup::DataNotifier;
+    use ringbuf::traits::{Producer, Split};
+    use ringbuf::{HeapCons, HeapProd, HeapRb};
+
+    struct MockSource {
+        sample_rate: u32,
+        consumer: Option<HeapCons<f32>>,
+        notifier: DataNotifier,
+    }
+
+    impl AudioSource for MockSource {
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn channels(&self) -> u16 {
+            1
+        }
+
+        fn take_consumer(&mut self) -> Option<HeapCons<f32>> {
+            self.consumer.take()
+        }
+
+        fn data_notifier(&self) -> DataNotifier {
+            self.notifier.clone()
+        }
+
+        fn overflow_counter(&self) -> OverflowCounter {
+            OverflowCounter::new()
+        }
+
+        fn start(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct MockDevices {
+        default: Option<String>,
+        /// (id, sample rate)
+        present: Vec<(String, u32)>,
+        /// Producers of the opened sources, newest last
+        opened: Vec<(String, HeapProd<f32>, DataNotifier)>,
+        /// Opens that fail before the device works
+        busy_opens: u32,
+        /// Calls to `devices`
+        enumerations: u32,
+    }
+
+    /// Device list the test changes while the supervisor polls it
+    #[derive(Clone, Default)]
+    struct MockHost {
+        devices: Arc<Mutex<MockDevices>>,
+    }
+
+    impl MockHost {
+        fn plug(&self, id: &str, sample_rate: u32, make_default: bool) {
+            let mut devices = self.devices.lock().unwrap();
+            devices.present.push((id.to_string(), sample_rate));
+            if make_default {
+                devices.default = Some(id.to_string());
+            }
+        }
+
+        fn unplug(&self, id: &str) {
+            let mut devices = self.devices.lock().unwrap();
+            devices.present.retain(|(present, _)| present != id);
+            if devices.default.as_deref() == Some(id) {
+                devices.default = devices.present.first().map(|(id, _)| id.clone());
+            }
+        }
+
+        fn opened(&self) -> Vec<String> {
+            self.devices.lock().unwrap().opened.iter().map(|(id, _, _)| id.clone()).collect()
+        }
+
+        /// Play `samples` on the newest source opened on `id`
+        fn feed(&self, id: &str, samples: &[f32]) {
+            let mut devices = self.devices.lock().unwrap();
+            let (_, producer, notifier) = devices.opened.iter_mut().rev()
+                .find(|(opened, _, _)| opened == id)
+                .unwrap();
+            assert_eq!(producer.push_slice(samples), samples.len());
+            notifier.notify();
+        }
+    }
+
+    impl DeviceHost for MockHost {
+        fn default_device(&self) -> Option<String> {
+            self.devices.lock().unwrap().default.clone()
+        }
+
+        fn devices(&self) -> Result<Vec<String>> {
+            let mut devices = self.devices.lock().unwrap();
+            devices.enumerations += 1;
+            Ok(devices.present.iter().map(|(id, _)| id.clone()).collect())
+        }
+
+        fn describe(&self) -> Result<Vec<DeviceDescription>> {
+            let devices = self.devices.lock().unwrap();
+            Ok(devices.present.iter()
+                .map(|(id, rate)| DeviceDescription {
+                    id: id.clone(),
+                    name: id.clone(),
+                    is_default: devices.default.as_ref() == Some(id),
+                    sample_rates: vec![*rate],
+                    channels: 1,
+                    sample_formats: vec!["f32".to_string()],
+                    host_api: "Mock".to_string(),
+                })
+                .collect())
+        }
+
+        /// Like the PulseAudio host: "<sink>.monitor" records "<sink>"
+        fn canonical_id(&self, id: &str) -> String {
+            id.strip_suffix(".monitor").unwrap_or(id).to_string()
+        }
+
+        fn open(&mut self, device: &str, _status: &StatusReporter) -> Result<Box<dyn AudioSource>> {
+            let mut devices = self.devices.lock().unwrap();
+            let sample_rate = devices.present.iter()
+                .find(|(id, _)| id == device)
+                .map(|&(_, rate)| rate)
+                .ok_or_else(|| anyhow!("Input device not found: {}", device))?;
+            if devices.busy_opens > 0 {
+                devices.busy_opens -= 1;
+                return Err(anyhow!("Device busy: {}", device));
+            }
+            let (producer, consumer) = HeapRb::<f32>::new(48_000).split();
+            let notifier = DataNotifier::new();
+            devices.opened.push((device.to_string(), producer, notifier.clone()));
+            Ok(Box::new(MockSource { sample_rate, consumer: Some(consumer), notifier }))
+        }
+    }
+
+    fn change(device: &str, reason: ChangeReason) -> Option<DeviceChange> {
+        Some(DeviceChange { device: device.to_string(), reason })
+    }
+
+    #[test]
+    fn test_watcher_follows_default_and_requested_device() {
+        let host = MockHost::default();
+        host.plug("Built-in", 48_000, true);
+        host.plug("USB Mic", 48_000, false);
+
+        // Following the default device
+        let mut watcher = DeviceWatcher::new(None);
+        watcher.bind(watcher.target(&host).unwrap());
+        assert_eq!(watcher.poll(&host), None);
+        host.plug("AirPods", 24_000, true);
+        assert_eq!(watcher.poll(&host), change("AirPods", ChangeReason::DefaultChanged));
+
+        // A requested device: the default while it is gone, back once it returns
+        let mut watcher = DeviceWatcher::new(Some("USB Mic".to_string()));
+        watcher.bind(watcher.target(&host).unwrap());
+        assert_eq!(watcher.bound.as_deref(), Some("USB Mic"));
+        host.unplug("USB Mic");
+        assert_eq!(watcher.poll(&host), change("AirPods", ChangeReason::DeviceLost));
+        watcher.bind("AirPods".to_string());
+        host.unplug("AirPods");
+        assert_eq!(watcher.poll(&host), change("Built-in", ChangeReason::DeviceLost));
+        watcher.bind("Built-in".to_string());
+        host.plug("USB Mic", 48_000, false);
+        assert_eq!(watcher.poll(&host), change("USB Mic", ChangeReason::DeviceReturned));
+    }
+
+    #[test]
+    fn test_supervisor_switches_device_under_running_pipeline() {
+        let host = MockHost::default();
+        host.plug("Built-in", 16_000, true);
+
+        let status = StatusReporter::new();
+        let (status_tx, status_rx) = mpsc::channel();
+        status.set_sink(Some(Box::new(move |event: StatusEvent| {
+            let _ = status_tx.send((event.code, event.device));
+        })));
+        status.set_state(CaptureState::Running, "");
+
+        let supervisor = DeviceSupervisor::spawn("Test", host.clone(), None, status.clone(), Duration::from_millis(10));
+        let config = PipelineConfig { status: status.clone(), ..PipelineConfig::for_microphone() };
+        let stats = config.stats.clone();
+        let (frame_tx, frames) = mpsc::channel();
+        let mut pipeline = supervisor.start_pipeline(config, move |frame| {
+            let _ = frame_tx.send(frame);
+        }).unwrap();
+
+        let tone = |rate: u32| -> Vec<f32> {
+            (0..rate as usize / 2)
+                .map(|i| 0.3 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / rate as f32).sin())
+                .collect()
+        };
+        let receive = |count: usize| -> Vec<OutputFrame> {
+            (0..count).map(|_| frames.recv_timeout(Duration::from_secs(5)).unwrap()).collect()
+        };
+
+        // 500ms on the built-in microphone
+        host.feed("Built-in", &tone(16_000));
+        let mut received = receive(15);
+
+        // The old device dies, AirPods become the default
+        status.fail(StatusCode::DeviceLost, "The microphone is no longer available");
+        host.plug("AirPods", 48_000, true);
+        let device = loop {
+            match status_rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+                (StatusCode::DeviceChanged, device) => break device,
+                _ => continue,
+            }
+        };
+        assert_eq!(device.as_deref(), Some("AirPods"));
+        assert_eq!(status.state(), CaptureState::Running);
+
+        // Same pipeline, callback and sequence; new device rate
+        host.feed("AirPods", &tone(48_000));
+        received.extend(receive(14));
+        assert!(received.iter().enumerate().all(|(i, f)| f.sequence == i as u64));
+        assert_eq!(stats.snapshot().input_sample_rate, 48_000);
+
+        pipeline.stop();
+        supervisor.stop();
+        assert_eq!(host.opened(), ["Built-in", "AirPods"]);
+    }
+
+    #[test]
+    fn test_supervisor_retries_busy_device() {
+        let host = MockHost::default();
+        host.plug("Built-in", 16_000, true);
+
+        let status = StatusReporter::new();
+        let (status_tx, status_rx) = mpsc::channel();
+        status.set_sink(Some(Box::new(move |event: StatusEvent| {
+            let _ = status_tx.send((event.code, event.device));
+        })));
+
+        let supervisor = DeviceSupervisor::spawn("Test", host.clone(), None, status.clone(), Duration::from_millis(10));
+        let config = PipelineConfig { status: status.clone(), ..PipelineConfig::for_microphone() };
+        let mut pipeline = supervisor.start_pipeline(config, |_| {}).unwrap();
+
+        // The new default refuses the first three opens
+        host.devices.lock().unwrap().busy_opens = 3;
+        host.plug("USB Mic", 48_000, true);
+        let mut events = Vec::new();
+        while events.last().is_none_or(|(code, _)| *code != StatusCode::DeviceChanged) {
+            events.push(status_rx.recv_timeout(Duration::from_secs(5)).unwrap());
+        }
+
+        // One failure reported, then the device once it works
+        assert_eq!(events, [
+            (StatusCode::StreamError, None),
+            (StatusCode::DeviceChanged, Some("USB Mic".to_string())),
+        ]);
+        pipeline.stop();
+        supervisor.stop();
+        assert_eq!(host.opened(), ["Built-in", "USB Mic"]);
+    }
+
+    #[test]
+    fn test_supervisor_accepts_alternate_device_id() {
+        let host = MockHost::default();
+        host.plug("HDMI", 48_000, true);
+        host.plug("Speakers", 48_000, false);
+
+        let status = StatusReporter::new();
+        let (status_tx, status_rx) = mpsc::channel();
+        status.set_sink(Some(Box::new(move |event: StatusEvent| {
+            let _ = status_tx.send(event.code);
+        })));
+
+        // A monitor source id records its sink, not the default
+        let requested = Some("Speakers.monitor".to_string());
+        let supervisor = DeviceSupervisor::spawn("Test", host.clone(), requested, status.clone(), Duration::from_millis(10));
+        let config = PipelineConfig { status: status.clone(), ..PipelineConfig::for_microphone() };
+        let mut pipeline = supervisor.start_pipeline(config, |_| {}).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        pipeline.stop();
+        supervisor.stop();
+        assert_eq!(host.opened(), ["Speakers"]);
+        let codes: Vec<StatusCode> = status_rx.try_iter().collect();
+        assert!(!codes.contains(&StatusCode::BackendFallback), "{:?}", codes);
+    }
+
+    #[test]
+    fn test_shared_host_enumerates_once_per_interval() {
+        let host = MockHost::default();
+        host.plug("Built-in", 48_000, true);
+        let cache = Arc::new(DeviceCache::new(Duration::from_secs(3600)));
+
+        // Two captures and a monitor polling the same kind of device
+        let pollers: Vec<SharedDeviceHost<MockHost>> = (0..3)
+            .map(|_| SharedDeviceHost::new(host.clone(), cache.clone()))
+            .collect();
+        let watcher = DeviceWatcher::new(None);
+        for _ in 0..5 {
+            for poller in &pollers {
+                assert_eq!(watcher.target(poller).as_deref(), Some("Built-in"));
+            }
+        }
+        assert_eq!(host.devices.lock().unwrap().enumerations, 1);
+
+        // A stale snapshot is taken again, with the changes since
+        let cache = Arc::new(DeviceCache::new(Duration::ZERO));
+        let poller = SharedDeviceHost::new(host.clone(), cache);
+        host.plug("AirPods", 24_000, true);
+        assert_eq!(poller.devices().unwrap(), ["Built-in", "AirPods"]);
+        assert_eq!(host.devices.lock().unwrap().enumerations, 2);
+    }
+
+    #[test]
+    fn test_list_watcher_reports_changes() {
+        let host = MockHost::default();
+        host.plug("Built-in", 48_000, true);
+        let mut watcher = DeviceListWatcher::new(DeviceDirection::Input, host.clone());
+        assert!(watcher.poll().is_empty());
+
+        let changes = |watcher: &mut DeviceListWatcher<MockHost>| -> Vec<(DeviceEventKind, String)> {
+            watcher.poll().into_iter().map(|e| (e.kind, e.device.id)).collect()
+        };
+        host.plug("AirPods", 24_000, true);
+        assert_eq!(changes(&mut watcher), [
+            (DeviceEventKind::Added, "AirPods".to_string()),
+            (DeviceEventKind::DefaultChanged, "AirPods".to_string()),
+        ]);
+
+        // Removed devices are reported with their last known details
+        host.unplug("AirPods");
+        let events = watcher.poll();
+        assert_eq!(events[0].kind, DeviceEventKind::Removed);
+        assert_eq!(events[0].device.sample_rates, [24_000]);
+        assert_eq!(events[1].kind, DeviceEventKind::DefaultChanged);
+        assert_eq!(events[1].device.id, "Built-in");
+        assert!(watcher.poll().is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+// Downmix - multi-channel input to the mono signal the pipeline runs on
+//
+// Sources deliver interleaved frames with all their channels; the first DSP
+// stage picks what becomes mono (`channelMode` option):
+//
+// - Channel(n): one channel, for interfaces and headsets that put the
+//   voice on a fixed input (e.g. channel 2 of a stereo device). Channel 0
+//   is the default, which is what the microphone always captured
+// - Average: mean of all channels
+// - Loudest: the channel with the most energy, for array mics and devices
+//   whose active channel is not known up front. Energy is smoothed over
+//   ~LOUDEST_TIME_CONSTANT, the pick only moves to a channel
+//   LOUDEST_HYSTERESIS times more energetic and the move is crossfaded
+//   over one batch, so it neither flaps nor clicks.
+
+use std::time::Duration;
+
+/// Smoothing of the per-channel energy in Loudest mode
+const LOUDEST_TIME_CONSTANT: Duration = Duration::from_millis(300);
+
+/// Energy ratio another channel needs over the current one to take over (~3dB)
+const LOUDEST_HYSTERESIS: f32 = 2.0;
+
+/// How interleaved channels become mono
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelMode {
+    Average,
+    /// Zero-based channel index; averages if the input has fewer channels
+    Channel(usize),
+    Loudest,
+}
+
+/// The first channel
+impl Default for ChannelMode {
+    fn default() -> Self {
+        Self::Channel(0)
+    }
+}
+
+pub struct Downmixer {
+    mode: ChannelMode,
+    channels: usize,
+    sample_rate: u32,
+    /// Loudest: smoothed mean square per channel
+    energy: Vec<f32>,
+    /// Loudest: channel currently passed through
+    selected: usize,
+}
+
+impl Downmixer {
+    pub fn new(mode: ChannelMode, channels: u16, sample_rate: u32) -> Self {
+        let mut downmixer = Self {
+            mode,
+            channels: 1,
+            sample_rate: 1,
+            energy: Vec::new(),
+            selected: 0,
+        };
+        downmixer.set_input(channels, sample_rate);
+        downmixer
+    }
+
+    /// Layout of a new source (device switch); Loudest starts over
+    pub fn set_input(&mut self, channels: u16, sample_rate: u32) {
+        self.channels = channels.max(1) as usize;
+        self.sample_rate = sample_rate.max(1);
+        self.energy = vec![0.0; self.channels];
+        self.selected = 0;
+        if let ChannelMode::Channel(index) = self.mode {
+            if index >= self.channels && self.channels > 1 {
+                println!("[Downmix] Channel {} requested, input has {}; averaging", index, self.channels);
+            }
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Channel Loudest mode currently passes through
+    pub fn selected_channel(&self) -> usize {
+        self.selected
+    }
+
+    /// Append the mono samples of `interleaved` (whole frames) to `mono`
+    pub fn process(&mut self, interleaved: &[f32], mono: &mut Vec<f32>) {
+        let channels = self.channels;
+        if channels == 1 {
+            mono.extend_from_slice(interleaved);
+            return;
+        }
+        match self.mode {
+            ChannelMode::Channel(index) if index < channels => {
+                mono.extend(interleaved.chunks_exact(channels).map(|f| f[index]));
+            }
+            ChannelMode::Loudest => self.loudest(interleaved, mono),
+            _ => {
+                let scale = 1.0 / channels as f32;
+                mono.extend(interleaved.chunks_exact(channels).map(|f| f.iter().sum::<f32>() * scale));
+            }
+        }
+    }
+
+    fn loudest(&mut self, interleaved: &[f32], mono: &mut Vec<f32>) {
+        let channels = self.channels;
+        let frame_count = interleaved.len() / channels;
+        if frame_count == 0 {
+            return;
+        }
+
+        let batch_secs = frame_count as f32 / self.sample_rate as f32;
+        let alpha = 1.0 - (-batch_secs / LOUDEST_TIME_CONSTANT.as_secs_f32()).exp();
+        for (channel, energy) in self.energy.iter_mut().enumerate() {
+            let mean_square = interleaved[..frame_count * channels].iter()
+                .skip(channel)
+                .step_by(channels)
+                .map(|s| s * s)
+                .sum::<f32>() / frame_count as f32;
+            *energy += alpha * (mean_square - *energy);
+        }
+
+        let previous = self.selected;
+        let loudest = (0..channels)
+            .max_by(|&a, &b| self.energy[a].total_cmp(&self.energy[b]))
+            .unwrap_or(previous);
+        if self.energy[loudest] > self.energy[previous] * LOUDEST_HYSTERESIS {
+            self.selected = loudest;
+        }
+
+        let next = self.selected;
+        let frames = interleaved.chunks_exact(channels);
+        if next == previous {
+            mono.extend(frames.map(|f| f[next]));
+        } else {
+            let step = 1.0 / frame_count as f32;
+            mono.extend(frames.enumerate().map(|(i, f)| {
+                let t = (i + 1) as f32 * step;
+                f[previous] * (1.0 - t) + f[next] * t
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::Noise;
+
+    /// 10ms batches of 4-channel 48kHz audio; `level(channel)` per channel
+    fn batch(noise: &mut Noise, level: impl Fn(usize) -> f32) -> Vec<f32> {
+        (0..480 * 4).map(|i| noise.next() * level(i % 4)).collect()
+    }
+
+    #[test]
+    fn test_channel_modes() {
+        let frames = [0.25, 0.5, -0.5, 1.0, 0.75, 0.0, 0.0, 0.25];
+        let mut mono = Vec::new();
+        // Default: the first channel only
+        Downmixer::new(ChannelMode::default(), 2, 48_000).process(&frames, &mut mono);
+        assert_eq!(mono, [0.25, -0.5, 0.75, 0.0]);
+
+        mono.clear();
+        Downmixer::new(ChannelMode::Channel(1), 2, 48_000).process(&frames, &mut mono);
+        assert_eq!(mono, [0.5, 1.0, 0.0, 0.25]);
+
+        mono.clear();
+        Downmixer::new(ChannelMode::Average, 2, 48_000).process(&frames, &mut mono);
+        assert_eq!(mono, [0.375, 0.25, 0.375, 0.125]);
+
+        mono.clear();
+        // Out of range: averages instead of going silent
+        Downmixer::new(ChannelMode::Channel(5), 2, 48_000).process(&frames, &mut mono);
+        assert_eq!(mono, [0.375, 0.25, 0.375, 0.125]);
+    }
+
+    #[test]
+    fn test_loudest_follows_the_voice_channel() {
+        let mut downmixer = Downmixer::new(ChannelMode::Loudest, 4, 48_000);
+        let mut noise = Noise::new(7);
+        let mut mono = Vec::new();
+
+        // Voice on channel 2, low noise on the others
+        for _ in 0..50 {
+            let frames = batch(&mut noise, |c| if c == 2 { 0.3 } else { 0.01 });
+            downmixer.process(&frames, &mut mono);
+        }
+        assert_eq!(downmixer.selected_channel(), 2);
+        assert_eq!(mono.len(), 50 * 480);
+
+        // A brief knock on channel 0 does not steal the pick
+        let frames = batch(&mut noise, |c| if c == 0 { 0.5 } else if c == 2 { 0.3 } else { 0.01 });
+        downmixer.process(&frames, &mut mono);
+        assert_eq!(downmixer.selected_channel(), 2);
+
+        // The talker moves to channel 3: followed within half a second
+        let switched = (1..=50).find(|_| {
+            let frames = batch(&mut noise, |c| if c == 3 { 0.3 } else { 0.01 });
+            downmixer.process(&frames, &mut mono);
+            downmixer.selected_channel() == 3
+        });
+        assert!(switched.is_some(), "still on channel {}", downmixer.selected_channel());
+    }
+}
@@ -0,0 +1,729 @@
+// Acoustic Echo Cancellation - system audio as far-end reference
+//
+// When the user is on speakers, the microphone picks up the remote party.
+// SystemAudioCapture already records exactly what is played, so its
+// resampled 16kHz frames are the far-end reference:
+//
+//   SystemAudioCapture DSP thread --push--> EchoReference --pull--> EchoCanceller
+//                                                                (mic DSP thread,
+//                                                                 before suppression)
+//
+// Canceller: partitioned-block frequency-domain NLMS (overlap-save, one
+// frame per partition, per-bin step normalization) with a Geigel double-talk
+// detector (adaptation freezes while the near end is talking). A frame
+// costs two FFTs (of two frames) per partition plus three, instead of taps
+// multiply-adds per sample, so the full echo tail stays affordable at 48kHz
+// (6144 taps: 17 FFTs per frame instead of ~590M multiply-adds per second).
+//
+// ALIGNMENT:
+// Both DSP threads run on their own schedule (chunky backends deliver
+// tens of ms at once), so how much reference is buffered says nothing about
+// which far-end sample a mic frame hears. Both sides stamp their frames with
+// CaptureClock timestamps instead: the reference keeps its recent samples
+// indexed by capture time, and each mic frame reads the reference window
+// that ends at its own capture time. Reference that has not arrived yet
+// counts as silence for the estimate and the filter does not adapt on it,
+// so a late system thread costs cancellation on those samples but never
+// shifts the alignment.
+//
+// CLOCK DRIFT:
+// The two captures run on different device clocks, so the aligned reference
+// position moves slightly faster or slower than the mic consumes samples. A
+// servo follows the smoothed alignment error by skipping or repeating single
+// reference samples; the adaptive filter absorbs the one-sample shifts.
+// Errors beyond RESYNC_MS (capture restarted, stalled device) jump straight
+// to the new alignment.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use realfft::num_complex::Complex;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+
+use crate::audio_config::{OutputFormat, SAMPLE_RATE};
+
+/// Reference kept for alignment (how far the mic may lag the system capture)
+const REFERENCE_CAPACITY_MS: usize = 1000;
+
+/// Echo tail the adaptive filter covers
+const ECHO_TAIL_MS: usize = 128;
+
+/// Frames without new reference data before the canceller bypasses
+const REFERENCE_IDLE_FRAMES: u32 = 10;
+
+/// Smoothing of the alignment error (per frame, ~0.4s time constant)
+const ALIGNMENT_SMOOTHING: f32 = 0.05;
+
+/// Alignment error tolerated before correcting drift
+const DRIFT_TOLERANCE_MS: f32 = 1.5;
+
+/// Alignment error treated as a jump rather than drift
+const RESYNC_MS: f32 = 20.0;
+
+/// Double-talk detection hold time
+const DOUBLE_TALK_HOLD_MS: usize = 30;
+
+/// Smoothing of the per-bin reference power the step is normalized by
+const POWER_SMOOTHING: f32 = 0.5;
+
+/// Echo canceller tuning
+#[derive(Debug, Clone)]
+pub struct EchoCancelConfig {
+    /// Adaptive filter length in samples, rounded up to whole frames
+    /// (2048 = 128ms echo tail at 16kHz)
+    pub filter_taps: usize,
+    /// Sample rate of both the mic frames and the reference
+    pub sample_rate: u32,
+    /// NLMS step size (0 < mu < 2, smaller = slower but steadier)
+    pub step_size: f32,
+}
+
+impl Default for EchoCancelConfig {
+    fn default() -> Self {
+        Self::for_format(OutputFormat::default())
+    }
+}
+
+impl EchoCancelConfig {
+    /// Same echo tail and buffering time at any output format
+    pub fn for_format(format: OutputFormat) -> Self {
+        Self {
+            filter_taps: format.sample_rate as usize * ECHO_TAIL_MS / 1000,
+            sample_rate: format.sample_rate,
+            step_size: 0.5,
+        }
+    }
+}
+
+// ============================================================================
+// FAR-END REFERENCE
+// ============================================================================
+
+/// Far-end samples shared between the system and mic DSP threads
+///
+/// Both sides are DSP threads (never audio callbacks), so a short mutex
+/// section per frame is fine here.
+pub struct EchoReference {
+    sample_rate: u32,
+    capacity: usize,
+    inner: Mutex<ReferenceInner>,
+}
+
+struct ReferenceInner {
+    /// The most recent samples; samples[0] is sample `written - samples.len()`
+    samples: VecDeque<f32>,
+    /// Total samples ever written, used to detect an idle reference
+    written: u64,
+    /// Index and capture timestamp of the newest frame's first sample
+    anchor: Option<(u64, Instant)>,
+}
+
+impl Default for EchoReference {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EchoReference {
+    pub fn new() -> Self {
+        Self::with_sample_rate(SAMPLE_RATE)
+    }
+
+    /// Reference carrying frames at `sample_rate` (the system capture's output rate)
+    pub fn with_sample_rate(sample_rate: u32) -> Self {
+        let capacity = sample_rate as usize * REFERENCE_CAPACITY_MS / 1000;
+        Self {
+            sample_rate,
+            capacity,
+            inner: Mutex::new(ReferenceInner {
+                samples: VecDeque::with_capacity(capacity),
+                written: 0,
+                anchor: None,
+            }),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Append resampled far-end audio whose first sample was captured at
+    /// `timestamp` (called by the system capture)
+    pub fn push(&self, frame: &[i16], timestamp: Instant) {
+        let mut inner = self.lock();
+        inner.anchor = Some((inner.written, timestamp));
+        inner.samples.extend(frame.iter().map(|&s| s as f32 / 32768.0));
+        inner.written += frame.len() as u64;
+        let excess = inner.samples.len().saturating_sub(self.capacity);
+        inner.samples.drain(..excess);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ReferenceInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Echo settings shared between a MicrophoneCapture and its DSP thread
+///
+/// Both the reference and the toggle can change while capture runs.
+#[derive(Default)]
+pub struct EchoControl {
+    enabled: AtomicBool,
+    reference: Mutex<Option<Arc<EchoReference>>>,
+}
+
+impl EchoControl {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_reference(&self, reference: Option<Arc<EchoReference>>) {
+        *self.reference.lock().unwrap_or_else(|e| e.into_inner()) = reference;
+    }
+
+    fn reference(&self) -> Option<Arc<EchoReference>> {
+        self.reference.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+// ============================================================================
+// NLMS ECHO CANCELLER
+// ============================================================================
+
+pub struct EchoCanceller {
+    config: EchoCancelConfig,
+    /// Set up on the first frame (the frame length is the block size)
+    filter: Option<BlockFilter>,
+    /// Far-end window (oldest first): one block per partition + current frame
+    history: Vec<f32>,
+    /// Leading samples of the current frame whose reference has arrived
+    available: usize,
+    /// Reference sample aligned with the next mic sample (None until the
+    /// reference has a timestamp)
+    read_pos: Option<u64>,
+    /// Smoothed difference between the timestamp alignment and read_pos
+    smoothed_error: f32,
+    /// Reference write counter at the last pull
+    last_written: u64,
+    idle_frames: u32,
+    /// Samples left before adaptation resumes after double talk
+    double_talk_hold: usize,
+    /// Samples of drift correction applied (+ skipped, - repeated)
+    drift_corrections: i64,
+}
+
+impl EchoCanceller {
+    pub fn new(config: EchoCancelConfig) -> Self {
+        Self {
+            filter: None,
+            history: Vec::new(),
+            available: 0,
+            read_pos: None,
+            smoothed_error: 0.0,
+            last_written: 0,
+            idle_frames: REFERENCE_IDLE_FRAMES,
+            double_talk_hold: 0,
+            drift_corrections: 0,
+            config: EchoCancelConfig { filter_taps: config.filter_taps.max(1), ..config },
+        }
+    }
+
+    /// Remove the far-end echo from a mic frame in place; `timestamp` is
+    /// the capture time of its first sample
+    ///
+    /// Passes the frame through untouched while the reference is idle
+    /// (system capture stopped or not linked yet).
+    pub fn process(&mut self, reference: &EchoReference, frame: &mut [i16], timestamp: Instant) {
+        let block = frame.len();
+        if block == 0 {
+            return;
+        }
+        let taps = self.config.filter_taps;
+        let partitions = match &mut self.filter {
+            Some(filter) if filter.block == block => filter.partitions,
+            slot => slot.insert(BlockFilter::new(block, taps)).partitions,
+        };
+        let Some(window_end) = self.pull_reference(reference, block, partitions * block, timestamp) else {
+            return;
+        };
+
+        // Geigel double-talk detector: near end louder than the far end
+        // anywhere in the echo tail
+        let far_peak = self.history.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        let talking = frame.iter().any(|&s| {
+            let near = (s as f32 / 32768.0).abs();
+            near > 0.5 * far_peak && near > 1e-3
+        });
+        let hold_samples = (self.config.sample_rate as usize * DOUBLE_TALK_HOLD_MS) / 1000;
+        self.double_talk_hold = if talking {
+            hold_samples.max(1)
+        } else {
+            self.double_talk_hold.saturating_sub(block)
+        };
+
+        // Only adapt on a complete reference and without near-end speech
+        let complete = self.available == block;
+        let adapt = complete && self.double_talk_hold == 0;
+        if let Some(filter) = &mut self.filter {
+            filter.process(&self.history, frame, window_end, complete, adapt, self.config.step_size);
+        }
+    }
+
+    /// Samples of drift correction applied so far (+ skipped, - repeated)
+    pub fn drift_corrections(&self) -> i64 {
+        self.drift_corrections
+    }
+
+    /// Forget the adapted echo path and the reference alignment
+    /// (e.g. after a device change)
+    pub fn reset(&mut self) {
+        self.filter = None;
+        self.double_talk_hold = 0;
+        self.read_pos = None;
+        self.smoothed_error = 0.0;
+    }
+
+    /// Whether an echo path is being learned (false before the first
+    /// frame and after a reset)
+    pub fn is_adapting(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    /// Fill `history` with `past` samples before a mic frame captured at
+    /// `timestamp` plus the frame's own reference, applying drift
+    /// correction. Returns the reference index after the frame, None while
+    /// the reference is idle.
+    fn pull_reference(&mut self, reference: &EchoReference, count: usize, past: usize, timestamp: Instant) -> Option<u64> {
+        let rate = self.config.sample_rate as f64;
+        let tolerance = self.config.sample_rate as f32 * DRIFT_TOLERANCE_MS / 1000.0;
+        let resync = self.config.sample_rate as f32 * RESYNC_MS / 1000.0;
+        let mut inner = reference.lock();
+
+        // Idle detection: no new far-end audio for a while -> bypass
+        if inner.written == self.last_written {
+            self.idle_frames = self.idle_frames.saturating_add(1);
+        } else {
+            self.idle_frames = 0;
+        }
+        self.last_written = inner.written;
+        if self.idle_frames >= REFERENCE_IDLE_FRAMES {
+            inner.samples.clear();
+            inner.anchor = None;
+            self.read_pos = None;
+            return None;
+        }
+        let (anchor_index, anchor_time) = inner.anchor?;
+
+        // Reference sample captured at the same time as the mic frame
+        let offset = if timestamp >= anchor_time {
+            (timestamp - anchor_time).as_secs_f64()
+        } else {
+            -(anchor_time - timestamp).as_secs_f64()
+        };
+        let aligned = anchor_index as f64 + offset * rate;
+
+        // Drift servo on the smoothed alignment error; jumps resync at once
+        let read_pos = match self.read_pos {
+            Some(pos) if ((aligned - pos as f64).abs() as f32) < resync => {
+                self.smoothed_error += ALIGNMENT_SMOOTHING * ((aligned - pos as f64) as f32 - self.smoothed_error);
+                if self.smoothed_error > tolerance {
+                    self.smoothed_error -= 1.0; // far end clock is faster: skip one
+                    self.drift_corrections += 1;
+                    pos + 1
+                } else if self.smoothed_error < -tolerance && pos > 0 {
+                    self.smoothed_error += 1.0; // far end clock is slower: repeat one
+                    self.drift_corrections -= 1;
+                    pos - 1
+                } else {
+                    pos
+                }
+            }
+            _ => {
+                self.smoothed_error = 0.0;
+                aligned.round().max(0.0) as u64
+            }
+        };
+        self.read_pos = Some(read_pos + count as u64);
+
+        // Window: `past` samples before the frame, then the frame itself;
+        // evicted or not yet written samples read as silence
+        let oldest = inner.written - inner.samples.len() as u64;
+        let first = read_pos as i64 - past as i64;
+        self.history.clear();
+        self.history.extend((0..(past + count) as i64).map(|i| {
+            let index = first + i;
+            if index < oldest as i64 || index >= inner.written as i64 {
+                0.0
+            } else {
+                inner.samples[(index - oldest as i64) as usize]
+            }
+        }));
+        self.available = inner.written.saturating_sub(read_pos).min(count as u64) as usize;
+        Some(read_pos + count as u64)
+    }
+}
+
+/// Partitioned frequency-domain filter for one block (frame) size
+struct BlockFilter {
+    block: usize,
+    partitions: usize,
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+    /// Weights per partition, newest reference block first
+    weights: Vec<Vec<Complex<f32>>>,
+    /// Spectra of [previous block | block] per partition, same order
+    spectra: VecDeque<Vec<Complex<f32>>>,
+    /// Reference index the cached spectra end at (None: recompute all)
+    spectra_end: Option<u64>,
+    /// Smoothed reference power per bin
+    power: Vec<f32>,
+    time_buffer: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    error_spectrum: Vec<Complex<f32>>,
+}
+
+impl BlockFilter {
+    fn new(block: usize, taps: usize) -> Self {
+        let fft_size = block * 2;
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(fft_size);
+        let inverse = planner.plan_fft_inverse(fft_size);
+        let partitions = taps.div_ceil(block).max(1);
+        let bins = fft_size / 2 + 1;
+        Self {
+            block,
+            partitions,
+            weights: vec![vec![Complex::default(); bins]; partitions],
+            spectra: (0..partitions).map(|_| vec![Complex::default(); bins]).collect(),
+            spectra_end: None,
+            power: vec![0.0; bins],
+            time_buffer: vec![0.0; fft_size],
+            spectrum: forward.make_output_vec(),
+            error_spectrum: forward.make_output_vec(),
+            forward,
+            inverse,
+        }
+    }
+
+    /// Cancel the echo of `history` (partitions + 1 blocks, oldest first)
+    /// from `frame`; `end` is the reference index after the frame
+    fn process(&mut self, history: &[f32], frame: &mut [i16], end: u64, complete: bool, adapt: bool, step_size: f32) {
+        let block = self.block;
+        let partitions = self.partitions;
+        let fft_size = (2 * block) as f32;
+
+        // 1. Reference spectra: after a contiguous frame only the newest
+        //    block is new, the others shift one partition back
+        let contiguous = self.spectra_end == Some(end - block as u64);
+        let fresh = if contiguous { 1 } else { partitions };
+        for p in (0..fresh).rev() {
+            let start = (partitions - 1 - p) * block;
+            self.time_buffer.copy_from_slice(&history[start..start + 2 * block]);
+            let mut spectrum = if contiguous {
+                self.spectra.pop_back().unwrap_or_default()
+            } else {
+                std::mem::take(&mut self.spectra[p])
+            };
+            spectrum.resize(self.power.len(), Complex::default());
+            let _ = self.forward.process(&mut self.time_buffer, &mut spectrum);
+            if contiguous {
+                self.spectra.push_front(spectrum);
+            } else {
+                self.spectra[p] = spectrum;
+            }
+        }
+        // Spectra over a partly missing reference are not reused
+        self.spectra_end = complete.then_some(end);
+
+        // 2. Echo estimate (overlap-save: the last block of the product)
+        for (bin, out) in self.spectrum.iter_mut().enumerate() {
+            *out = self.weights.iter().zip(&self.spectra).map(|(w, x)| w[bin] * x[bin]).sum();
+        }
+        zero_edge_imaginary(&mut self.spectrum);
+        if self.inverse.process(&mut self.spectrum, &mut self.time_buffer).is_err() {
+            return;
+        }
+
+        // 3. Error = mic - estimate, which is also the output
+        let (head, tail) = self.time_buffer.split_at_mut(block);
+        for ((sample, estimate), error) in frame.iter_mut().zip(tail.iter()).zip(head.iter_mut()) {
+            let near = *sample as f32 / 32768.0;
+            let e = near - estimate / fft_size;
+            *error = e;
+            *sample = (e * 32768.0).clamp(-32768.0, 32767.0) as i16;
+        }
+
+        for (power, x) in self.power.iter_mut().zip(&self.spectra[0]) {
+            *power = POWER_SMOOTHING * *power + (1.0 - POWER_SMOOTHING) * x.norm_sqr();
+        }
+        if !adapt {
+            return;
+        }
+
+        // 4. NLMS update: error spectrum of [zeros | error], per-bin
+        //    normalized gradient, constrained to one block of taps
+        let (head, tail) = self.time_buffer.split_at_mut(block);
+        tail.copy_from_slice(head);
+        head.fill(0.0);
+        if self.forward.process(&mut self.time_buffer, &mut self.error_spectrum).is_err() {
+            return;
+        }
+        // Regularization: bins far below the mean power (between the
+        // partials of a tonal far end, near-silent reference) get no more
+        // than the mean step
+        let mean_power = self.power.iter().sum::<f32>() / self.power.len() as f32;
+        let epsilon = mean_power + fft_size * 1e-6;
+        let mu = 2.0 * step_size / partitions as f32;
+        for (weights, spectrum) in self.weights.iter_mut().zip(&self.spectra) {
+            for (bin, gradient) in self.spectrum.iter_mut().enumerate() {
+                *gradient = spectrum[bin].conj() * self.error_spectrum[bin] / (self.power[bin] + epsilon);
+            }
+            zero_edge_imaginary(&mut self.spectrum);
+            if self.inverse.process(&mut self.spectrum, &mut self.time_buffer).is_err() {
+                continue;
+            }
+            self.time_buffer[block..].fill(0.0);
+            if self.forward.process(&mut self.time_buffer, &mut self.spectrum).is_err() {
+                continue;
+            }
+            let scale = mu / fft_size;
+            for (w, g) in weights.iter_mut().zip(&self.spectrum) {
+                *w += g * scale;
+            }
+        }
+    }
+}
+
+/// DC and Nyquist bins of a real signal's spectrum are real
+fn zero_edge_imaginary(spectrum: &mut [Complex<f32>]) {
+    if let Some(first) = spectrum.first_mut() {
+        first.im = 0.0;
+    }
+    if let Some(last) = spectrum.last_mut() {
+        last.im = 0.0;
+    }
+}
+
+/// Mic-side AEC stage: owns the canceller, follows the shared control
+pub struct EchoStage {
+    control: Arc<EchoControl>,
+    canceller: EchoCanceller,
+    mismatch_logged: bool,
+}
+
+impl EchoStage {
+    pub fn new(control: Arc<EchoControl>, config: EchoCancelConfig) -> Self {
+        Self {
+            control,
+            canceller: EchoCanceller::new(config),
+            mismatch_logged: false,
+        }
+    }
+
+    pub fn process(&mut self, frame: &mut [i16], timestamp: Instant) {
+        if !self.control.is_enabled() {
+            return;
+        }
+        if let Some(reference) = self.control.reference() {
+            // Both captures must share an output rate; otherwise bypass
+            if reference.sample_rate() != self.canceller.config.sample_rate {
+                if !self.mismatch_logged {
+                    eprintln!("[EchoCanceller] Reference is {}Hz but mic output is {}Hz, bypassing",
+                        reference.sample_rate(), self.canceller.config.sample_rate);
+                    self.mismatch_logged = true;
+                }
+                return;
+            }
+            self.canceller.process(&reference, frame, timestamp);
+        }
+    }
+
+    /// Start over on a new microphone: its echo path and clock differ
+    pub fn reset(&mut self) {
+        self.canceller.reset();
+    }
+
+    pub fn is_adapting(&self) -> bool {
+        self.canceller.is_adapting()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_config::FRAME_SAMPLES;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::f64::consts::PI;
+
+    /// Band-limited far-end signal that can be sampled at any time
+    struct FarEnd {
+        partials: Vec<(f64, f64, f64)>, // (freq, phase, amplitude)
+    }
+
+    impl FarEnd {
+        fn new(seed: u64) -> Self {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let partials = (0..24)
+                .map(|_| (rng.gen_range(150.0..3500.0), rng.gen_range(0.0..2.0 * PI), rng.gen_range(0.01..0.04)))
+                .collect();
+            Self { partials }
+        }
+
+        fn at(&self, t: f64) -> f64 {
+            self.partials.iter().map(|(f, p, a)| a * (2.0 * PI * f * t + p).sin()).sum()
+        }
+    }
+
+    /// Sparse room impulse response: (delay seconds, gain)
+    const ECHO_PATH: [(f64, f64); 3] = [(0.010, 0.5), (0.013, -0.2), (0.021, 0.1)];
+
+    fn to_i16(x: f64) -> i16 {
+        (x * 32768.0).clamp(-32768.0, 32767.0) as i16
+    }
+
+    /// When each DSP thread gets to its frames
+    #[derive(Clone, Copy)]
+    enum Schedule {
+        /// Every reference frame is pushed just before the mic frame of the same time
+        LockStep,
+        /// Independent threads: the system side delivers 40ms bursts up to
+        /// 20ms late, the mic side runs 60-100ms behind capture
+        Independent,
+    }
+
+    struct EchoRun {
+        /// Echo return loss enhancement over the last second, dB
+        erle: f64,
+        drift_corrections: i64,
+    }
+
+    /// `far_rate` is the system device clock as seen by the mic clock.
+    fn run_echo_scenario(format: OutputFormat, seconds: f64, far_rate: f64, near_talk: bool, schedule: Schedule) -> EchoRun {
+        let far = FarEnd::new(7);
+        let near = FarEnd::new(99);
+        let reference = EchoReference::with_sample_rate(format.sample_rate);
+        let mut canceller = EchoCanceller::new(EchoCancelConfig::for_format(format));
+        let mut rng = StdRng::seed_from_u64(3);
+        let base = Instant::now();
+        let at = |secs: f64| base + std::time::Duration::from_secs_f64(secs);
+
+        let rate = format.sample_rate as f64;
+        let frame_samples = format.frame_samples();
+        let frame_secs = frame_samples as f64 / rate;
+        let frames = (seconds * rate) as usize / frame_samples;
+
+        // (wall time, mic side, frame index), system frames first on ties
+        let mut events = Vec::with_capacity(frames * 2);
+        let mut mic_ready = 0.0f64;
+        let mut burst_delay = 0.0;
+        for f in 0..frames {
+            let captured = (f + 1) as f64 * frame_secs;
+            let (system, mic) = match schedule {
+                Schedule::LockStep => (captured, captured),
+                Schedule::Independent => {
+                    if f % 2 == 0 {
+                        burst_delay = rng.gen_range(0.0..0.020);
+                    }
+                    mic_ready = mic_ready.max(captured + rng.gen_range(0.060..0.100));
+                    ((f / 2 + 1) as f64 * 2.0 * frame_secs + burst_delay, mic_ready)
+                }
+            };
+            events.push((system, false, f));
+            events.push((mic, true, f));
+        }
+        events.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        // System capture frame f holds what its clock produced during mic frame f
+        let far_due = |f: usize| ((f * frame_samples) as f64 * far_rate / rate) as usize;
+        let (mut in_energy, mut out_energy) = (0.0f64, 0.0f64);
+        for (_, is_mic, f) in events {
+            if !is_mic {
+                let ref_frame: Vec<i16> = (far_due(f)..far_due(f + 1)).map(|k| to_i16(far.at(k as f64 / far_rate))).collect();
+                reference.push(&ref_frame, at(far_due(f) as f64 / far_rate));
+                continue;
+            }
+
+            // Mic hears the echo of the far end
+            let mut mic: Vec<i16> = (0..frame_samples)
+                .map(|i| {
+                    let t = (f * frame_samples + i) as f64 / rate;
+                    let echo: f64 = ECHO_PATH.iter().map(|(d, g)| g * far.at(t - d)).sum();
+                    let talk = if near_talk && t > seconds - 1.0 { near.at(t) } else { 0.0 };
+                    to_i16(echo + talk)
+                })
+                .collect();
+            let echo_only: Vec<f64> = mic.iter().map(|&s| s as f64).collect();
+
+            canceller.process(&reference, &mut mic, at(f as f64 * frame_secs));
+
+            if (f + 1) * frame_samples > ((seconds - 1.0) * rate) as usize {
+                in_energy += echo_only.iter().map(|s| s * s).sum::<f64>();
+                out_energy += mic.iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>();
+            }
+        }
+
+        EchoRun {
+            erle: 10.0 * (in_energy / out_energy.max(1.0)).log10(),
+            drift_corrections: canceller.drift_corrections(),
+        }
+    }
+
+    #[test]
+    fn test_echo_cancelled_same_clock() {
+        let run = run_echo_scenario(OutputFormat::default(), 4.0, 16000.0, false, Schedule::LockStep);
+        assert!(run.erle > 15.0, "ERLE {:.1} dB", run.erle);
+        assert_eq!(run.drift_corrections, 0);
+    }
+
+    #[test]
+    fn test_echo_cancelled_with_clock_drift() {
+        // Far-end device runs 1000ppm fast (well beyond real hardware):
+        // 64 samples over 4 seconds, tracked while still cancelling
+        let run = run_echo_scenario(OutputFormat::default(), 4.0, 16016.0, false, Schedule::LockStep);
+        assert!(run.erle > 8.0, "ERLE {:.1} dB", run.erle);
+        assert!(run.drift_corrections > 0, "drift was never corrected");
+    }
+
+    #[test]
+    fn test_echo_cancelled_with_independent_threads() {
+        // The mic thread lags and the reference arrives in jittered bursts:
+        // the buffered amount keeps changing, the timestamps do not
+        let run = run_echo_scenario(OutputFormat::default(), 4.0, 16000.0, false, Schedule::Independent);
+        assert!(run.erle > 15.0, "ERLE {:.1} dB", run.erle);
+        assert_eq!(run.drift_corrections, 0);
+    }
+
+    #[test]
+    fn test_default_config_at_every_rate() {
+        // Default tails (2048 taps at 16kHz, 6144 at 48kHz); their cost is
+        // measured by `cargo bench --bench echo_cancel`
+        for sample_rate in [16_000, 48_000] {
+            let format = OutputFormat { sample_rate, frame_ms: 20 };
+            let run = run_echo_scenario(format, 3.0, sample_rate as f64, false, Schedule::Independent);
+            assert!(run.erle > 15.0, "{}Hz: ERLE {:.1} dB", sample_rate, run.erle);
+        }
+    }
+
+    #[test]
+    fn test_near_end_speech_preserved() {
+        // During double talk the output keeps the near-end voice
+        let run = run_echo_scenario(OutputFormat::default(), 4.0, 16000.0, true, Schedule::LockStep);
+        assert!(run.erle < 6.0, "near-end speech was cancelled ({:.1} dB)", run.erle);
+    }
+
+    #[test]
+    fn test_bypass_without_reference() {
+        let reference = EchoReference::new();
+        let mut canceller = EchoCanceller::new(EchoCancelConfig::default());
+        let original: Vec<i16> = (0..FRAME_SAMPLES as i16).collect();
+        let mut frame = original.clone();
+        canceller.process(&reference, &mut frame, Instant::now());
+        assert_eq!(frame, original);
+    }
+}
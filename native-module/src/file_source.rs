@@ -0,0 +1,393 @@
+// File-Backed Audio Source - Offline / CI Replay
+//
+// Reads a WAV file (any bit depth, rate, channel count) or headerless PCM
+// (f32le / s16le) and feeds it through the same ring buffer + DataNotifier
+// contract as the live backends, so the full capture pipeline can run
+// without audio hardware.
+//
+// Pacing:
+// - RealTime: pushes 10ms blocks on the file's own clock, like a device
+// - AsFastAsPossible: pushes as fast as the DSP thread drains (never drops)
+
+use anyhow::Result;
+use ringbuf::{traits::{Observer, Producer, Split}, HeapCons, HeapProd, HeapRb};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio_config::RING_BUFFER_SAMPLES;
+use crate::audio_source::AudioSource;
+use crate::stats::OverflowCounter;
+use crate::wakeup::DataNotifier;
+
+/// Headerless PCM sample encodings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RawFormat {
+    F32Le,
+    S16Le,
+}
+
+/// How the file is laid out on disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+    Wav,
+    Raw {
+        format: RawFormat,
+        sample_rate: u32,
+        channels: u16,
+    },
+}
+
+/// How fast samples are pushed into the ring buffer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    RealTime,
+    AsFastAsPossible,
+}
+
+/// Audio source that replays decoded file samples
+pub struct FileAudioSource {
+    /// Interleaved samples in [-1.0, 1.0]
+    samples: Arc<Vec<f32>>,
+    sample_rate: u32,
+    channels: u16,
+    pacing: Pacing,
+    consumer: Option<HeapCons<f32>>,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
+    stop_signal: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    feeder: Option<thread::JoinHandle<()>>,
+}
+
+impl FileAudioSource {
+    /// Decode a file from disk
+    pub fn open(path: impl AsRef<Path>, format: FileFormat, pacing: Pacing) -> Result<Self> {
+        let path = path.as_ref();
+        let (samples, sample_rate, channels) = match format {
+            FileFormat::Wav => read_wav(path)?,
+            FileFormat::Raw { format, sample_rate, channels } => {
+                let bytes = std::fs::read(path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+                (decode_raw(&bytes, format), sample_rate, channels)
+            }
+        };
+
+        println!(
+            "[FileAudioSource] {}: {}Hz, {} channels, {:.2}s, {:?}",
+            path.display(),
+            sample_rate,
+            channels,
+            samples.len() as f64 / (sample_rate as f64 * channels.max(1) as f64),
+            pacing
+        );
+
+        Self::from_samples(samples, sample_rate, channels, pacing)
+    }
+
+    /// Replay in-memory interleaved samples
+    pub fn from_samples(samples: Vec<f32>, sample_rate: u32, channels: u16, pacing: Pacing) -> Result<Self> {
+        if sample_rate == 0 || channels == 0 {
+            return Err(anyhow::anyhow!("Invalid format: {}Hz, {} channels", sample_rate, channels));
+        }
+        Ok(Self {
+            samples: Arc::new(samples),
+            sample_rate,
+            channels,
+            pacing,
+            consumer: None,
+            notifier: DataNotifier::new(),
+            overflow: OverflowCounter::new(),
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            finished: Arc::new(AtomicBool::new(false)),
+            feeder: None,
+        })
+    }
+
+    /// True once every sample has been pushed to the ring buffer
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    /// Duration of the file
+    pub fn duration(&self) -> Duration {
+        let frames = self.samples.len() / self.channels as usize;
+        Duration::from_secs_f64(frames as f64 / self.sample_rate as f64)
+    }
+}
+
+impl AudioSource for FileAudioSource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn take_consumer(&mut self) -> Option<HeapCons<f32>> {
+        self.consumer.take()
+    }
+
+    fn data_notifier(&self) -> DataNotifier {
+        self.notifier.clone()
+    }
+
+    fn overflow_counter(&self) -> OverflowCounter {
+        self.overflow.clone()
+    }
+
+    /// Start (or restart from the beginning) the feeder thread
+    fn start(&mut self) -> Result<()> {
+        self.stop()?;
+
+        let rb = HeapRb::<f32>::new(RING_BUFFER_SAMPLES);
+        let (producer, consumer) = rb.split();
+        self.consumer = Some(consumer);
+
+        self.stop_signal.store(false, Ordering::SeqCst);
+        self.finished.store(false, Ordering::SeqCst);
+
+        let feeder = Feeder {
+            samples: self.samples.clone(),
+            sample_rate: self.sample_rate,
+            channels: self.channels as usize,
+            pacing: self.pacing,
+            notifier: self.notifier.clone(),
+            overflow: self.overflow.clone(),
+            stop_signal: self.stop_signal.clone(),
+            finished: self.finished.clone(),
+        };
+        self.feeder = Some(thread::spawn(move || feeder.run(producer)));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.stop_signal.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.feeder.take() {
+            let _ = handle.join();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FileAudioSource {
+    fn drop(&mut self) {
+        let _ = AudioSource::stop(self);
+    }
+}
+
+/// Feeder thread state (plays the role of the device callback)
+struct Feeder {
+    samples: Arc<Vec<f32>>,
+    sample_rate: u32,
+    channels: usize,
+    pacing: Pacing,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
+    stop_signal: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+}
+
+impl Feeder {
+    fn run(self, mut producer: HeapProd<f32>) {
+        // 10ms blocks, always whole channel frames
+        let block_frames = (self.sample_rate as usize / 100).max(1);
+        let block = block_frames * self.channels;
+        let started = Instant::now();
+        let mut pos = 0usize;
+
+        while pos < self.samples.len() && !self.stop_signal.load(Ordering::Relaxed) {
+            let end = (pos + block).min(self.samples.len());
+
+            match self.pacing {
+                Pacing::RealTime => {
+                    // Push this block once its audio time has been reached
+                    let frames_pushed = (pos / self.channels) as f64;
+                    let due = started + Duration::from_secs_f64(frames_pushed / self.sample_rate as f64);
+                    let now = Instant::now();
+                    if due > now {
+                        thread::sleep(due - now);
+                    }
+                    let fits = end.min(pos + self.vacant_frames(&producer));
+                    let pushed = producer.push_slice(&self.samples[pos..fits]);
+                    self.overflow.record(end - pos, pushed);
+                    pos = end;
+                }
+                Pacing::AsFastAsPossible => {
+                    // Back-pressure: push what fits, wait for room for the rest
+                    // (a 10ms block can be larger than the whole ring buffer)
+                    let fits = end.min(pos + self.vacant_frames(&producer));
+                    if fits == pos {
+                        self.notifier.notify();
+                        thread::sleep(Duration::from_micros(500));
+                        continue;
+                    }
+                    pos += producer.push_slice(&self.samples[pos..fits]);
+                }
+            }
+
+            self.notifier.notify();
+        }
+
+        self.finished.store(pos >= self.samples.len(), Ordering::Release);
+        self.notifier.notify();
+    }
+
+    /// Free ring buffer space in samples, rounded down to whole frames
+    fn vacant_frames(&self, producer: &HeapProd<f32>) -> usize {
+        producer.vacant_len() / self.channels * self.channels
+    }
+}
+
+/// Decode any PCM WAV into interleaved f32
+fn read_wav(path: &Path) -> Result<(Vec<f32>, u32, u16)> {
+    let mut reader = hound::WavReader::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open WAV {}: {}", path.display(), e))?;
+    let spec = reader.spec();
+
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|v| v as f32 * scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    Ok((samples, spec.sample_rate, spec.channels))
+}
+
+/// Decode headerless little-endian PCM (a trailing partial sample is ignored)
+fn decode_raw(bytes: &[u8], format: RawFormat) -> Vec<f32> {
+    match format {
+        RawFormat::F32Le => bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        RawFormat::S16Le => bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_config::FRAME_SAMPLES;
+    use crate::pipeline::{CapturePipeline, PipelineConfig};
+    use std::sync::mpsc;
+
+    fn tone(sample_rate: u32, channels: u16, seconds: f32) -> Vec<f32> {
+        let frames = (sample_rate as f32 * seconds) as usize;
+        (0..frames)
+            .flat_map(|i| {
+                let s = 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin();
+                std::iter::repeat_n(s, channels as usize)
+            })
+            .collect()
+    }
+
+    /// Run a source through the real pipeline thread and collect every frame
+    fn run_pipeline(mut source: FileAudioSource) -> Vec<Vec<i16>> {
+        let (tx, rx) = mpsc::channel();
+        let mut pipeline = CapturePipeline::start(&mut source, PipelineConfig::for_microphone(), move |frame| {
+            let _ = tx.send(frame.samples);
+        })
+        .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !source.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        // Let the DSP thread drain what is left in the ring buffer
+        thread::sleep(Duration::from_millis(100));
+        pipeline.stop();
+
+        rx.try_iter().collect()
+    }
+
+    #[test]
+    fn test_decode_raw() {
+        let bytes: Vec<u8> = [0i16, 16384, -32768].iter().flat_map(|s| s.to_le_bytes()).collect();
+        assert_eq!(decode_raw(&bytes, RawFormat::S16Le), vec![0.0, 0.5, -1.0]);
+
+        let bytes: Vec<u8> = [0.25f32, -0.75].iter().flat_map(|s| s.to_le_bytes()).collect();
+        assert_eq!(decode_raw(&bytes, RawFormat::F32Le), vec![0.25, -0.75]);
+    }
+
+    #[test]
+    fn test_wav_roundtrip_any_format() {
+        let dir = std::env::temp_dir();
+        for (rate, channels, bits) in [(44100u32, 2u16, 16u16), (8000, 1, 24), (48000, 3, 32)] {
+            let path = dir.join(format!("rustyn_file_source_{}_{}_{}.wav", rate, channels, bits));
+            let spec = hound::WavSpec {
+                channels,
+                sample_rate: rate,
+                bits_per_sample: bits,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+            let peak = ((1i64 << (bits - 1)) - 1) as f32;
+            for s in tone(rate, channels, 0.1) {
+                writer.write_sample((s * peak) as i32).unwrap();
+            }
+            writer.finalize().unwrap();
+
+            let source = FileAudioSource::open(&path, FileFormat::Wav, Pacing::AsFastAsPossible).unwrap();
+            assert_eq!(source.sample_rate(), rate);
+            assert_eq!(source.channels(), channels);
+            assert!((source.duration().as_secs_f32() - 0.1).abs() < 0.01);
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+
+    #[test]
+    fn test_pipeline_as_fast_as_possible() {
+        // 2 seconds of 44.1kHz stereo -> 100 frames of 20ms at 16kHz
+        let source = FileAudioSource::from_samples(tone(44100, 2, 2.0), 44100, 2, Pacing::AsFastAsPossible).unwrap();
+        let frames = run_pipeline(source);
+
+        assert!(frames.len() >= 98 && frames.len() <= 100, "got {} frames", frames.len());
+        assert!(frames.iter().all(|f| f.len() == FRAME_SAMPLES));
+
+        // 10ms of 96kHz x 48 channels does not fit the ring buffer at once
+        let source = FileAudioSource::from_samples(tone(96_000, 48, 0.5), 96_000, 48, Pacing::AsFastAsPossible).unwrap();
+        let frames = run_pipeline(source);
+        assert!(frames.len() >= 23 && frames.len() <= 25, "got {} frames", frames.len());
+    }
+
+    #[test]
+    fn test_pipeline_silence_is_deterministic() {
+        // 1s tone then 2s silence: output must be identical on every run
+        let mut samples = tone(16000, 1, 1.0);
+        samples.extend(std::iter::repeat_n(0.0, 32000));
+
+        let first = run_pipeline(
+            FileAudioSource::from_samples(samples.clone(), 16000, 1, Pacing::AsFastAsPossible).unwrap(),
+        );
+        let second = run_pipeline(
+            FileAudioSource::from_samples(samples, 16000, 1, Pacing::AsFastAsPossible).unwrap(),
+        );
+
+        assert_eq!(first, second);
+        // 50 speech frames + hangover + one keepalive per 100ms of silence
+        assert!(first.len() > 60 && first.len() < 90, "got {} frames", first.len());
+    }
+
+    #[test]
+    fn test_realtime_pacing() {
+        let mut source = FileAudioSource::from_samples(tone(16000, 1, 0.2), 16000, 1, Pacing::RealTime).unwrap();
+        let started = Instant::now();
+        source.start().unwrap();
+        while !source.is_finished() {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(started.elapsed() >= Duration::from_millis(180));
+    }
+}
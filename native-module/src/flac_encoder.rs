@@ -0,0 +1,626 @@
+// FLAC Encoder - lossless 16-bit recording without external codecs
+//
+// Deliberately small subset of the format, enough for speech recordings:
+// - fixed blocksize (BLOCK_SIZE), independent channels, 16 bits/sample
+// - per channel: CONSTANT, VERBATIM or FIXED (order 0-4) subframe,
+//   whichever is smallest
+// - partitioned Rice residuals with a per-partition parameter
+//
+// Every frame is self-contained (own header and CRCs), so a truncated file
+// decodes up to its last complete frame. `checkpoint()` rewrites STREAMINFO
+// with the running sample count; it is 0 ("unknown") only before the first
+// checkpoint, which decoders accept.
+
+use anyhow::{anyhow, Result};
+use std::io::{Seek, SeekFrom, Write};
+
+/// Samples per channel in every frame but the last
+pub const BLOCK_SIZE: usize = 4096;
+
+/// Offset of the STREAMINFO body ("fLaC" + metadata block header)
+const STREAMINFO_OFFSET: u64 = 8;
+
+const STREAMINFO_LEN: usize = 34;
+
+const MAX_FIXED_ORDER: usize = 4;
+
+const MAX_PARTITION_ORDER: u32 = 8;
+
+/// Largest Rice parameter for the 4-bit parameter field (15 is the escape code)
+const MAX_RICE_PARAM: u32 = 14;
+
+pub struct FlacWriter<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    channels: usize,
+    /// Interleaved samples not yet filling a block
+    pending: Vec<i16>,
+    frame_number: u32,
+    /// Samples per channel written as frames
+    total_samples: u64,
+    min_frame_bytes: u32,
+    max_frame_bytes: u32,
+    channel_buffer: Vec<i32>,
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+    pub fn new(mut writer: W, sample_rate: u32, channels: u16) -> Result<Self> {
+        if !(1..=8).contains(&channels) {
+            return Err(anyhow!("FLAC supports 1-8 channels, got {}", channels));
+        }
+        if sample_rate == 0 || sample_rate >= 1 << 20 {
+            return Err(anyhow!("Unsupported FLAC sample rate: {}", sample_rate));
+        }
+
+        writer.write_all(b"fLaC")?;
+        // Last metadata block, type 0 (STREAMINFO)
+        writer.write_all(&[0x80, 0, 0, STREAMINFO_LEN as u8])?;
+
+        let mut flac = Self {
+            writer,
+            sample_rate,
+            channels: channels as usize,
+            pending: Vec::with_capacity(BLOCK_SIZE * channels as usize),
+            frame_number: 0,
+            total_samples: 0,
+            min_frame_bytes: 0,
+            max_frame_bytes: 0,
+            channel_buffer: Vec::with_capacity(BLOCK_SIZE),
+        };
+        let info = flac.streaminfo();
+        flac.writer.write_all(&info)?;
+        Ok(flac)
+    }
+
+    /// Append interleaved samples (length must be a multiple of channels)
+    pub fn write_samples(&mut self, interleaved: &[i16]) -> Result<()> {
+        self.pending.extend_from_slice(interleaved);
+        let block = BLOCK_SIZE * self.channels;
+        while self.pending.len() >= block {
+            let frame: Vec<i16> = self.pending.drain(..block).collect();
+            self.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Make everything written so far decodable: update STREAMINFO and flush.
+    /// Samples short of a full block stay buffered.
+    pub fn checkpoint(&mut self) -> Result<()> {
+        self.update_streaminfo()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Encode the remaining samples as a final short frame and close the stream
+    pub fn finalize(mut self) -> Result<()> {
+        let whole = self.pending.len() - self.pending.len() % self.channels;
+        if whole > 0 {
+            let frame: Vec<i16> = self.pending.drain(..whole).collect();
+            self.write_frame(&frame)?;
+        }
+        self.checkpoint()
+    }
+
+    /// Samples per channel written so far, including buffered ones
+    pub fn duration(&self) -> u64 {
+        self.total_samples + (self.pending.len() / self.channels) as u64
+    }
+
+    fn write_frame(&mut self, interleaved: &[i16]) -> Result<()> {
+        let block_size = interleaved.len() / self.channels;
+        let mut bits = BitWriter::with_capacity(interleaved.len() * 2);
+
+        // Frame header: sync + fixed blocksize, blocksize from the 16-bit
+        // field below, sample rate from STREAMINFO, 16 bits/sample
+        bits.write(0xFFF8, 16);
+        bits.write(0b0111, 4);
+        bits.write(0b0000, 4);
+        bits.write(self.channels as u64 - 1, 4);
+        bits.write(0b100, 3);
+        bits.write(0, 1);
+        for byte in utf8_number(self.frame_number) {
+            bits.write(byte as u64, 8);
+        }
+        bits.write(block_size as u64 - 1, 16);
+        let header_crc = crc8(bits.bytes());
+        bits.write(header_crc as u64, 8);
+
+        for channel in 0..self.channels {
+            self.channel_buffer.clear();
+            self.channel_buffer.extend(
+                interleaved.iter().skip(channel).step_by(self.channels).map(|&s| s as i32),
+            );
+            write_subframe(&mut bits, &self.channel_buffer);
+        }
+
+        bits.align();
+        let footer_crc = crc16(bits.bytes());
+        bits.write(footer_crc as u64, 16);
+
+        let bytes = bits.into_bytes();
+        self.writer.write_all(&bytes)?;
+
+        let len = bytes.len() as u32;
+        self.min_frame_bytes = if self.frame_number == 0 { len } else { self.min_frame_bytes.min(len) };
+        self.max_frame_bytes = self.max_frame_bytes.max(len);
+        self.frame_number += 1;
+        self.total_samples += block_size as u64;
+        Ok(())
+    }
+
+    fn streaminfo(&self) -> Vec<u8> {
+        let mut bits = BitWriter::with_capacity(STREAMINFO_LEN);
+        bits.write(BLOCK_SIZE as u64, 16);
+        bits.write(BLOCK_SIZE as u64, 16);
+        bits.write(self.min_frame_bytes as u64, 24);
+        bits.write(self.max_frame_bytes as u64, 24);
+        bits.write(self.sample_rate as u64, 20);
+        bits.write(self.channels as u64 - 1, 3);
+        bits.write(15, 5);
+        bits.write(self.total_samples >> 32, 4);
+        bits.write(self.total_samples & 0xFFFF_FFFF, 32);
+        // MD5 left as zero ("not computed")
+        for _ in 0..4 {
+            bits.write(0, 32);
+        }
+        bits.into_bytes()
+    }
+
+    fn update_streaminfo(&mut self) -> Result<()> {
+        let info = self.streaminfo();
+        let position = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(STREAMINFO_OFFSET))?;
+        self.writer.write_all(&info)?;
+        self.writer.seek(SeekFrom::Start(position))?;
+        Ok(())
+    }
+}
+
+// ============================================================================
+// SUBFRAMES
+// ============================================================================
+
+/// Write the smallest of CONSTANT, FIXED(0..=4) and VERBATIM
+fn write_subframe(bits: &mut BitWriter, samples: &[i32]) {
+    if samples.iter().all(|&s| s == samples[0]) {
+        bits.write(0, 1);
+        bits.write(0b000000, 6);
+        bits.write(0, 1);
+        bits.write_signed(samples[0], 16);
+        return;
+    }
+
+    let verbatim_bits = 16 * samples.len() as u64;
+    let mut best: Option<(usize, Vec<i32>, RicePlan)> = None;
+    for order in 0..=MAX_FIXED_ORDER.min(samples.len() - 1) {
+        let residual = fixed_residual(samples, order);
+        let plan = plan_rice(&residual, samples.len(), order);
+        let cost = 16 * order as u64 + plan.bits;
+        if best.as_ref().is_none_or(|(o, _, p)| cost < 16 * *o as u64 + p.bits) {
+            best = Some((order, residual, plan));
+        }
+    }
+
+    match best {
+        Some((order, residual, plan)) if 16 * order as u64 + plan.bits < verbatim_bits => {
+            bits.write(0, 1);
+            bits.write(0b001000 | order as u64, 6);
+            bits.write(0, 1);
+            for &warmup in &samples[..order] {
+                bits.write_signed(warmup, 16);
+            }
+            write_residual(bits, &residual, samples.len(), order, &plan);
+        }
+        _ => {
+            bits.write(0, 1);
+            bits.write(0b000001, 6);
+            bits.write(0, 1);
+            for &sample in samples {
+                bits.write_signed(sample, 16);
+            }
+        }
+    }
+}
+
+/// Residual of the fixed polynomial predictor of `order` (first `order` samples are warm-up)
+fn fixed_residual(samples: &[i32], order: usize) -> Vec<i32> {
+    let x = |i: usize| samples[i] as i64;
+    (order..samples.len())
+        .map(|i| {
+            let r = match order {
+                0 => x(i),
+                1 => x(i) - x(i - 1),
+                2 => x(i) - 2 * x(i - 1) + x(i - 2),
+                3 => x(i) - 3 * x(i - 1) + 3 * x(i - 2) - x(i - 3),
+                _ => x(i) - 4 * x(i - 1) + 6 * x(i - 2) - 4 * x(i - 3) + x(i - 4),
+            };
+            r as i32
+        })
+        .collect()
+}
+
+/// Chosen partition order and Rice parameters, plus the residual size in bits
+struct RicePlan {
+    partition_order: u32,
+    params: Vec<u32>,
+    bits: u64,
+}
+
+fn zigzag(r: i32) -> u64 {
+    ((r as i64) << 1 ^ (r as i64) >> 63) as u64
+}
+
+/// Pick the partition order and per-partition Rice parameters
+fn plan_rice(residual: &[i32], block_size: usize, order: usize) -> RicePlan {
+    let mut best: Option<RicePlan> = None;
+    for partition_order in 0..=MAX_PARTITION_ORDER {
+        let partitions = 1usize << partition_order;
+        if !block_size.is_multiple_of(partitions) || block_size / partitions <= order {
+            break;
+        }
+        let mut params = Vec::with_capacity(partitions);
+        let mut bits = 2 + 4; // coding method + partition order
+        let mut start = 0;
+        for p in 0..partitions {
+            let len = block_size / partitions - if p == 0 { order } else { 0 };
+            let (param, cost) = best_rice_param(&residual[start..start + len]);
+            params.push(param);
+            bits += 4 + cost;
+            start += len;
+        }
+        if best.as_ref().is_none_or(|b| bits < b.bits) {
+            best = Some(RicePlan { partition_order, params, bits });
+        }
+    }
+    best.expect("partition order 0 always fits")
+}
+
+/// Rice parameter with the fewest bits for this partition, and that bit count
+fn best_rice_param(partition: &[i32]) -> (u32, u64) {
+    if partition.is_empty() {
+        return (0, 0);
+    }
+    let sum: u64 = partition.iter().map(|&r| zigzag(r)).sum();
+    let mean = sum / partition.len() as u64;
+    let estimate = if mean == 0 { 0 } else { (63 - mean.leading_zeros()).min(MAX_RICE_PARAM) };
+
+    let cost = |k: u32| {
+        partition.len() as u64 * (k as u64 + 1)
+            + partition.iter().map(|&r| zigzag(r) >> k).sum::<u64>()
+    };
+    (estimate.saturating_sub(1)..=(estimate + 1).min(MAX_RICE_PARAM))
+        .map(|k| (k, cost(k)))
+        .min_by_key(|&(_, bits)| bits)
+        .expect("non-empty parameter range")
+}
+
+fn write_residual(bits: &mut BitWriter, residual: &[i32], block_size: usize, order: usize, plan: &RicePlan) {
+    bits.write(0b00, 2); // Rice coding, 4-bit parameters
+    bits.write(plan.partition_order as u64, 4);
+    let partitions = 1usize << plan.partition_order;
+    let mut start = 0;
+    for (p, &k) in plan.params.iter().enumerate() {
+        let len = block_size / partitions - if p == 0 { order } else { 0 };
+        bits.write(k as u64, 4);
+        for &r in &residual[start..start + len] {
+            let u = zigzag(r);
+            bits.write_unary(u >> k);
+            bits.write(u & ((1 << k) - 1), k);
+        }
+        start += len;
+    }
+}
+
+// ============================================================================
+// BIT WRITER / CHECKSUMS
+// ============================================================================
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn with_capacity(bytes: usize) -> Self {
+        Self { bytes: Vec::with_capacity(bytes), acc: 0, bits: 0 }
+    }
+
+    /// Write the low `n` bits of `value`, MSB first (n <= 32)
+    fn write(&mut self, value: u64, n: u32) {
+        if n == 0 {
+            return;
+        }
+        self.acc = (self.acc << n) | (value & ((1u64 << n) - 1));
+        self.bits += n;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.bytes.push((self.acc >> self.bits) as u8);
+        }
+        self.acc &= (1u64 << self.bits) - 1;
+    }
+
+    fn write_signed(&mut self, value: i32, n: u32) {
+        self.write(value as u32 as u64, n);
+    }
+
+    /// `count` zero bits followed by a one
+    fn write_unary(&mut self, mut count: u64) {
+        while count >= 32 {
+            self.write(0, 32);
+            count -= 32;
+        }
+        self.write(1, count as u32 + 1);
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+
+    /// Completed bytes (excludes a partial trailing byte)
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+/// Frame number in FLAC's UTF-8-like variable length coding
+fn utf8_number(value: u32) -> Vec<u8> {
+    if value < 0x80 {
+        return vec![value as u8];
+    }
+    let len = match value {
+        0..=0x7FF => 2,
+        0x800..=0xFFFF => 3,
+        0x1_0000..=0x1F_FFFF => 4,
+        0x20_0000..=0x3FF_FFFF => 5,
+        _ => 6,
+    };
+    let mut out = Vec::with_capacity(len);
+    let prefix = !(0xFFu8 >> len);
+    out.push(prefix | (value >> (6 * (len - 1))) as u8);
+    for i in (0..len - 1).rev() {
+        out.push(0x80 | ((value >> (6 * i)) & 0x3F) as u8);
+    }
+    out
+}
+
+/// CRC-8, polynomial x^8 + x^2 + x + 1 (frame header)
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// CRC-16, polynomial x^16 + x^15 + x^2 + 1 (whole frame)
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// Minimal decoder for exactly the subset written above (test oracle)
+#[cfg(test)]
+pub mod decode {
+    use super::{crc16, crc8};
+
+    struct BitReader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl BitReader<'_> {
+        /// None past the end of the data (truncated frame)
+        fn read(&mut self, n: u32) -> Option<u64> {
+            let mut value = 0u64;
+            for _ in 0..n {
+                let byte = *self.data.get(self.pos / 8)?;
+                value = (value << 1) | ((byte >> (7 - self.pos % 8)) & 1) as u64;
+                self.pos += 1;
+            }
+            Some(value)
+        }
+
+        fn read_signed(&mut self, n: u32) -> Option<i32> {
+            let value = self.read(n)? as i64;
+            Some((if value >> (n - 1) & 1 == 1 { value - (1 << n) } else { value }) as i32)
+        }
+
+        fn read_unary(&mut self) -> Option<u64> {
+            let mut count = 0;
+            while self.read(1)? == 0 {
+                count += 1;
+            }
+            Some(count)
+        }
+    }
+
+    /// (sample rate, channels, STREAMINFO total samples, interleaved samples);
+    /// stops at the first incomplete or corrupt frame like a player would
+    pub fn decode(data: &[u8]) -> (u32, u16, u64, Vec<i16>) {
+        assert_eq!(&data[..4], b"fLaC");
+        let mut info = BitReader { data: &data[8..42], pos: 0 };
+        info.read(16 + 16 + 24 + 24);
+        let sample_rate = info.read(20).unwrap() as u32;
+        let channels = info.read(3).unwrap() as u16 + 1;
+        assert_eq!(info.read(5), Some(15));
+        let total = info.read(36).unwrap();
+
+        let mut out = Vec::new();
+        let mut offset = 42;
+        while let Some((samples, len)) = decode_frame(&data[offset..], channels as usize) {
+            out.extend(samples);
+            offset += len;
+        }
+        (sample_rate, channels, total, out)
+    }
+
+    fn decode_frame(data: &[u8], channels: usize) -> Option<(Vec<i16>, usize)> {
+        let mut bits = BitReader { data, pos: 0 };
+        if bits.read(16)? != 0xFFF8 || bits.read(4)? != 0b0111 || bits.read(4)? != 0 {
+            return None;
+        }
+        assert_eq!(bits.read(4)? as usize, channels - 1);
+        assert_eq!(bits.read(3)?, 0b100);
+        bits.read(1)?;
+        let first = bits.read(8)? as u8;
+        for _ in 1..first.leading_ones().max(1) {
+            bits.read(8)?;
+        }
+        let block_size = bits.read(16)? as usize + 1;
+        let header_len = bits.pos / 8;
+        if bits.read(8)? as u8 != crc8(&data[..header_len]) {
+            return None;
+        }
+
+        let mut decoded = Vec::with_capacity(channels);
+        for _ in 0..channels {
+            decoded.push(read_subframe(&mut bits, block_size)?);
+        }
+        if !bits.pos.is_multiple_of(8) {
+            bits.read(8 - (bits.pos % 8) as u32)?;
+        }
+        let len = bits.pos / 8;
+        if bits.read(16)? as u16 != crc16(&data[..len]) {
+            return None;
+        }
+
+        let samples = (0..block_size)
+            .flat_map(|i| decoded.iter().map(move |c: &Vec<i32>| c[i] as i16))
+            .collect();
+        Some((samples, len + 2))
+    }
+
+    fn read_subframe(bits: &mut BitReader, block_size: usize) -> Option<Vec<i32>> {
+        bits.read(1)?;
+        let kind = bits.read(6)?;
+        bits.read(1)?;
+        match kind {
+            0 => Some(vec![bits.read_signed(16)?; block_size]),
+            1 => (0..block_size).map(|_| bits.read_signed(16)).collect(),
+            k if k & 0b111000 == 0b001000 => {
+                let order = (k & 0b111) as usize;
+                let mut x = Vec::with_capacity(block_size);
+                for _ in 0..order {
+                    x.push(bits.read_signed(16)?);
+                }
+                assert_eq!(bits.read(2)?, 0);
+                let partitions = 1usize << bits.read(4)?;
+                for p in 0..partitions {
+                    let k = bits.read(4)? as u32;
+                    let len = block_size / partitions - if p == 0 { order } else { 0 };
+                    for _ in 0..len {
+                        let u = (bits.read_unary()? << k) | bits.read(k)?;
+                        let r = ((u >> 1) as i64) ^ -((u & 1) as i64);
+                        let n = x.len();
+                        let h = |i: usize| x[n - i] as i64;
+                        let prediction = match order {
+                            0 => 0,
+                            1 => h(1),
+                            2 => 2 * h(1) - h(2),
+                            3 => 3 * h(1) - 3 * h(2) + h(3),
+                            _ => 4 * h(1) - 6 * h(2) + 4 * h(3) - h(4),
+                        };
+                        x.push((prediction + r) as i32);
+                    }
+                }
+                Some(x)
+            }
+            other => panic!("unexpected subframe type {:#08b}", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use std::io::Cursor;
+
+    fn encode(samples: &[i16], channels: u16) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        let mut flac = FlacWriter::new(&mut cursor, 16_000, channels).unwrap();
+        // Odd write sizes, like frames arriving from the pipeline
+        for chunk in samples.chunks(320 * channels as usize) {
+            flac.write_samples(chunk).unwrap();
+        }
+        flac.finalize().unwrap();
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn test_round_trip_is_lossless() {
+        let speech = test_fixtures::to_i16(&test_fixtures::speech(3.3, 31));
+        let data = encode(&speech, 1);
+        let (rate, channels, total, decoded) = decode::decode(&data);
+        assert_eq!((rate, channels, total), (16_000, 1, speech.len() as u64));
+        assert_eq!(decoded, speech);
+        assert!(data.len() < speech.len() * 2 * 3 / 4,
+            "{:.2} of LINEAR16 size", data.len() as f64 / (speech.len() * 2) as f64);
+
+        // Stereo with full-scale noise (verbatim subframes) and digital silence (constant)
+        let mut rng = test_fixtures::Noise::new(32);
+        let stereo: Vec<i16> = (0..10_000)
+            .flat_map(|i| [(rng.next() * 32767.0) as i16, if i < 5000 { 0 } else { i16::MIN }])
+            .collect();
+        let (_, channels, _, decoded) = decode::decode(&encode(&stereo, 2));
+        assert_eq!(channels, 2);
+        assert_eq!(decoded, stereo);
+    }
+
+    #[test]
+    fn test_independent_decoder_reads_output() {
+        // claxon shares no code with the encoder or decode::decode
+        let speech = test_fixtures::to_i16(&test_fixtures::speech(2.1, 34));
+        let mut rng = test_fixtures::Noise::new(35);
+        let stereo: Vec<i16> = speech.iter().flat_map(|&s| [s, (rng.next() * 32767.0) as i16]).collect();
+
+        for (samples, channels) in [(&speech, 1u16), (&stereo, 2)] {
+            let mut reader = claxon::FlacReader::new(Cursor::new(encode(samples, channels))).unwrap();
+            let info = reader.streaminfo();
+            assert_eq!((info.sample_rate, info.channels, info.bits_per_sample), (16_000, channels as u32, 16));
+            assert_eq!(info.samples, Some((samples.len() / channels as usize) as u64));
+            let decoded: Vec<i16> = reader.samples().map(|s| s.unwrap() as i16).collect();
+            assert_eq!(&decoded, samples);
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_survives_crash() {
+        let speech = test_fixtures::to_i16(&test_fixtures::speech(1.5, 33));
+        let mut cursor = Cursor::new(Vec::new());
+        let mut flac = FlacWriter::new(&mut cursor, 16_000, 1).unwrap();
+        flac.write_samples(&speech).unwrap();
+        flac.checkpoint().unwrap();
+        // Process dies: no finalize, partial block never written
+        std::mem::forget(flac);
+
+        let (_, _, total, decoded) = decode::decode(cursor.get_ref());
+        let whole_blocks = speech.len() / BLOCK_SIZE * BLOCK_SIZE;
+        assert_eq!(total, whole_blocks as u64);
+        assert_eq!(decoded, speech[..whole_blocks]);
+    }
+
+    #[test]
+    fn test_frame_numbers() {
+        assert_eq!(utf8_number(0x7F), vec![0x7F]);
+        assert_eq!(utf8_number(0x80), vec![0xC2, 0x80]);
+        assert_eq!(utf8_number(0x1234), vec![0xE1, 0x88, 0xB4]);
+    }
+}
@@ -0,0 +1,155 @@
+// Audio History - "replay the last N seconds"
+//
+// Keeps a rolling window of the processed (echo cancelled, denoised, gain
+// controlled) output audio of one capture, taken before the silence gate,
+// so audio that never reached STT can still be re-transcribed.
+//
+// The DSP thread appends every frame; JS reads any range of the window.
+// Memory is fixed at construction: 120s at 16kHz is ~3.8MB.
+
+use anyhow::Result;
+use std::io::Cursor;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Longest history a capture can keep
+pub const MAX_HISTORY_SECONDS: u32 = 600;
+
+struct HistoryRing {
+    samples: Vec<i16>,
+    /// Next write index
+    write_pos: usize,
+    /// Samples currently held (<= samples.len())
+    filled: usize,
+}
+
+pub struct AudioHistory {
+    sample_rate: u32,
+    ring: Mutex<HistoryRing>,
+}
+
+impl AudioHistory {
+    pub fn new(sample_rate: u32, duration: Duration) -> Self {
+        let capacity = ((duration.as_secs_f64() * sample_rate as f64) as usize).max(1);
+        println!("[AudioHistory] Keeping the last {:.0}s ({} samples)", duration.as_secs_f64(), capacity);
+        Self {
+            sample_rate,
+            ring: Mutex::new(HistoryRing {
+                samples: vec![0; capacity],
+                write_pos: 0,
+                filled: 0,
+            }),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Append one frame (called on the DSP thread)
+    pub fn push(&self, frame: &[i16]) {
+        let mut ring = self.ring.lock().unwrap_or_else(|e| e.into_inner());
+        let capacity = ring.samples.len();
+        // Only the newest `capacity` samples can survive
+        let frame = &frame[frame.len().saturating_sub(capacity)..];
+
+        let pos = ring.write_pos;
+        let first = frame.len().min(capacity - pos);
+        ring.samples[pos..pos + first].copy_from_slice(&frame[..first]);
+        ring.samples[..frame.len() - first].copy_from_slice(&frame[first..]);
+        ring.write_pos = (pos + frame.len()) % capacity;
+        ring.filled = (ring.filled + frame.len()).min(capacity);
+    }
+
+    /// Audio currently held
+    pub fn duration(&self) -> Duration {
+        let filled = self.ring.lock().unwrap_or_else(|e| e.into_inner()).filled;
+        Duration::from_secs_f64(filled as f64 / self.sample_rate as f64)
+    }
+
+    /// Samples from `start_ago` to `end_ago` before the newest sample,
+    /// oldest first, clipped to what the history holds
+    pub fn range(&self, start_ago: Duration, end_ago: Duration) -> Vec<i16> {
+        let to_samples = |d: Duration| (d.as_secs_f64() * self.sample_rate as f64).round() as usize;
+        let ring = self.ring.lock().unwrap_or_else(|e| e.into_inner());
+        let start_ago = to_samples(start_ago).min(ring.filled);
+        let end_ago = to_samples(end_ago).min(start_ago);
+
+        let capacity = ring.samples.len();
+        let len = start_ago - end_ago;
+        let start = (ring.write_pos + capacity - start_ago) % capacity;
+        (0..len).map(|i| ring.samples[(start + i) % capacity]).collect()
+    }
+
+    pub fn clear(&self) {
+        let mut ring = self.ring.lock().unwrap_or_else(|e| e.into_inner());
+        ring.write_pos = 0;
+        ring.filled = 0;
+    }
+}
+
+/// Little-endian LINEAR16 bytes
+pub fn to_pcm_bytes(samples: &[i16]) -> Vec<u8> {
+    samples.iter().flat_map(|s| s.to_le_bytes()).collect()
+}
+
+/// Complete mono 16-bit WAV file in memory
+pub fn to_wav_bytes(samples: &[i16], sample_rate: u32) -> Result<Vec<u8>> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut cursor = Cursor::new(Vec::with_capacity(44 + samples.len() * 2));
+    let mut writer = hound::WavWriter::new(&mut cursor, spec)?;
+    let mut samples_writer = writer.get_i16_writer(samples.len() as u32);
+    for &sample in samples {
+        samples_writer.write_sample(sample);
+    }
+    samples_writer.flush()?;
+    writer.finalize()?;
+    Ok(cursor.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp(start: i16, len: usize) -> Vec<i16> {
+        (0..len).map(|i| start.wrapping_add(i as i16)).collect()
+    }
+
+    #[test]
+    fn test_keeps_newest_samples_across_wraparound() {
+        // 1s at 1kHz keeps 1000 samples
+        let history = AudioHistory::new(1000, Duration::from_secs(1));
+        for i in 0..7 {
+            history.push(&ramp(i * 320, 320));
+        }
+        assert_eq!(history.duration(), Duration::from_secs(1));
+
+        // Everything held: samples 1240..2240
+        let all = history.range(Duration::from_secs(5), Duration::ZERO);
+        assert_eq!(all, ramp(1240, 1000));
+
+        // 300ms .. 100ms ago
+        let slice = history.range(Duration::from_millis(300), Duration::from_millis(100));
+        assert_eq!(slice, ramp(1940, 200));
+
+        // Inverted range is empty; a frame longer than the window keeps its tail
+        assert!(history.range(Duration::from_millis(100), Duration::from_millis(300)).is_empty());
+        history.push(&ramp(5000, 1500));
+        assert_eq!(history.range(Duration::from_secs(1), Duration::ZERO), ramp(5500, 1000));
+    }
+
+    #[test]
+    fn test_wav_export() {
+        let samples = ramp(-100, 480);
+        let bytes = to_wav_bytes(&samples, 16_000).unwrap();
+        let reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().sample_rate, 16_000);
+        let decoded: Vec<i16> = reader.into_samples().map(|s| s.unwrap()).collect();
+        assert_eq!(decoded, samples);
+    }
+}
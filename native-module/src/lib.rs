@@ -3,14 +3,8 @@
 #[macro_use]
 extern crate napi_derive;
 
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread;
-use std::time::Duration;
-
 use napi::bindgen_prelude::*;
 use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode, ErrorStrategy};
-use ringbuf::traits::Consumer;
 
 pub mod vad; 
 pub mod microphone;
@@ -18,15 +12,254 @@ pub mod speaker;
 pub mod streaming_resampler;
 pub mod audio_config;
 pub mod silence_suppression;
+pub mod speech_detector;
+pub mod audio_source;
+pub mod pipeline;
+pub mod wakeup;
+pub mod file_source;
+pub mod echo_cancel;
+pub mod noise_suppression;
+pub mod capture_options;
+pub mod agc;
+pub mod stats;
+pub mod flac_encoder;
+pub mod recorder;
+pub mod capture_clock;
+pub mod capture_status;
+pub mod device_watch;
+pub mod downmix;
+pub mod stream_align;
+pub mod history;
+#[cfg(feature = "opus")]
+pub mod opus_encoder;
+#[cfg(feature = "neural-vad")]
+pub mod neural_vad;
+
+#[cfg(test)]
+mod test_fixtures;
 
 // Keep old resampler module for compatibility
 pub mod resampler;
 
-use crate::streaming_resampler::StreamingResampler;
-use crate::audio_config::{FRAME_SAMPLES, DSP_POLL_MS};
-use crate::silence_suppression::{
-    SilenceSuppressor, SilenceSuppressionConfig, FrameAction, generate_silence_frame
-};
+use std::sync::Arc;
+
+use crate::capture_status::{CaptureState, StatusCode, StatusEvent, StatusReporter, StatusSink};
+use crate::capture_options::{CallbackMode, CaptureOptions, CaptureSettings, FrameEncoding};
+use crate::device_watch::{DeviceDescription, DeviceEvent, DeviceMonitor, DeviceSupervisor, DEVICE_POLL_INTERVAL};
+use crate::echo_cancel::{EchoControl, EchoReference};
+use crate::history::AudioHistory;
+use crate::pipeline::{CapturePipeline, OutputFrame, PipelineConfig, VadSink};
+use crate::recorder::{ChannelLayout, Recorder, RecorderConfig, RecordingFormat, RecordingTap};
+use crate::stats::{CaptureStats, PipelineStats};
+use crate::vad::VadEvent;
+
+/// Wrap a JS callback so each frame arrives as little-endian LINEAR16 bytes
+fn create_pcm_callback(callback: JsFunction) -> napi::Result<ThreadsafeFunction<Vec<i16>, ErrorStrategy::Fatal>> {
+    callback.create_threadsafe_function(0, |ctx| {
+        let vec: Vec<i16> = ctx.value;
+        let mut pcm_bytes = Vec::with_capacity(vec.len() * 2);
+        for sample in vec {
+            pcm_bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        Ok(vec![pcm_bytes])
+    })
+}
+
+/// Frame metadata shared by the structured callbacks
+fn set_frame_metadata(object: &mut napi::JsObject, frame: &OutputFrame) -> napi::Result<()> {
+    object.set_named_property("timestamp", capture_clock::monotonic_ms(frame.timestamp))?;
+    object.set_named_property("kind", frame.kind.as_str())?;
+    object.set_named_property("rms", frame.rms as f64)?;
+    Ok(())
+}
+
+/// Wrap a JS callback so each frame arrives as
+/// `{ pcm: Buffer, timestamp, sequence, kind, rms }`
+fn create_frame_callback(callback: JsFunction) -> napi::Result<ThreadsafeFunction<OutputFrame, ErrorStrategy::Fatal>> {
+    callback.create_threadsafe_function(0, |ctx| {
+        let frame: OutputFrame = ctx.value;
+        let mut object = ctx.env.create_object()?;
+        object.set_named_property("pcm", Buffer::from(history::to_pcm_bytes(&frame.samples)))?;
+        object.set_named_property("sequence", frame.sequence as i64)?;
+        set_frame_metadata(&mut object, &frame)?;
+        Ok(vec![object])
+    })
+}
+
+/// Wrap a JS callback so each frame arrives as
+/// `{ data: Buffer, sequence, samples, sampleRate, timestamp, kind, rms }`
+/// (one Opus packet)
+#[cfg(feature = "opus")]
+fn create_opus_callback(
+    callback: JsFunction,
+) -> napi::Result<ThreadsafeFunction<(opus_encoder::EncodedPacket, OutputFrame), ErrorStrategy::Fatal>> {
+    callback.create_threadsafe_function(0, |ctx| {
+        let (packet, frame): (opus_encoder::EncodedPacket, OutputFrame) = ctx.value;
+        let mut object = ctx.env.create_object()?;
+        object.set_named_property("data", Buffer::from(packet.data))?;
+        object.set_named_property("sequence", packet.sequence as i64)?;
+        object.set_named_property("samples", packet.samples)?;
+        object.set_named_property("sampleRate", packet.sample_rate)?;
+        set_frame_metadata(&mut object, &frame)?;
+        Ok(vec![object])
+    })
+}
+
+/// Shared by the capture classes' getHistory()
+fn history_bytes(
+    history: Option<&AudioHistory>,
+    start_seconds_ago: f64,
+    end_seconds_ago: Option<f64>,
+    format: Option<String>,
+) -> napi::Result<Buffer> {
+    let history = history.ok_or_else(|| {
+        napi::Error::from_reason("History is off; set historySeconds in the capture options")
+    })?;
+    let end_seconds_ago = end_seconds_ago.unwrap_or(0.0);
+    let valid = |seconds: f64| seconds.is_finite() && seconds >= 0.0;
+    if !(valid(start_seconds_ago) && valid(end_seconds_ago)) {
+        return Err(napi::Error::new(Status::InvalidArg, "Seconds ago must be finite and >= 0"));
+    }
+
+    // No history holds more than MAX_HISTORY_SECONDS, so clipping to it
+    // changes nothing and keeps huge values in Duration's range
+    let start_seconds_ago = start_seconds_ago.min(history::MAX_HISTORY_SECONDS as f64);
+    let samples = history.range(
+        std::time::Duration::from_secs_f64(start_seconds_ago),
+        std::time::Duration::from_secs_f64(end_seconds_ago.min(start_seconds_ago)),
+    );
+    let bytes = match format.as_deref() {
+        None | Some("pcm") => history::to_pcm_bytes(&samples),
+        Some("wav") => history::to_wav_bytes(&samples, history.sample_rate())
+            .map_err(|e| napi::Error::from_reason(format!("{}", e)))?,
+        Some(other) => {
+            return Err(napi::Error::new(Status::InvalidArg, format!("Unknown history format: {}", other)));
+        }
+    };
+    Ok(bytes.into())
+}
+
+/// Runs on the DSP thread for every frame that should reach JS
+type FrameSink = Box<dyn FnMut(OutputFrame) + Send>;
+
+/// Minimum time between encodeFailed reports
+#[cfg(feature = "opus")]
+const ENCODE_FAILURE_REPORT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Hand frames to the JS callback, encoding them first if configured
+/// (`status` is told about frames dropped by the encoder)
+fn create_frame_sink(
+    callback: JsFunction,
+    settings: &CaptureSettings,
+    #[cfg_attr(not(feature = "opus"), allow(unused_variables))] status: &StatusReporter,
+) -> napi::Result<FrameSink> {
+    match settings.encoding {
+        FrameEncoding::Pcm => match settings.callback_mode {
+            CallbackMode::Frame => {
+                let tsfn = create_frame_callback(callback)?;
+                Ok(Box::new(move |frame| {
+                    tsfn.call(frame, ThreadsafeFunctionCallMode::NonBlocking);
+                }))
+            }
+            CallbackMode::Raw => {
+                let tsfn = create_pcm_callback(callback)?;
+                Ok(Box::new(move |frame| {
+                    tsfn.call(frame.samples, ThreadsafeFunctionCallMode::NonBlocking);
+                }))
+            }
+        },
+        #[cfg(feature = "opus")]
+        FrameEncoding::Opus { bitrate } => {
+            let mut encoder = opus_encoder::OpusFrameEncoder::new(settings.format, bitrate)
+                .map_err(|e| napi::Error::from_reason(format!("{}", e)))?;
+            let tsfn = create_opus_callback(callback)?;
+            let status = status.clone();
+            // Failures since the last report, and when that was
+            let mut dropped = 0u64;
+            let mut last_report: Option<std::time::Instant> = None;
+            Ok(Box::new(move |mut frame| match encoder.encode(&frame.samples) {
+                Ok(packet) => {
+                    // Only the metadata travels on; the packet carries the audio
+                    frame.samples = Vec::new();
+                    tsfn.call((packet, frame), ThreadsafeFunctionCallMode::NonBlocking);
+                }
+                Err(e) => {
+                    eprintln!("[OpusEncoder] {}", e);
+                    dropped += 1;
+                    if last_report.is_none_or(|at| at.elapsed() >= ENCODE_FAILURE_REPORT_INTERVAL) {
+                        status.report(
+                            StatusCode::EncodeFailed,
+                            format!("{} frame(s) dropped, last sequence {}: {}", dropped, frame.sequence, e),
+                        );
+                        dropped = 0;
+                        last_report = Some(std::time::Instant::now());
+                    }
+                }
+            }))
+        }
+    }
+}
+
+/// Wrap a JS callback so each speech start / end arrives as
+/// `{ type: "speechStart" | "speechEnd", timestamp, peakLevel, peakRms, durationMs }`
+fn create_vad_sink(callback: JsFunction) -> napi::Result<VadSink> {
+    let tsfn: ThreadsafeFunction<(VadEvent, std::time::Instant), ErrorStrategy::Fatal> =
+        callback.create_threadsafe_function(0, |ctx| {
+            let (event, timestamp): (VadEvent, std::time::Instant) = ctx.value;
+            let mut object = ctx.env.create_object()?;
+            object.set_named_property("type", event.kind.as_str())?;
+            object.set_named_property("timestamp", capture_clock::monotonic_ms(timestamp))?;
+            object.set_named_property("peakLevel", event.peak_level as u32)?;
+            object.set_named_property("peakRms", event.peak_rms as f64)?;
+            object.set_named_property("durationMs", event.duration.as_secs_f64() * 1000.0)?;
+            Ok(vec![object])
+        })?;
+    Ok(Box::new(move |event, timestamp| {
+        tsfn.call((event, timestamp), ThreadsafeFunctionCallMode::NonBlocking);
+    }))
+}
+
+/// Wrap a JS callback so each status event arrives as
+/// `{ code, state, message, fatal, timestamp }` (+ `device` for deviceChanged)
+fn create_status_sink(callback: JsFunction) -> napi::Result<StatusSink> {
+    let tsfn: ThreadsafeFunction<StatusEvent, ErrorStrategy::Fatal> =
+        callback.create_threadsafe_function(0, |ctx| {
+            let event: StatusEvent = ctx.value;
+            let mut object = ctx.env.create_object()?;
+            object.set_named_property("code", event.code.as_str())?;
+            object.set_named_property("state", event.state.as_str())?;
+            object.set_named_property("message", event.message)?;
+            object.set_named_property("fatal", event.fatal)?;
+            if let Some(device) = event.device {
+                object.set_named_property("device", device)?;
+            }
+            object.set_named_property("timestamp", capture_clock::monotonic_ms(event.timestamp))?;
+            Ok(vec![object])
+        })?;
+    Ok(Box::new(move |event| {
+        tsfn.call(event, ThreadsafeFunctionCallMode::NonBlocking);
+    }))
+}
+
+/// Attach the status callback of a start() and move to Starting
+fn begin_start(status: &StatusReporter, status_callback: Option<JsFunction>) -> napi::Result<()> {
+    status.set_sink(status_callback.map(create_status_sink).transpose()?);
+    status.set_state(CaptureState::Starting, "");
+    Ok(())
+}
+
+/// Report a failed start() and turn it into the JS exception
+fn start_failed(status: &StatusReporter, error: impl std::fmt::Display) -> napi::Error {
+    let message = error.to_string();
+    status.fail(StatusCode::classify(&message), message.clone());
+    napi::Error::from_reason(message)
+}
+
+/// Report Stopped, then detach the status callback
+fn end_stop(status: &StatusReporter) {
+    status.set_state(CaptureState::Stopped, "");
+    status.set_sink(None);
+}
 
 // ============================================================================
 // SYSTEM AUDIO CAPTURE (ScreenCaptureKit on macOS)
@@ -34,27 +267,44 @@ use crate::silence_suppression::{
 
 #[napi]
 pub struct SystemAudioCapture {
-    stop_signal: Arc<AtomicBool>,
-    capture_thread: Option<thread::JoinHandle<()>>,
+    pipeline: Option<CapturePipeline>,
     sample_rate: u32,
-    device_id: Option<String>,
-    input: Option<speaker::SpeakerInput>,
-    stream: Option<speaker::SpeakerStream>,
+    /// Opens the output device on start() and follows device changes
+    devices: DeviceSupervisor,
+    /// Far-end reference for microphone echo cancellation
+    echo_reference: Arc<EchoReference>,
+    recording_tap: Arc<RecordingTap>,
+    settings: CaptureSettings,
+    stats: Arc<PipelineStats>,
+    history: Option<Arc<AudioHistory>>,
+    status: StatusReporter,
 }
 
 #[napi]
 impl SystemAudioCapture {
     #[napi(constructor)]
-    pub fn new(device_id: Option<String>) -> napi::Result<Self> {
+    pub fn new(device_id: Option<String>, options: Option<CaptureOptions>) -> napi::Result<Self> {
         println!("[SystemAudioCapture] Created with lazy init (device: {:?})", device_id);
-        
+        let settings = CaptureSettings::from_options(options)?;
+        let status = StatusReporter::new();
+
         Ok(SystemAudioCapture {
-            stop_signal: Arc::new(AtomicBool::new(false)),
-            capture_thread: None,
-            sample_rate: 16000,
-            device_id,
-            input: None,
-            stream: None,
+            pipeline: None,
+            sample_rate: settings.format.sample_rate,
+            devices: DeviceSupervisor::spawn(
+                "SystemAudioCapture",
+                speaker::OutputDeviceHost::shared(),
+                device_id,
+                status.clone(),
+                DEVICE_POLL_INTERVAL,
+            ),
+            echo_reference: Arc::new(EchoReference::with_sample_rate(settings.format.sample_rate)),
+            recording_tap: Arc::new(RecordingTap::default()),
+            history: settings.history
+                .map(|duration| Arc::new(AudioHistory::new(settings.format.sample_rate, duration))),
+            settings,
+            stats: Arc::new(PipelineStats::default()),
+            status,
         })
     }
 
@@ -63,115 +313,78 @@ impl SystemAudioCapture {
         self.sample_rate
     }
 
+    /// `vadCallback` receives speechStart / speechEnd events,
+    /// `statusCallback` errors, state changes and device changes
     #[napi]
-    pub fn start(&mut self, callback: JsFunction) -> napi::Result<()> {
-        let tsfn: ThreadsafeFunction<Vec<i16>, ErrorStrategy::Fatal> = callback
-            .create_threadsafe_function(0, |ctx| {
-                let vec: Vec<i16> = ctx.value;
-                let mut pcm_bytes = Vec::with_capacity(vec.len() * 2);
-                for sample in vec {
-                    pcm_bytes.extend_from_slice(&sample.to_le_bytes());
-                }
-                Ok(vec![pcm_bytes])
-            })?;
-
-        self.stop_signal.store(false, Ordering::SeqCst);
-        let stop_signal = self.stop_signal.clone();
-        
-        // Lazy init: Create SpeakerInput now
-        let input = if let Some(existing) = self.input.take() {
-            existing
-        } else {
-            println!("[SystemAudioCapture] Creating ScreenCaptureKit stream...");
-            match speaker::SpeakerInput::new(self.device_id.take()) {
-                Ok(i) => i,
-                Err(e) => {
-                    println!("[SystemAudioCapture] Failed: {}. Trying default...", e);
-                    match speaker::SpeakerInput::new(None) {
-                        Ok(i) => i,
-                        Err(e2) => return Err(napi::Error::from_reason(format!("Failed: {}", e2))),
-                    }
-                }
-            }
-        };
-        
-        let mut stream = input.stream();
-        let input_sample_rate = stream.sample_rate() as f64;
-        let mut consumer = stream.take_consumer()
-            .ok_or_else(|| napi::Error::from_reason("Failed to get consumer"))?;
-        
-        self.stream = Some(stream);
-
-        // DSP thread with silence suppression
-        self.capture_thread = Some(thread::spawn(move || {
-            let mut resampler = StreamingResampler::new(input_sample_rate, 16000.0);
-            let mut frame_buffer: Vec<i16> = Vec::with_capacity(FRAME_SAMPLES * 4);
-            let mut raw_batch: Vec<f32> = Vec::with_capacity(4096);
-            
-            // Use system audio config (lower threshold for quieter system audio)
-            let mut suppressor = SilenceSuppressor::new(
-                SilenceSuppressionConfig::for_system_audio()
-            );
-
-            println!("[SystemAudioCapture] DSP thread started (suppression active)");
-
-            loop {
-                if stop_signal.load(Ordering::Relaxed) {
-                    break;
-                }
-                
-                // 1. Drain ring buffer (lock-free)
-                let mut batch_count = 0;
-                while let Some(sample) = consumer.try_pop() {
-                    raw_batch.push(sample);
-                    batch_count += 1;
-                    if raw_batch.len() >= 480 {
-                        break;
-                    }
-                }
-                
-                // 2. Resample
-                if !raw_batch.is_empty() {
-                    let resampled = resampler.resample(&raw_batch);
-                    frame_buffer.extend(resampled);
-                    raw_batch.clear();
-                }
+    pub fn start(
+        &mut self,
+        callback: JsFunction,
+        vad_callback: Option<JsFunction>,
+        status_callback: Option<JsFunction>,
+    ) -> napi::Result<()> {
+        let emit = create_frame_sink(callback, &self.settings, &self.status)?;
+        let vad_events = vad_callback.map(create_vad_sink).transpose()?;
+        begin_start(&self.status, status_callback)?;
 
-                // 3. Process frames with Silence Suppression
-                while frame_buffer.len() >= FRAME_SAMPLES {
-                    let frame: Vec<i16> = frame_buffer.drain(0..FRAME_SAMPLES).collect();
-                    match suppressor.process(&frame) {
-                        FrameAction::Send(audio) => {
-                             tsfn.call(audio, ThreadsafeFunctionCallMode::NonBlocking);
-                        },
-                        FrameAction::SendSilence => {
-                             tsfn.call(generate_silence_frame(FRAME_SAMPLES), ThreadsafeFunctionCallMode::NonBlocking);
-                        },
-                        FrameAction::Suppress => {
-                            // Do nothing (bandwidth saving)
-                        }
-                    }
-                }
-                
-                // 4. Short sleep
-                if frame_buffer.len() < FRAME_SAMPLES {
-                    thread::sleep(Duration::from_millis(DSP_POLL_MS));
-                }
-            }
-            
-            println!("[SystemAudioCapture] DSP thread stopped.");
-        }));
+        // Lazy init: the device is opened now (falling back to the default)
+        println!("[SystemAudioCapture] Creating system audio stream...");
+        let pipeline = self.devices.start_pipeline(
+            PipelineConfig {
+                echo_reference: Some(self.echo_reference.clone()),
+                recording_tap: Some(self.recording_tap.clone()),
+                stats: self.stats.clone(),
+                history: self.history.clone(),
+                vad_events,
+                status: self.status.clone(),
+                ..self.settings.apply(PipelineConfig::for_system_audio())
+            },
+            emit,
+        ).map_err(|e| start_failed(&self.status, format!("Failed: {}", e)))?;
+
+        self.pipeline = Some(pipeline);
+        // A backend that failed while starting has already reported it
+        self.status.transition(CaptureState::Starting, CaptureState::Running, "");
 
         Ok(())
     }
 
     #[napi]
     pub fn stop(&mut self) {
-        self.stop_signal.store(true, Ordering::SeqCst);
-        if let Some(handle) = self.capture_thread.take() {
-            let _ = handle.join();
+        if let Some(mut pipeline) = self.pipeline.take() {
+            pipeline.stop();
         }
-        self.stream = None;
+        self.devices.stop();
+        end_stop(&self.status);
+    }
+
+    #[napi]
+    pub fn get_stats(&self) -> CaptureStats {
+        self.stats.snapshot()
+    }
+
+    /// "idle", "starting", "running", "stalled", "stopped" or "failed"
+    #[napi]
+    pub fn get_state(&self) -> String {
+        self.status.state().as_str().to_string()
+    }
+
+    /// Unsuppressed output audio from `startSecondsAgo` to `endSecondsAgo`
+    /// (default 0) before the newest sample, clipped to the history kept.
+    /// format: "pcm" (default, LINEAR16) or "wav"
+    #[napi]
+    pub fn get_history(
+        &self,
+        start_seconds_ago: f64,
+        end_seconds_ago: Option<f64>,
+        format: Option<String>,
+    ) -> napi::Result<Buffer> {
+        history_bytes(self.history.as_deref(), start_seconds_ago, end_seconds_ago, format)
+    }
+
+    /// Seconds of audio currently held in the history (0 when off)
+    #[napi]
+    pub fn get_history_duration(&self) -> f64 {
+        self.history.as_ref().map_or(0.0, |h| h.duration().as_secs_f64())
     }
 }
 
@@ -181,28 +394,51 @@ impl SystemAudioCapture {
 
 #[napi]
 pub struct MicrophoneCapture {
-    stop_signal: Arc<AtomicBool>,
-    capture_thread: Option<thread::JoinHandle<()>>,
+    pipeline: Option<CapturePipeline>,
     sample_rate: u32,
-    input: Option<microphone::MicrophoneStream>,
+    /// Opens the microphone on start() and follows device changes
+    devices: DeviceSupervisor,
+    echo_control: Arc<EchoControl>,
+    recording_tap: Arc<RecordingTap>,
+    settings: CaptureSettings,
+    stats: Arc<PipelineStats>,
+    history: Option<Arc<AudioHistory>>,
+    status: StatusReporter,
 }
 
 #[napi]
 impl MicrophoneCapture {
     #[napi(constructor)]
-    pub fn new(device_id: Option<String>) -> napi::Result<Self> {
-        let input = match microphone::MicrophoneStream::new(device_id) {
-            Ok(i) => i,
-            Err(e) => return Err(napi::Error::from_reason(format!("Failed: {}", e))),
-        };
-        
-        let sample_rate = 16000;
+    pub fn new(device_id: Option<String>, options: Option<CaptureOptions>) -> napi::Result<Self> {
+        let settings = CaptureSettings::from_options(options)?;
+        let status = StatusReporter::new();
+        if let Err(e) = microphone::check_input_device(device_id.as_deref()) {
+            // Distinct status so JS can detect the missing device and decide on a fallback
+            return Err(match e.downcast_ref::<microphone::MicrophoneError>() {
+                Some(err @ microphone::MicrophoneError::DeviceNotFound(_)) => {
+                    napi::Error::new(Status::InvalidArg, err.to_string())
+                }
+                _ => napi::Error::from_reason(format!("Failed: {}", e)),
+            });
+        }
 
         Ok(MicrophoneCapture {
-            stop_signal: Arc::new(AtomicBool::new(false)),
-            capture_thread: None,
-            sample_rate,
-            input: Some(input),
+            pipeline: None,
+            sample_rate: settings.format.sample_rate,
+            devices: DeviceSupervisor::spawn(
+                "MicrophoneCapture",
+                microphone::InputDeviceHost::shared(),
+                device_id,
+                status.clone(),
+                DEVICE_POLL_INTERVAL,
+            ),
+            echo_control: Arc::new(EchoControl::default()),
+            recording_tap: Arc::new(RecordingTap::default()),
+            history: settings.history
+                .map(|duration| Arc::new(AudioHistory::new(settings.format.sample_rate, duration))),
+            settings,
+            stats: Arc::new(PipelineStats::default()),
+            status,
         })
     }
 
@@ -211,101 +447,497 @@ impl MicrophoneCapture {
         self.sample_rate
     }
 
+    /// `vadCallback` receives speechStart / speechEnd events,
+    /// `statusCallback` errors, state changes and device changes
     #[napi]
-    pub fn start(&mut self, callback: JsFunction) -> napi::Result<()> {
-        let tsfn: ThreadsafeFunction<Vec<i16>, ErrorStrategy::Fatal> = callback
-            .create_threadsafe_function(0, |ctx| {
-                let vec: Vec<i16> = ctx.value;
-                let mut pcm_bytes = Vec::with_capacity(vec.len() * 2);
-                for sample in vec {
-                    pcm_bytes.extend_from_slice(&sample.to_le_bytes());
-                }
-                Ok(vec![pcm_bytes])
-            })?;
-
-        self.stop_signal.store(false, Ordering::SeqCst);
-        let stop_signal = self.stop_signal.clone();
-        
-        let input_ref = self.input.as_mut()
-            .ok_or_else(|| napi::Error::from_reason("Input missing"))?;
-        
-        input_ref.play().map_err(|e| napi::Error::from_reason(format!("{}", e)))?;
-        
-        let input_sample_rate = input_ref.sample_rate() as f64;
-        let mut consumer = input_ref.take_consumer()
-            .ok_or_else(|| napi::Error::from_reason("Failed to get consumer"))?;
-
-        // DSP thread with silence suppression
-        self.capture_thread = Some(thread::spawn(move || {
-            let mut resampler = StreamingResampler::new(input_sample_rate, 16000.0);
-            let mut frame_buffer: Vec<i16> = Vec::with_capacity(FRAME_SAMPLES * 4);
-            let mut raw_batch: Vec<f32> = Vec::with_capacity(4096);
-            
-            // Use microphone config (standard threshold)
-            let mut suppressor = SilenceSuppressor::new(
-                SilenceSuppressionConfig::for_microphone()
-            );
-
-            println!("[MicrophoneCapture] DSP thread started (suppression active)");
-
-            loop {
-                if stop_signal.load(Ordering::Relaxed) {
-                    break;
-                }
-                
-                // 1. Drain ring buffer (lock-free)
-                let mut batch_count = 0;
-                while let Some(sample) = consumer.try_pop() {
-                    raw_batch.push(sample);
-                    batch_count += 1;
-                    if raw_batch.len() >= 480 {
-                        break;
-                    }
-                }
-                
-                // 2. Resample
-                if !raw_batch.is_empty() {
-                    let resampled = resampler.resample(&raw_batch);
-                    frame_buffer.extend(resampled);
-                    raw_batch.clear();
-                }
+    pub fn start(
+        &mut self,
+        callback: JsFunction,
+        vad_callback: Option<JsFunction>,
+        status_callback: Option<JsFunction>,
+    ) -> napi::Result<()> {
+        let emit = create_frame_sink(callback, &self.settings, &self.status)?;
+        let vad_events = vad_callback.map(create_vad_sink).transpose()?;
+        begin_start(&self.status, status_callback)?;
 
-                // 3. Process frames with Silence Suppression
-                while frame_buffer.len() >= FRAME_SAMPLES {
-                    let frame: Vec<i16> = frame_buffer.drain(0..FRAME_SAMPLES).collect();
-                    match suppressor.process(&frame) {
-                        FrameAction::Send(audio) => {
-                             tsfn.call(audio, ThreadsafeFunctionCallMode::NonBlocking);
-                        },
-                        FrameAction::SendSilence => {
-                             tsfn.call(generate_silence_frame(FRAME_SAMPLES), ThreadsafeFunctionCallMode::NonBlocking);
-                        },
-                         FrameAction::Suppress => {
-                            // Do nothing
-                        }
-                    }
-                }
-                
-                // 4. Short sleep
-                if frame_buffer.len() < FRAME_SAMPLES {
-                    thread::sleep(Duration::from_millis(DSP_POLL_MS));
-                }
+        let pipeline = self.devices.start_pipeline(
+            PipelineConfig {
+                echo_control: Some(self.echo_control.clone()),
+                recording_tap: Some(self.recording_tap.clone()),
+                stats: self.stats.clone(),
+                history: self.history.clone(),
+                vad_events,
+                status: self.status.clone(),
+                ..self.settings.apply(PipelineConfig::for_microphone())
+            },
+            emit,
+        ).map_err(|e| start_failed(&self.status, e))?;
+
+        self.pipeline = Some(pipeline);
+        self.status.transition(CaptureState::Starting, CaptureState::Running, "");
+
+        Ok(())
+    }
+
+    #[napi]
+    pub fn stop(&mut self) {
+        if let Some(mut pipeline) = self.pipeline.take() {
+            pipeline.stop();
+        }
+        self.devices.stop();
+        end_stop(&self.status);
+    }
+
+    #[napi]
+    pub fn get_stats(&self) -> CaptureStats {
+        self.stats.snapshot()
+    }
+
+    /// "idle", "starting", "running", "stalled", "stopped" or "failed"
+    #[napi]
+    pub fn get_state(&self) -> String {
+        self.status.state().as_str().to_string()
+    }
+
+    /// Unsuppressed output audio from `startSecondsAgo` to `endSecondsAgo`
+    /// (default 0) before the newest sample, clipped to the history kept.
+    /// format: "pcm" (default, LINEAR16) or "wav"
+    #[napi]
+    pub fn get_history(
+        &self,
+        start_seconds_ago: f64,
+        end_seconds_ago: Option<f64>,
+        format: Option<String>,
+    ) -> napi::Result<Buffer> {
+        history_bytes(self.history.as_deref(), start_seconds_ago, end_seconds_ago, format)
+    }
+
+    /// Seconds of audio currently held in the history (0 when off)
+    #[napi]
+    pub fn get_history_duration(&self) -> f64 {
+        self.history.as_ref().map_or(0.0, |h| h.duration().as_secs_f64())
+    }
+
+    /// Use a SystemAudioCapture's output as the far-end echo reference.
+    /// Takes effect immediately, also while capturing.
+    /// Both captures must use the same sampleRate.
+    #[napi]
+    pub fn set_echo_reference(&mut self, system: &SystemAudioCapture) {
+        self.echo_control.set_reference(Some(system.echo_reference.clone()));
+    }
+
+    #[napi]
+    pub fn clear_echo_reference(&mut self) {
+        self.echo_control.set_reference(None);
+    }
+
+    /// Toggle echo cancellation (off by default; needs an echo reference)
+    #[napi]
+    pub fn set_echo_cancellation(&mut self, enabled: bool) {
+        self.echo_control.set_enabled(enabled);
+    }
+}
+
+// ============================================================================
+// FILE AUDIO CAPTURE (offline replay for tests / CI)
+// ============================================================================
+
+#[napi(object)]
+pub struct FileAudioOptions {
+    /// "wav" (default), "f32" or "s16" for headerless little-endian PCM
+    pub format: Option<String>,
+    /// Sample rate of headerless PCM (ignored for WAV)
+    pub sample_rate: Option<u32>,
+    /// Channel count of headerless PCM (ignored for WAV)
+    pub channels: Option<u32>,
+    /// Pace playback like a live device (default true).
+    /// false replays as fast as the pipeline can process.
+    pub realtime: Option<bool>,
+    /// Suppression profile: "microphone" (default) or "system"
+    pub profile: Option<String>,
+}
+
+#[napi]
+pub struct FileAudioCapture {
+    pipeline: Option<CapturePipeline>,
+    sample_rate: u32,
+    system_profile: bool,
+    source: file_source::FileAudioSource,
+    settings: CaptureSettings,
+    stats: Arc<PipelineStats>,
+    history: Option<Arc<AudioHistory>>,
+    status: StatusReporter,
+}
+
+#[napi]
+impl FileAudioCapture {
+    #[napi(constructor)]
+    pub fn new(
+        path: String,
+        options: Option<FileAudioOptions>,
+        capture_options: Option<CaptureOptions>,
+    ) -> napi::Result<Self> {
+        let settings = CaptureSettings::from_options(capture_options)?;
+        let status = StatusReporter::new();
+        let options = options.unwrap_or(FileAudioOptions {
+            format: None,
+            sample_rate: None,
+            channels: None,
+            realtime: None,
+            profile: None,
+        });
+
+        let raw = |format| -> napi::Result<file_source::FileFormat> {
+            let sample_rate = options.sample_rate
+                .ok_or_else(|| napi::Error::new(Status::InvalidArg, "sampleRate is required for raw PCM"))?;
+            Ok(file_source::FileFormat::Raw {
+                format,
+                sample_rate,
+                channels: options.channels.unwrap_or(1) as u16,
+            })
+        };
+        let format = match options.format.as_deref() {
+            None | Some("wav") => file_source::FileFormat::Wav,
+            Some("f32") => raw(file_source::RawFormat::F32Le)?,
+            Some("s16") => raw(file_source::RawFormat::S16Le)?,
+            Some(other) => {
+                return Err(napi::Error::new(Status::InvalidArg, format!("Unknown format: {}", other)));
             }
-            
-            println!("[MicrophoneCapture] DSP thread stopped.");
-        }));
+        };
+        let pacing = if options.realtime.unwrap_or(true) {
+            file_source::Pacing::RealTime
+        } else {
+            file_source::Pacing::AsFastAsPossible
+        };
+
+        let source = file_source::FileAudioSource::open(&path, format, pacing)
+            .map_err(|e| napi::Error::from_reason(format!("Failed: {}", e)))?;
+
+        Ok(FileAudioCapture {
+            pipeline: None,
+            sample_rate: settings.format.sample_rate,
+            system_profile: options.profile.as_deref() == Some("system"),
+            source,
+            history: settings.history
+                .map(|duration| Arc::new(AudioHistory::new(settings.format.sample_rate, duration))),
+            settings,
+            stats: Arc::new(PipelineStats::default()),
+            status,
+        })
+    }
+
+    #[napi]
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Replays the file from the beginning.
+    /// `vadCallback` receives speechStart / speechEnd events,
+    /// `statusCallback` errors and state changes
+    #[napi]
+    pub fn start(
+        &mut self,
+        callback: JsFunction,
+        vad_callback: Option<JsFunction>,
+        status_callback: Option<JsFunction>,
+    ) -> napi::Result<()> {
+        self.stop();
+        let emit = create_frame_sink(callback, &self.settings, &self.status)?;
+        let vad_events = vad_callback.map(create_vad_sink).transpose()?;
+        begin_start(&self.status, status_callback)?;
+
+        let config = self.settings.apply(if self.system_profile {
+            PipelineConfig::for_system_audio()
+        } else {
+            PipelineConfig::for_microphone()
+        });
+
+        let pipeline = CapturePipeline::start(
+            &mut self.source,
+            PipelineConfig {
+                name: "FileAudioCapture",
+                stats: self.stats.clone(),
+                history: self.history.clone(),
+                vad_events,
+                status: self.status.clone(),
+                // The file ending is not a stall
+                stall_timeout: None,
+                ..config
+            },
+            emit,
+        ).map_err(|e| start_failed(&self.status, e))?;
+
+        self.pipeline = Some(pipeline);
+        self.status.transition(CaptureState::Starting, CaptureState::Running, "");
 
         Ok(())
     }
 
+    /// True once the whole file has been fed to the pipeline
+    #[napi]
+    pub fn is_finished(&self) -> bool {
+        self.source.is_finished()
+    }
+
     #[napi]
     pub fn stop(&mut self) {
-        self.stop_signal.store(true, Ordering::SeqCst);
-        if let Some(handle) = self.capture_thread.take() {
-            let _ = handle.join();
+        if let Some(mut pipeline) = self.pipeline.take() {
+            pipeline.stop();
+        }
+        let _ = audio_source::AudioSource::stop(&mut self.source);
+        end_stop(&self.status);
+    }
+
+    #[napi]
+    pub fn get_stats(&self) -> CaptureStats {
+        self.stats.snapshot()
+    }
+
+    /// "idle", "starting", "running", "stalled", "stopped" or "failed"
+    #[napi]
+    pub fn get_state(&self) -> String {
+        self.status.state().as_str().to_string()
+    }
+
+    /// Unsuppressed output audio from `startSecondsAgo` to `endSecondsAgo`
+    /// (default 0) before the newest sample, clipped to the history kept.
+    /// format: "pcm" (default, LINEAR16) or "wav"
+    #[napi]
+    pub fn get_history(
+        &self,
+        start_seconds_ago: f64,
+        end_seconds_ago: Option<f64>,
+        format: Option<String>,
+    ) -> napi::Result<Buffer> {
+        history_bytes(self.history.as_deref(), start_seconds_ago, end_seconds_ago, format)
+    }
+
+    /// Seconds of audio currently held in the history (0 when off)
+    #[napi]
+    pub fn get_history_duration(&self) -> f64 {
+        self.history.as_ref().map_or(0.0, |h| h.duration().as_secs_f64())
+    }
+}
+
+// ============================================================================
+// RECORDING (WAV / FLAC)
+// ============================================================================
+
+#[napi(object)]
+pub struct RecorderOptions {
+    /// "wav" or "flac" (default: from the file extension, else wav)
+    pub format: Option<String>,
+    /// 1 (default): all captures mixed to mono.
+    /// 2: microphone left, system audio right (needs both).
+    pub channels: Option<u32>,
+}
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct RecordingInfo {
+    pub path: String,
+    pub format: String,
+    pub recording: bool,
+    /// File size so far
+    pub bytes_written: f64,
+    pub duration_ms: f64,
+    /// Frames lost because the disk could not keep up
+    pub dropped_frames: f64,
+}
+
+/// Records one or both captures at their output sample rate, aligned by
+/// capture timestamps. Captures keep running while recordings start and stop.
+#[napi]
+pub struct AudioRecorder {
+    path: String,
+    format: RecordingFormat,
+    layout: ChannelLayout,
+    microphone: Option<Arc<RecordingTap>>,
+    system: Option<Arc<RecordingTap>>,
+    sample_rate: Option<u32>,
+    recorder: Option<Recorder>,
+    last_info: Option<RecordingInfo>,
+}
+
+#[napi]
+impl AudioRecorder {
+    #[napi(constructor)]
+    pub fn new(path: String, options: Option<RecorderOptions>) -> napi::Result<Self> {
+        let options = options.unwrap_or(RecorderOptions { format: None, channels: None });
+        let format = match options.format {
+            Some(format) => RecordingFormat::parse(&format)
+                .map_err(|e| napi::Error::new(Status::InvalidArg, e.to_string()))?,
+            None => RecordingFormat::from_path(std::path::Path::new(&path)),
+        };
+        let layout = match options.channels {
+            None | Some(1) => ChannelLayout::Mono,
+            Some(2) => ChannelLayout::Stereo,
+            Some(other) => {
+                return Err(napi::Error::new(Status::InvalidArg, format!("channels must be 1 or 2, got {}", other)));
+            }
+        };
+        Ok(AudioRecorder {
+            path,
+            format,
+            layout,
+            microphone: None,
+            system: None,
+            sample_rate: None,
+            recorder: None,
+            last_info: None,
+        })
+    }
+
+    #[napi]
+    pub fn add_microphone(&mut self, microphone: &MicrophoneCapture) -> napi::Result<()> {
+        self.check_source(microphone.sample_rate)?;
+        self.sample_rate = Some(microphone.sample_rate);
+        self.microphone = Some(microphone.recording_tap.clone());
+        Ok(())
+    }
+
+    #[napi]
+    pub fn add_system_audio(&mut self, system: &SystemAudioCapture) -> napi::Result<()> {
+        self.check_source(system.sample_rate)?;
+        self.sample_rate = Some(system.sample_rate);
+        self.system = Some(system.recording_tap.clone());
+        Ok(())
+    }
+
+    /// Create (or overwrite) the file and start writing
+    #[napi]
+    pub fn start(&mut self) -> napi::Result<()> {
+        if self.recorder.is_some() {
+            return Err(napi::Error::from_reason("Already recording"));
+        }
+        let sample_rate = self.sample_rate
+            .ok_or_else(|| napi::Error::new(Status::InvalidArg, "Add a capture before starting"))?;
+        let taps: Vec<Arc<RecordingTap>> = self.microphone.iter().chain(self.system.iter()).cloned().collect();
+        if self.layout == ChannelLayout::Stereo && taps.len() != 2 {
+            return Err(napi::Error::new(Status::InvalidArg, "Stereo recording needs a microphone and system audio"));
+        }
+
+        let recorder = Recorder::start(
+            RecorderConfig {
+                path: Some(std::path::PathBuf::from(&self.path)),
+                format: self.format,
+                sample_rate,
+                layout: self.layout,
+                frame_samples: 0,
+            },
+            taps,
+            None,
+        ).map_err(|e| napi::Error::from_reason(format!("{}", e)))?;
+        self.recorder = Some(recorder);
+        Ok(())
+    }
+
+    /// Finalize the file and return its final size and duration
+    #[napi]
+    pub fn stop(&mut self) -> napi::Result<RecordingInfo> {
+        let mut recorder = self.recorder.take()
+            .ok_or_else(|| napi::Error::from_reason("Not recording"))?;
+        let result = recorder.stop();
+        let info = self.info(&recorder);
+        self.last_info = Some(info.clone());
+        result.map_err(|e| napi::Error::from_reason(format!("{}", e)))?;
+        Ok(info)
+    }
+
+    #[napi]
+    pub fn get_info(&self) -> RecordingInfo {
+        match (&self.recorder, &self.last_info) {
+            (Some(recorder), _) => self.info(recorder),
+            (None, Some(info)) => info.clone(),
+            (None, None) => RecordingInfo {
+                path: self.path.clone(),
+                format: self.format.as_str().to_string(),
+                recording: false,
+                bytes_written: 0.0,
+                duration_ms: 0.0,
+                dropped_frames: 0.0,
+            },
+        }
+    }
+
+    fn check_source(&self, sample_rate: u32) -> napi::Result<()> {
+        if self.recorder.is_some() {
+            return Err(napi::Error::from_reason("Cannot add a capture while recording"));
         }
-        if let Some(input) = self.input.as_ref() {
-            let _ = input.pause();
+        if self.sample_rate.is_some_and(|rate| rate != sample_rate) {
+            return Err(napi::Error::new(Status::InvalidArg, "Recorded captures must use the same sampleRate"));
+        }
+        Ok(())
+    }
+
+    fn info(&self, recorder: &Recorder) -> RecordingInfo {
+        RecordingInfo {
+            path: self.path.clone(),
+            format: recorder.format().as_str().to_string(),
+            recording: self.recorder.is_some(),
+            bytes_written: recorder.bytes_written() as f64,
+            duration_ms: recorder.duration().as_secs_f64() * 1000.0,
+            dropped_frames: recorder.dropped_frames() as f64,
+        }
+    }
+}
+
+/// Microphone (left) and system audio (right) aligned by capture
+/// timestamps, delivered as interleaved stereo LINEAR16 frames
+#[napi]
+pub struct StereoMixer {
+    microphone: Arc<RecordingTap>,
+    system: Arc<RecordingTap>,
+    sample_rate: u32,
+    frame_samples: usize,
+    mixer: Option<Recorder>,
+}
+
+#[napi]
+impl StereoMixer {
+    #[napi(constructor)]
+    pub fn new(microphone: &MicrophoneCapture, system: &SystemAudioCapture) -> napi::Result<Self> {
+        if microphone.settings.format != system.settings.format {
+            return Err(napi::Error::new(
+                Status::InvalidArg,
+                "Both captures must use the same sampleRate and frameMs",
+            ));
+        }
+        Ok(StereoMixer {
+            microphone: microphone.recording_tap.clone(),
+            system: system.recording_tap.clone(),
+            sample_rate: microphone.sample_rate,
+            frame_samples: microphone.settings.format.frame_samples(),
+            mixer: None,
+        })
+    }
+
+    /// Callback receives frameMs of interleaved stereo per call
+    #[napi]
+    pub fn start(&mut self, callback: JsFunction) -> napi::Result<()> {
+        self.stop();
+        let tsfn = create_pcm_callback(callback)?;
+        let mixer = Recorder::start(
+            RecorderConfig {
+                path: None,
+                format: RecordingFormat::Wav,
+                sample_rate: self.sample_rate,
+                layout: ChannelLayout::Stereo,
+                frame_samples: self.frame_samples,
+            },
+            vec![self.microphone.clone(), self.system.clone()],
+            Some(Box::new(move |frame| {
+                tsfn.call(frame, ThreadsafeFunctionCallMode::NonBlocking);
+            })),
+        ).map_err(|e| napi::Error::from_reason(format!("{}", e)))?;
+        self.mixer = Some(mixer);
+        Ok(())
+    }
+
+    #[napi]
+    pub fn stop(&mut self) {
+        if let Some(mut mixer) = self.mixer.take() {
+            let _ = mixer.stop();
         }
     }
 }
@@ -318,14 +950,37 @@ impl MicrophoneCapture {
 pub struct AudioDeviceInfo {
     pub id: String,
     pub name: String,
+    /// The current system default device
+    /// (the "default" input entry follows it and is never flagged)
+    pub is_default: bool,
+    /// Sample rates the device accepts, ascending
+    pub sample_rates: Vec<u32>,
+    /// Most channels the device offers
+    pub channels: u32,
+    /// Native sample formats, e.g. "f32", "i16"
+    pub sample_formats: Vec<String>,
+    /// "CoreAudio", "WASAPI", "ALSA" or "PulseAudio"
+    pub host_api: String,
+}
+
+impl From<DeviceDescription> for AudioDeviceInfo {
+    fn from(device: DeviceDescription) -> Self {
+        AudioDeviceInfo {
+            id: device.id,
+            name: device.name,
+            is_default: device.is_default,
+            sample_rates: device.sample_rates,
+            channels: device.channels as u32,
+            sample_formats: device.sample_formats,
+            host_api: device.host_api,
+        }
+    }
 }
 
 #[napi]
 pub fn get_input_devices() -> Vec<AudioDeviceInfo> {
     match microphone::list_input_devices() {
-        Ok(devs) => devs.into_iter()
-            .map(|(id, name)| AudioDeviceInfo { id, name })
-            .collect(),
+        Ok(devs) => devs.into_iter().map(AudioDeviceInfo::from).collect(),
         Err(e) => {
             eprintln!("[get_input_devices] Error: {}", e);
             Vec::new()
@@ -335,13 +990,58 @@ pub fn get_input_devices() -> Vec<AudioDeviceInfo> {
 
 #[napi]
 pub fn get_output_devices() -> Vec<AudioDeviceInfo> {
-    match speaker::list_output_devices() {
-        Ok(devs) => devs.into_iter()
-            .map(|(id, name)| AudioDeviceInfo { id, name })
-            .collect(),
+    match speaker::describe_output_devices() {
+        Ok(devs) => devs.into_iter().map(AudioDeviceInfo::from).collect(),
         Err(e) => {
             eprintln!("[get_output_devices] Error: {}", e);
             Vec::new()
         }
     }
 }
+
+/// Live device-list subscription returned by `watchDevices`
+#[napi]
+pub struct DeviceSubscription {
+    monitor: Option<DeviceMonitor>,
+}
+
+#[napi]
+impl DeviceSubscription {
+    /// Stop watching (also releases the callback)
+    #[napi]
+    pub fn unsubscribe(&mut self) {
+        self.monitor = None;
+    }
+}
+
+/// Call `callback` with `{ type, direction, device }` whenever an input or
+/// output device is added or removed ("added" / "removed") or a default
+/// device changes ("defaultChanged", device is the new default).
+/// direction: "input" or "output"; device: AudioDeviceInfo
+#[napi]
+pub fn watch_devices(callback: JsFunction) -> napi::Result<DeviceSubscription> {
+    let tsfn: ThreadsafeFunction<DeviceEvent, ErrorStrategy::Fatal> =
+        callback.create_threadsafe_function(0, |ctx| {
+            let event: DeviceEvent = ctx.value;
+            let mut object = ctx.env.create_object()?;
+            object.set_named_property("type", event.kind.as_str())?;
+            object.set_named_property("direction", event.direction.as_str())?;
+            object.set_named_property("device", AudioDeviceInfo::from(event.device))?;
+            Ok(vec![object])
+        })?;
+    let monitor = DeviceMonitor::spawn(
+        microphone::InputDeviceHost::shared(),
+        speaker::OutputDeviceHost::shared(),
+        DEVICE_POLL_INTERVAL,
+        Box::new(move |event| {
+            tsfn.call(event, ThreadsafeFunctionCallMode::NonBlocking);
+        }),
+    );
+    Ok(DeviceSubscription { monitor: Some(monitor) })
+}
+
+/// Current time on the clock of frame timestamps (ms since a process-wide epoch)
+#[napi]
+pub fn get_monotonic_time_ms() -> f64 {
+    capture_clock::monotonic_ms(std::time::Instant::now())
+}
@@ -1,57 +1,191 @@
 // Microphone Capture - Lock-Free Real-Time Compliant
 // 
 // Architecture:
-// 1. CPAL callback: ONLY pushes to lock-free ring buffer
+// 1. CPAL callback: ONLY converts to f32 and pushes every channel to
+//    lock-free ring buffer (any cpal sample format)
 // 2. No mutexes, allocations, or DSP in callback
-// 3. Background thread: drains buffer, resamples, emits to JS
+// 3. Background thread: drains buffer, downmixes (channelMode), resamples,
+//    emits to JS
 
 use anyhow::Result;
+use once_cell::sync::Lazy;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{SampleFormat, Stream};
-use ringbuf::{traits::{Producer, Consumer, Split}, HeapRb, HeapProd, HeapCons};
+use cpal::{FromSample, Sample, SampleFormat, SizedSample, Stream};
+use ringbuf::{traits::{Observer, Producer, Split}, HeapRb, HeapProd, HeapCons};
+use std::fmt;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::audio_config::RING_BUFFER_SAMPLES;
+use crate::audio_source::AudioSource;
+use crate::capture_status::{StatusCode, StatusReporter};
+use crate::device_watch::{DeviceCache, DeviceDescription, DeviceHost, SharedDeviceHost, DEVICE_POLL_INTERVAL};
+use crate::stats::OverflowCounter;
+use crate::wakeup::DataNotifier;
+
+/// Rates reported as supported when they fall inside a device's config ranges
+const STANDARD_SAMPLE_RATES: [u32; 12] = [
+    8000, 11025, 16000, 22050, 24000, 32000, 44100, 48000, 88200, 96000, 176400, 192000,
+];
 
 /// List available input devices
-pub fn list_input_devices() -> Result<Vec<(String, String)>> {
-    let host = cpal::default_host();
-    let mut list = Vec::new();
-    list.push(("default".to_string(), "Default Microphone".to_string()));
-    
-    if let Ok(devices) = host.input_devices() {
-        for device in devices {
-            if let Ok(name) = device.name() {
-                list.push((name.clone(), name));
+///
+/// The first entry, "default", follows the system default device and
+/// carries that device's capabilities.
+pub fn list_input_devices() -> Result<Vec<DeviceDescription>> {
+    let mut list = describe_input_devices(&cpal::default_host())?;
+    let mut default = list.iter().find(|d| d.is_default).cloned().unwrap_or_else(|| DeviceDescription {
+        id: String::new(),
+        name: String::new(),
+        is_default: false,
+        sample_rates: Vec::new(),
+        channels: 0,
+        sample_formats: Vec::new(),
+        host_api: cpal::default_host().id().name().to_string(),
+    });
+    default.id = "default".to_string();
+    default.name = "Default Microphone".to_string();
+    default.is_default = false;
+    list.insert(0, default);
+    Ok(list)
+}
+
+/// Every input device of `host` with its supported configs
+fn describe_input_devices(host: &cpal::Host) -> Result<Vec<DeviceDescription>> {
+    let default = host.default_input_device().and_then(|d| d.name().ok());
+    let devices = host.input_devices()
+        .map_err(|e| anyhow::anyhow!("Failed to enumerate input devices: {}", e))?;
+    Ok(devices
+        .filter_map(|device| {
+            let name = device.name().ok()?;
+            let mut description = DeviceDescription {
+                id: name.clone(),
+                is_default: default.as_ref() == Some(&name),
+                name,
+                sample_rates: Vec::new(),
+                channels: 0,
+                sample_formats: Vec::new(),
+                host_api: host.id().name().to_string(),
+            };
+            // A busy or vanishing device may refuse the query; it is still listed
+            for config in device.supported_input_configs().into_iter().flatten() {
+                description.channels = description.channels.max(config.channels());
+                let format = config.sample_format().to_string();
+                if !description.sample_formats.contains(&format) {
+                    description.sample_formats.push(format);
+                }
+                let range = config.min_sample_rate().0..=config.max_sample_rate().0;
+                description.sample_rates.extend(STANDARD_SAMPLE_RATES.iter().filter(|r| range.contains(r)));
             }
+            description.sample_rates.sort_unstable();
+            description.sample_rates.dedup();
+            Some(description)
+        })
+        .collect())
+}
+
+/// Typed microphone errors that callers may want to branch on
+#[derive(Debug, Clone, PartialEq)]
+pub enum MicrophoneError {
+    /// The requested device id is not among the host's input devices
+    DeviceNotFound(String),
+    /// The host has no default input device
+    NoDefaultDevice,
+}
+
+impl fmt::Display for MicrophoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MicrophoneError::DeviceNotFound(id) => write!(f, "Input device not found: {}", id),
+            MicrophoneError::NoDefaultDevice => write!(f, "No input device found"),
         }
     }
-    Ok(list)
+}
+
+impl std::error::Error for MicrophoneError {}
+
+/// Resolve a device id from `list_input_devices` to a cpal device
+///
+/// None, "" and "default" select the host default. Any other id must match
+/// an input device name; a missing device is an error, never a silent default.
+fn find_input_device(host: &cpal::Host, device_id: Option<&str>) -> Result<cpal::Device> {
+    match device_id {
+        Some(id) if !id.is_empty() && id != "default" => {
+            let found = host.input_devices()
+                .map_err(|e| anyhow::anyhow!("Failed to enumerate input devices: {}", e))?
+                .find(|d| d.name().map(|n| n == id).unwrap_or(false));
+            found.ok_or_else(|| MicrophoneError::DeviceNotFound(id.to_string()).into())
+        }
+        _ => host.default_input_device()
+            .ok_or_else(|| MicrophoneError::NoDefaultDevice.into()),
+    }
+}
+
+/// Check that `device_id` resolves to an input device (see `find_input_device`)
+pub fn check_input_device(device_id: Option<&str>) -> Result<()> {
+    find_input_device(&cpal::default_host(), device_id).map(|_| ())
+}
+
+/// Input device snapshot shared by every capture and device monitor
+static INPUT_DEVICES: Lazy<Arc<DeviceCache>> = Lazy::new(|| Arc::new(DeviceCache::new(DEVICE_POLL_INTERVAL)));
+
+/// Input devices of the default cpal host, for DeviceSupervisor
+///
+/// Device ids are device names, like in `list_input_devices`.
+pub struct InputDeviceHost;
+
+impl InputDeviceHost {
+    /// The input host, enumerated at most once per poll interval process-wide
+    pub fn shared() -> SharedDeviceHost<Self> {
+        SharedDeviceHost::new(Self, INPUT_DEVICES.clone())
+    }
+}
+
+impl DeviceHost for InputDeviceHost {
+    fn default_device(&self) -> Option<String> {
+        cpal::default_host().default_input_device().and_then(|d| d.name().ok())
+    }
+
+    fn devices(&self) -> Result<Vec<String>> {
+        let devices = cpal::default_host().input_devices()
+            .map_err(|e| anyhow::anyhow!("Failed to enumerate input devices: {}", e))?;
+        Ok(devices.filter_map(|d| d.name().ok()).collect())
+    }
+
+    fn describe(&self) -> Result<Vec<DeviceDescription>> {
+        describe_input_devices(&cpal::default_host())
+    }
+
+    fn open(&mut self, device: &str, status: &StatusReporter) -> Result<Box<dyn AudioSource>> {
+        Ok(Box::new(MicrophoneStream::new(Some(device.to_string()), status.clone())?))
+    }
 }
 
 /// Lock-free microphone stream
 /// 
-/// Callback pushes raw f32 samples to ring buffer.
+/// Callback pushes raw f32 samples (all channels) to ring buffer.
 /// Consumer is polled by DSP thread.
 pub struct MicrophoneStream {
     stream: Option<Stream>,
     consumer: Option<HeapCons<f32>>,
     sample_rate: u32,
+    channels: u16,
     is_running: Arc<AtomicBool>,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
 }
 
 impl MicrophoneStream {
-    pub fn new(_device_id: Option<String>) -> Result<Self> {
+    /// `status` receives stream errors (e.g. the device being unplugged)
+    pub fn new(device_id: Option<String>, status: StatusReporter) -> Result<Self> {
         let host = cpal::default_host();
-        let device = host.default_input_device()
-            .ok_or_else(|| anyhow::anyhow!("No input device found"))?;
+        let device = find_input_device(&host, device_id.as_deref())?;
         
         let config = device.default_input_config()
             .map_err(|e| anyhow::anyhow!("Failed to get config: {}", e))?;
         
         let sample_rate = config.sample_rate().0;
-        let channels = config.channels() as usize;
+        let channels = config.channels();
         
         println!(
             "[Microphone] Device: {}, Rate: {}Hz, Channels: {}, Format: {:?}", 
@@ -62,26 +196,33 @@ impl MicrophoneStream {
         );
         
         // Create lock-free SPSC ring buffer
-        let rb = HeapRb::<f32>::new(RING_BUFFER_SAMPLES);
+        let rb = ring_buffer(channels);
         let (producer, consumer) = rb.split();
         
         let is_running = Arc::new(AtomicBool::new(false));
         let is_running_clone = is_running.clone();
+        let notifier = DataNotifier::new();
+        let overflow = OverflowCounter::new();
         
         // Build the stream with minimal callback
         let stream = build_input_stream(
             &device, 
             &config, 
             producer, 
-            channels, 
-            is_running_clone
+            is_running_clone,
+            notifier.clone(),
+            overflow.clone(),
+            status,
         )?;
         
         Ok(Self {
             stream: Some(stream),
             consumer: Some(consumer),
             sample_rate,
+            channels,
             is_running,
+            notifier,
+            overflow,
         })
     }
 
@@ -121,88 +262,79 @@ impl MicrophoneStream {
     }
 }
 
+impl AudioSource for MicrophoneStream {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Every device channel, interleaved; the pipeline downmixes
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn take_consumer(&mut self) -> Option<HeapCons<f32>> {
+        self.consumer.take()
+    }
+
+    fn data_notifier(&self) -> DataNotifier {
+        self.notifier.clone()
+    }
+
+    fn overflow_counter(&self) -> OverflowCounter {
+        self.overflow.clone()
+    }
+
+    fn start(&mut self) -> Result<()> {
+        self.play()
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.pause()
+    }
+}
+
+/// Ring buffer for `channels` interleaved channels
+///
+/// Scaled by the channel count so an array mic gets the same headroom in
+/// time as a mono one (~680ms at 48kHz).
+fn ring_buffer(channels: u16) -> HeapRb<f32> {
+    HeapRb::<f32>::new(RING_BUFFER_SAMPLES * channels.max(1) as usize)
+}
+
 /// Build input stream with lock-free callback
 /// 
-/// The callback ONLY pushes to the ring buffer.
-/// No mutexes, allocations, or DSP.
+/// The callback ONLY converts to f32, pushes to the ring buffer and signals
+/// the DSP thread. No mutexes, allocations, or DSP.
 fn build_input_stream(
     device: &cpal::Device,
     config: &cpal::SupportedStreamConfig,
-    mut producer: HeapProd<f32>,
-    channels: usize,
+    producer: HeapProd<f32>,
     is_running: Arc<AtomicBool>,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
+    status: StatusReporter,
 ) -> Result<Stream> {
-    let err_fn = |err| eprintln!("[Microphone] Stream error: {}", err);
-    
+    let sink = SampleSink {
+        producer,
+        is_running,
+        notifier,
+        overflow,
+        channels: config.channels().max(1) as usize,
+    };
+    let err_fn = move |err| report_stream_error(&status, err);
+    let stream_config = config.config();
+
     let stream = match config.sample_format() {
-        SampleFormat::F32 => {
-            device.build_input_stream(
-                &config.clone().into(),
-                move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                    if !is_running.load(Ordering::Relaxed) {
-                        return;
-                    }
-                    // REAL-TIME SAFE: Only lock-free push
-                    // Convert stereo to mono if needed, then push
-                    if channels > 1 {
-                        // Take first channel only (interleaved)
-                        for chunk in data.chunks(channels) {
-                            let _ = producer.try_push(chunk[0]);
-                        }
-                    } else {
-                        let _ = producer.push_slice(data);
-                    }
-                },
-                err_fn,
-                None,
-            )?
-        }
-        SampleFormat::I16 => {
-            device.build_input_stream(
-                &config.clone().into(),
-                move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                    if !is_running.load(Ordering::Relaxed) {
-                        return;
-                    }
-                    // REAL-TIME SAFE: Convert and push
-                    if channels > 1 {
-                        for chunk in data.chunks(channels) {
-                            let sample = chunk[0] as f32 / 32768.0;
-                            let _ = producer.try_push(sample);
-                        }
-                    } else {
-                        for &sample in data {
-                            let _ = producer.try_push(sample as f32 / 32768.0);
-                        }
-                    }
-                },
-                err_fn,
-                None,
-            )?
-        }
-        SampleFormat::I32 => {
-            device.build_input_stream(
-                &config.clone().into(),
-                move |data: &[i32], _: &cpal::InputCallbackInfo| {
-                    if !is_running.load(Ordering::Relaxed) {
-                        return;
-                    }
-                    // REAL-TIME SAFE: Convert and push
-                    if channels > 1 {
-                        for chunk in data.chunks(channels) {
-                            let sample = chunk[0] as f32 / 2147483648.0;
-                            let _ = producer.try_push(sample);
-                        }
-                    } else {
-                        for &sample in data {
-                            let _ = producer.try_push(sample as f32 / 2147483648.0);
-                        }
-                    }
-                },
-                err_fn,
-                None,
-            )?
-        }
+        SampleFormat::I8 => build_typed_stream::<i8>(device, &stream_config, sink, err_fn)?,
+        SampleFormat::I16 => build_typed_stream::<i16>(device, &stream_config, sink, err_fn)?,
+        SampleFormat::I32 => build_typed_stream::<i32>(device, &stream_config, sink, err_fn)?,
+        SampleFormat::I64 => build_typed_stream::<i64>(device, &stream_config, sink, err_fn)?,
+        SampleFormat::U8 => build_typed_stream::<u8>(device, &stream_config, sink, err_fn)?,
+        SampleFormat::U16 => build_typed_stream::<u16>(device, &stream_config, sink, err_fn)?,
+        SampleFormat::U32 => build_typed_stream::<u32>(device, &stream_config, sink, err_fn)?,
+        SampleFormat::U64 => build_typed_stream::<u64>(device, &stream_config, sink, err_fn)?,
+        SampleFormat::F32 => build_typed_stream::<f32>(device, &stream_config, sink, err_fn)?,
+        SampleFormat::F64 => build_typed_stream::<f64>(device, &stream_config, sink, err_fn)?,
         format => {
             return Err(anyhow::anyhow!("Unsupported sample format: {:?}", format));
         }
@@ -211,9 +343,87 @@ fn build_input_stream(
     Ok(stream)
 }
 
+/// Input stream delivering `T` samples
+fn build_typed_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut sink: SampleSink,
+    err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| sink.push(data),
+        err_fn,
+        None,
+    )
+}
+
+/// Callback end of the ring buffer
+struct SampleSink {
+    producer: HeapProd<f32>,
+    is_running: Arc<AtomicBool>,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
+    channels: usize,
+}
+
+impl SampleSink {
+    /// REAL-TIME SAFE: convert and push all channels, interleaved
+    fn push<T: Sample>(&mut self, data: &[T])
+    where
+        f32: FromSample<T>,
+    {
+        if !self.is_running.load(Ordering::Relaxed) {
+            return;
+        }
+        // Whole frames only: a partial one would shift every later
+        // sample onto the wrong channel
+        let room = self.producer.vacant_len() / self.channels * self.channels;
+        let count = data.len().min(room);
+        let pushed = self.producer.push_iter(data[..count].iter().map(|&s| f32::from_sample(s)));
+        self.overflow.record(data.len(), pushed);
+        self.notifier.notify();
+    }
+}
+
+/// Forward cpal stream errors to JS; a vanished device is fatal
+fn report_stream_error(status: &StatusReporter, err: cpal::StreamError) {
+    eprintln!("[Microphone] Stream error: {}", err);
+    match err {
+        cpal::StreamError::DeviceNotAvailable => {
+            status.fail(StatusCode::DeviceLost, "The microphone is no longer available");
+        }
+        other => status.report(StatusCode::StreamError, other.to_string()),
+    }
+}
+
 impl Drop for MicrophoneStream {
     fn drop(&mut self) {
         self.is_running.store(false, Ordering::SeqCst);
         // Stream will be dropped and stopped automatically
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_holds_same_duration_for_any_channel_count() {
+        let mono_frames = ring_buffer(1).capacity().get();
+        for channels in [1u16, 2, 4, 8] {
+            let frames = ring_buffer(channels).capacity().get() / channels as usize;
+            assert_eq!(
+                frames, mono_frames,
+                "{} channels hold {} frames per channel, mono holds {}",
+                channels, frames, mono_frames
+            );
+        }
+        // A device reporting 0 channels still gets a usable buffer
+        assert_eq!(ring_buffer(0).capacity().get(), RING_BUFFER_SAMPLES);
+    }
+}
@@ -0,0 +1,299 @@
+// Neural VAD - Silero-style ONNX model on CPU (cargo feature "neural-vad")
+//
+// Runs a small recurrent VAD model (Silero VAD v5 layout) with ONNX Runtime
+// on one CPU thread. The model is a local file named by the `vadModelPath`
+// capture option: nothing is downloaded and no GPU is used.
+//
+// ONNX Runtime (1.20.x) is not linked or bundled: it is opened when the
+// first model loads, from ORT_DYLIB_PATH if set, else libonnxruntime on
+// the library search path. Without it, loading the model fails.
+//
+// - Input: 16kHz only. Output frames are re-chunked into the model's
+//   512-sample (32ms) windows, each prefixed with the previous 64 samples
+//   as context, like the reference implementation
+// - State: the recurrent state is carried from window to window, one per
+//   detector; the loaded model is shared by a capture's detectors
+// - Decision: speech once the probability reaches SPEECH_PROBABILITY, kept
+//   until it drops below SPEECH_PROBABILITY - HYSTERESIS
+// - Model: NeuralDetector only sees the VadModel trait (one inference
+//   step); SileroModel implements it with ONNX Runtime
+//
+// Inference runs on the DSP thread; its cost shows in getStats()
+// as speechDetectorMs.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use ort::session::builder::GraphOptimizationLevel;
+use ort::session::Session;
+use ort::value::Tensor;
+
+use crate::speech_detector::SpeechDetector;
+
+/// The only rate the model is run at
+pub const MODEL_SAMPLE_RATE: u32 = 16_000;
+
+/// Samples per inference at 16kHz
+const WINDOW_SAMPLES: usize = 512;
+
+/// Samples of the previous window passed along with each window
+const CONTEXT_SAMPLES: usize = 64;
+
+/// Recurrent state shape: [2, batch, 128]
+const STATE_SHAPE: [usize; 3] = [2, 1, 128];
+
+const SPEECH_PROBABILITY: f32 = 0.5;
+const HYSTERESIS: f32 = 0.15;
+
+/// One inference step of a recurrent VAD model
+pub trait VadModel: Send + Sync {
+    /// Speech probability of one context + window; advances `state`
+    fn infer(&self, samples: &[f32], state: &mut [f32]) -> Result<f32>;
+}
+
+/// A loaded VAD model, shared between detectors
+pub struct SileroModel {
+    session: Session,
+}
+
+impl fmt::Debug for SileroModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SileroModel")
+    }
+}
+
+impl SileroModel {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Err(anyhow!("VAD model not found: {}", path.display()));
+        }
+        // ort panics when the runtime library is missing or too old
+        let session = std::panic::catch_unwind(|| {
+            Session::builder()
+                .and_then(|b| b.with_optimization_level(GraphOptimizationLevel::Level3))
+                .and_then(|b| b.with_intra_threads(1))
+                .and_then(|b| b.with_inter_threads(1))
+                .and_then(|b| b.commit_from_file(path))
+        })
+        .map_err(|_| anyhow!("ONNX Runtime 1.20 could not be loaded (install it or set ORT_DYLIB_PATH)"))?
+        .map_err(|e| anyhow!("Failed to load VAD model {}: {}", path.display(), e))?;
+
+        for name in ["input", "state", "sr"] {
+            if !session.inputs.iter().any(|input| input.name == name) {
+                return Err(anyhow!("{} is not a Silero VAD v5 model (no '{}' input)", path.display(), name));
+            }
+        }
+        println!("[NeuralVad] Loaded {}", path.display());
+        Ok(Self { session })
+    }
+}
+
+impl VadModel for SileroModel {
+    fn infer(&self, samples: &[f32], state: &mut [f32]) -> Result<f32> {
+        let input = Tensor::from_array(([1, samples.len()], samples.to_vec()))?;
+        let state_in = Tensor::from_array((STATE_SHAPE, state.to_vec()))?;
+        // Scalar (rank 0) input
+        let sample_rate = Tensor::from_array(([0usize; 0], vec![MODEL_SAMPLE_RATE as i64]))?;
+
+        let outputs = self.session.run(ort::inputs![
+            "input" => input,
+            "state" => state_in,
+            "sr" => sample_rate,
+        ]?)?;
+        let (_, probability) = outputs["output"].try_extract_raw_tensor::<f32>()?;
+        let (_, next_state) = outputs["stateN"].try_extract_raw_tensor::<f32>()?;
+        if next_state.len() != state.len() {
+            return Err(anyhow!("Unexpected VAD state size {}", next_state.len()));
+        }
+        state.copy_from_slice(next_state);
+        Ok(probability.first().copied().unwrap_or(0.0))
+    }
+}
+
+/// Per-capture detector state around a shared model
+pub struct NeuralDetector<M: VadModel = SileroModel> {
+    model: Arc<M>,
+    state: Vec<f32>,
+    /// Context followed by the window being filled
+    buffer: Vec<f32>,
+    speaking: bool,
+    failed: bool,
+}
+
+impl<M: VadModel> NeuralDetector<M> {
+    pub fn new(model: Arc<M>) -> Self {
+        Self {
+            model,
+            state: vec![0.0; STATE_SHAPE.iter().product()],
+            buffer: vec![0.0; CONTEXT_SAMPLES],
+            speaking: false,
+            failed: false,
+        }
+    }
+}
+
+impl<M: VadModel> SpeechDetector for NeuralDetector<M> {
+    /// Frames must be 16kHz; the decision lags by up to one window
+    fn is_speech(&mut self, frame: &[i16], _rms: f32) -> bool {
+        for &sample in frame {
+            self.buffer.push(sample as f32 / 32768.0);
+            if self.buffer.len() < CONTEXT_SAMPLES + WINDOW_SAMPLES || self.failed {
+                continue;
+            }
+
+            match self.model.infer(&self.buffer, &mut self.state) {
+                Ok(probability) => {
+                    self.speaking = if self.speaking {
+                        probability >= SPEECH_PROBABILITY - HYSTERESIS
+                    } else {
+                        probability >= SPEECH_PROBABILITY
+                    };
+                }
+                Err(e) => {
+                    // Fail open: without a decision, everything is treated as speech
+                    eprintln!("[NeuralVad] Inference failed, passing all audio: {}", e);
+                    self.failed = true;
+                    self.speaking = true;
+                }
+            }
+            self.buffer.drain(..WINDOW_SAMPLES);
+        }
+        if self.failed {
+            self.buffer.clear();
+        }
+        self.speaking
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::silence_suppression::calculate_rms;
+    use crate::speech_detector::SpectralDetector;
+    use crate::test_fixtures::labeled_set;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    /// Returns scripted probabilities (None: inference error, then 0.0
+    /// past the end) and records every window it is given
+    struct ScriptedModel {
+        script: Vec<Option<f32>>,
+        windows: Mutex<Vec<Vec<f32>>>,
+    }
+
+    impl ScriptedModel {
+        fn new(script: Vec<Option<f32>>) -> Arc<Self> {
+            Arc::new(Self { script, windows: Mutex::new(Vec::new()) })
+        }
+
+        fn calls(&self) -> usize {
+            self.windows.lock().unwrap().len()
+        }
+    }
+
+    impl VadModel for ScriptedModel {
+        fn infer(&self, samples: &[f32], _state: &mut [f32]) -> Result<f32> {
+            let mut windows = self.windows.lock().unwrap();
+            windows.push(samples.to_vec());
+            match self.script.get(windows.len() - 1) {
+                Some(Some(probability)) => Ok(*probability),
+                Some(None) => Err(anyhow!("scripted failure")),
+                None => Ok(0.0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_frames_rechunked_into_windows_with_context() {
+        let model = ScriptedModel::new(Vec::new());
+        let mut detector = NeuralDetector::new(model.clone());
+        let input: Vec<i16> = (0..320 * 5).map(|i| i as i16).collect();
+
+        let mut calls_after_frame = Vec::new();
+        for frame in input.chunks_exact(320) {
+            detector.is_speech(frame, 0.0);
+            calls_after_frame.push(model.calls());
+        }
+        // A window completes at samples 512, 1024 and 1536
+        assert_eq!(calls_after_frame, vec![0, 1, 1, 2, 3]);
+
+        let windows = model.windows.lock().unwrap();
+        for (k, window) in windows.iter().enumerate() {
+            assert_eq!(window.len(), CONTEXT_SAMPLES + WINDOW_SAMPLES);
+            // Each window is preceded by the last 64 samples of the one before
+            // (zeros before the first)
+            let expected: Vec<f32> = (0..window.len())
+                .map(|i| (k * WINDOW_SAMPLES + i) as i64 - CONTEXT_SAMPLES as i64)
+                .map(|sample| sample.max(0) as f32 / 32768.0)
+                .collect();
+            assert_eq!(window, &expected, "window {}", k);
+        }
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let script = [0.4, 0.6, 0.4, 0.3, 0.49, 0.5].map(Some).to_vec();
+        let model = ScriptedModel::new(script);
+        let mut detector = NeuralDetector::new(model.clone());
+
+        // One window per call
+        let frame = vec![1000i16; WINDOW_SAMPLES];
+        let decisions: Vec<bool> = (0..6).map(|_| detector.is_speech(&frame, 0.0)).collect();
+        assert_eq!(model.calls(), 6);
+        // Onset at 0.5, held down to 0.35
+        assert_eq!(decisions, vec![false, true, true, false, false, true]);
+    }
+
+    #[test]
+    fn test_inference_error_fails_open() {
+        let model = ScriptedModel::new(vec![Some(0.1), None, Some(0.1)]);
+        let mut detector = NeuralDetector::new(model.clone());
+
+        let frame = vec![0i16; WINDOW_SAMPLES];
+        assert!(!detector.is_speech(&frame, 0.0));
+        assert!(detector.is_speech(&frame, 0.0));
+        // The model is not asked again; everything passes as speech
+        for _ in 0..5 {
+            assert!(detector.is_speech(&frame, 0.0));
+        }
+        assert_eq!(model.calls(), 2);
+    }
+
+    #[test]
+    #[ignore = "needs RUSTYN_VAD_MODEL=/path/to/silero_vad.onnx and ONNX Runtime 1.20"]
+    fn test_neural_accuracy_and_cost() {
+        let path = std::env::var("RUSTYN_VAD_MODEL").expect("RUSTYN_VAD_MODEL not set");
+        let model = Arc::new(SileroModel::load(Path::new(&path)).unwrap());
+
+        let mut neural_correct = 0;
+        let mut spectral_correct = 0;
+        let mut frames = 0;
+        let mut neural_time = Duration::ZERO;
+        for clip in labeled_set() {
+            let mut neural = NeuralDetector::new(model.clone());
+            let mut spectral = SpectralDetector::new(MODEL_SAMPLE_RATE);
+            for (frame, &label) in clip.samples.chunks_exact(320).zip(&clip.labels) {
+                let rms = calculate_rms(frame);
+                let started = Instant::now();
+                let decision = neural.is_speech(frame, rms);
+                neural_time += started.elapsed();
+                neural_correct += (decision == label) as usize;
+                spectral_correct += (spectral.is_speech(frame, rms) == label) as usize;
+                frames += 1;
+            }
+        }
+
+        let ms_per_frame = neural_time.as_secs_f64() * 1000.0 / frames as f64;
+        // Synthetic speech is not what the model was trained on, so only
+        // the cost is asserted; well under real time on one core
+        assert!(
+            ms_per_frame < 5.0,
+            "{:.3}ms per 20ms frame (neural detector); neural {:.1}%, spectral {:.1}% correct",
+            ms_per_frame,
+            100.0 * neural_correct as f64 / frames as f64,
+            100.0 * spectral_correct as f64 / frames as f64,
+        );
+    }
+}
@@ -0,0 +1,186 @@
+// Opus Encoding - compressed frames for cloud STT upload
+//
+// LINEAR16 at 16kHz is 256 kbps per stream; Opus voice at 24 kbps is
+// transparent for STT. Each output frame (FRAME_MS, or the configured
+// frameMs) becomes exactly one Opus packet, so packet boundaries, sequence
+// numbers and keepalive timing match the PCM stream one-to-one.
+//
+// Encoding runs on the DSP thread, inside the capture's emit closure. A
+// frame that fails to encode is dropped but keeps its sequence number, so
+// JS sees the gap (and an encodeFailed status event explaining it).
+//
+// Build: audiopus_sys links the system libopus found by pkg-config (or in
+// LIBOPUS_LIB_DIR); without one it builds its bundled copy, which needs cmake.
+
+use anyhow::{anyhow, Result};
+use audiopus::coder::Encoder;
+use audiopus::{Application, Bitrate, Channels, SampleRate, Signal};
+
+use crate::audio_config::OutputFormat;
+
+/// Default target bitrate (bits/second)
+pub const DEFAULT_OPUS_BITRATE: u32 = 24_000;
+
+/// Frame durations Opus can encode in one packet
+const OPUS_FRAME_MS: [u32; 4] = [10, 20, 40, 60];
+
+/// Largest packet we ask libopus for (recommended max is 1275 bytes/frame)
+const MAX_PACKET_BYTES: usize = 4000;
+
+/// One encoded frame plus the metadata needed to decode it in order
+pub struct EncodedPacket {
+    pub data: Vec<u8>,
+    /// Frame counter since start (0-based)
+    pub sequence: u64,
+    /// Samples per channel the packet decodes to
+    pub samples: u32,
+    pub sample_rate: u32,
+}
+
+/// Check that Opus can encode frames of this output format
+pub fn validate_format(format: OutputFormat) -> Result<()> {
+    opus_sample_rate(format.sample_rate)?;
+    if !OPUS_FRAME_MS.contains(&format.frame_ms) {
+        return Err(anyhow!(
+            "Opus needs frameMs of {:?}, got {}", OPUS_FRAME_MS, format.frame_ms
+        ));
+    }
+    Ok(())
+}
+
+fn opus_sample_rate(sample_rate: u32) -> Result<SampleRate> {
+    match sample_rate {
+        8_000 => Ok(SampleRate::Hz8000),
+        12_000 => Ok(SampleRate::Hz12000),
+        16_000 => Ok(SampleRate::Hz16000),
+        24_000 => Ok(SampleRate::Hz24000),
+        48_000 => Ok(SampleRate::Hz48000),
+        other => Err(anyhow!("Opus does not support a {}Hz sampleRate", other)),
+    }
+}
+
+/// Mono Opus encoder producing one packet per output frame
+pub struct OpusFrameEncoder {
+    encoder: Encoder,
+    format: OutputFormat,
+    buffer: Vec<u8>,
+    sequence: u64,
+}
+
+impl OpusFrameEncoder {
+    pub fn new(format: OutputFormat, bitrate: u32) -> Result<Self> {
+        validate_format(format)?;
+        let mut encoder = Encoder::new(opus_sample_rate(format.sample_rate)?, Channels::Mono, Application::Voip)
+            .map_err(|e| anyhow!("Failed to create Opus encoder: {}", e))?;
+        encoder.set_bitrate(Bitrate::BitsPerSecond(bitrate as i32))
+            .map_err(|e| anyhow!("Failed to set Opus bitrate: {}", e))?;
+        encoder.set_signal(Signal::Voice)
+            .map_err(|e| anyhow!("Failed to set Opus signal type: {}", e))?;
+
+        println!("[OpusEncoder] Created: {}Hz, {}ms frames, {} bps",
+            format.sample_rate, format.frame_ms, bitrate);
+
+        Ok(Self {
+            encoder,
+            format,
+            buffer: vec![0u8; MAX_PACKET_BYTES],
+            sequence: 0,
+        })
+    }
+
+    /// Encode one output frame (exactly `frame_samples()` samples)
+    pub fn encode(&mut self, frame: &[i16]) -> Result<EncodedPacket> {
+        // Failed frames use up their number too
+        let sequence = self.sequence;
+        self.sequence += 1;
+        if frame.len() != self.format.frame_samples() {
+            return Err(anyhow!(
+                "Opus frame must be {} samples, got {}", self.format.frame_samples(), frame.len()
+            ));
+        }
+        let len = self.encoder.encode(frame, &mut self.buffer)
+            .map_err(|e| anyhow!("Opus encode failed: {}", e))?;
+
+        let packet = EncodedPacket {
+            data: self.buffer[..len].to_vec(),
+            sequence,
+            samples: frame.len() as u32,
+            sample_rate: self.format.sample_rate,
+        };
+        Ok(packet)
+    }
+
+    /// Encoder lookahead in samples (decoded audio is delayed by this much)
+    pub fn lookahead(&self) -> u32 {
+        self.encoder.lookahead().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use audiopus::coder::Decoder;
+    use audiopus::packet::Packet;
+    use audiopus::MutSignals;
+
+    #[test]
+    fn test_unsupported_formats_rejected() {
+        assert!(validate_format(OutputFormat { sample_rate: 44_100, frame_ms: 20 }).is_err());
+        assert!(validate_format(OutputFormat { sample_rate: 16_000, frame_ms: 30 }).is_err());
+        assert!(validate_format(OutputFormat::default()).is_ok());
+    }
+
+    #[test]
+    fn test_failed_frame_keeps_its_sequence() {
+        let format = OutputFormat::default();
+        let mut encoder = OpusFrameEncoder::new(format, DEFAULT_OPUS_BITRATE).unwrap();
+        let frame = vec![0i16; format.frame_samples()];
+        assert_eq!(encoder.encode(&frame).unwrap().sequence, 0);
+        assert!(encoder.encode(&frame[..10]).is_err());
+        // The gap shows where the dropped frame was
+        assert_eq!(encoder.encode(&frame).unwrap().sequence, 2);
+    }
+
+    #[test]
+    fn test_round_trip_quality() {
+        let format = OutputFormat::default();
+        let speech = test_fixtures::to_i16(&test_fixtures::speech(4.0, 21));
+        let mut encoder = OpusFrameEncoder::new(format, DEFAULT_OPUS_BITRATE).unwrap();
+        let mut decoder = Decoder::new(SampleRate::Hz16000, Channels::Mono).unwrap();
+
+        let mut decoded = Vec::with_capacity(speech.len());
+        let mut bytes = 0usize;
+        for (i, frame) in speech.chunks_exact(format.frame_samples()).enumerate() {
+            let packet = encoder.encode(frame).unwrap();
+            assert_eq!(packet.sequence, i as u64);
+            assert_eq!(packet.samples as usize, format.frame_samples());
+            bytes += packet.data.len();
+
+            let mut out = vec![0i16; format.frame_samples()];
+            let n = decoder.decode(
+                Some(Packet::try_from(&packet.data[..]).unwrap()),
+                MutSignals::try_from(&mut out[..]).unwrap(),
+                false,
+            ).unwrap();
+            assert_eq!(n, format.frame_samples());
+            decoded.extend_from_slice(&out);
+        }
+
+        // Bitrate: an order of magnitude below LINEAR16
+        let kbps = bytes as f64 * 8.0 / 4.0 / 1000.0;
+        assert!(kbps < 40.0, "{:.1} kbps", kbps);
+
+        // Align by the encoder lookahead, then compare waveforms
+        let delay = encoder.lookahead() as usize;
+        let original = &speech[..speech.len() - delay];
+        let restored = &decoded[delay..];
+        let dot: f64 = original.iter().zip(restored).map(|(&a, &b)| a as f64 * b as f64).sum();
+        let energy = |s: &[i16]| s.iter().map(|&x| (x as f64).powi(2)).sum::<f64>();
+        let (e_orig, e_rest) = (energy(original), energy(restored));
+        let correlation = dot / (e_orig * e_rest).sqrt();
+        let level_db = 10.0 * (e_rest / e_orig).log10();
+        assert!(correlation > 0.8, "correlation {:.3}", correlation);
+        assert!(level_db.abs() < 3.0, "level {:.1} dB", level_db);
+    }
+}
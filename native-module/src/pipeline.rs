@@ -0,0 +1,1028 @@
+// Capture Pipeline - shared DSP thread for every AudioSource
+//
+// Architecture:
+// 1. Source callback: pushes raw f32 to a lock-free ring buffer, then
+//    signals the DataNotifier
+// 2. DSP thread (here): parks until signalled, then drain -> Downmixer
+//    -> StreamingResampler -> [recording tap] -> [echo reference / EchoCanceller]
+//    -> [NoiseSuppressor] -> [AGC] -> [history] -> SilenceSuppressor
+//    -> [VAD events] -> emit
+//
+// Microphone and system audio captures run the exact same stages, only the
+// configuration differs. New stages are added once, in FrameProcessor.
+//
+// A running pipeline can be handed a new source through a SourceSwitch
+// (device changes, see device_watch.rs). The DSP thread swaps the ring
+// buffer between drains; every stage keeps its state and the frame
+// sequence continues.
+
+use anyhow::Result;
+use ringbuf::traits::{Consumer, Observer};
+use ringbuf::HeapCons;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio_config::{OutputFormat, DSP_WAIT_TIMEOUT_MS};
+use crate::agc::{AgcConfig, AutomaticGainControl};
+use crate::capture_clock::CaptureClock;
+use crate::capture_status::{CaptureState, StatusCode, StatusReporter};
+use crate::downmix::{ChannelMode, Downmixer};
+use crate::audio_source::AudioSource;
+use crate::echo_cancel::{EchoCancelConfig, EchoControl, EchoReference, EchoStage};
+use crate::history::AudioHistory;
+use crate::noise_suppression::{NoiseSuppressionLevel, NoiseSuppressor};
+use crate::recorder::RecordingTap;
+use crate::silence_suppression::{
+    calculate_rms, generate_silence_frame, FrameAction, FrameKind, SilenceSuppressionConfig, SilenceSuppressor,
+};
+use crate::stats::{OverflowCounter, PipelineStats};
+use crate::speech_detector::SpeechDetectorKind;
+use crate::vad::{VadConfig, VadEvent, VadIndicator};
+use crate::streaming_resampler::{ResamplerQuality, StreamingResampler};
+use crate::wakeup::DataNotifier;
+
+/// Receives speech start / end events with their capture time (DSP thread)
+pub type VadSink = Box<dyn FnMut(VadEvent, Instant) + Send>;
+
+/// Max samples drained from the ring buffer per loop iteration (per channel)
+const MAX_BATCH_FRAMES: usize = 480;
+
+/// Smoothing of the published DSP latency (per drain)
+const LATENCY_SMOOTHING: f32 = 0.05;
+
+/// No input for this long reports streamStalled
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Minimum time between bufferOverflow reports
+const OVERFLOW_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Per-capture pipeline configuration
+pub struct PipelineConfig {
+    /// Log prefix, e.g. "MicrophoneCapture"
+    pub name: &'static str,
+    pub suppression: SilenceSuppressionConfig,
+    /// Output sample rate and frame duration
+    pub format: OutputFormat,
+    pub resampler_quality: ResamplerQuality,
+    /// How multi-channel input becomes mono
+    pub channel_mode: ChannelMode,
+    /// Publish resampled frames as far-end reference (system audio)
+    pub echo_reference: Option<Arc<EchoReference>>,
+    /// Cancel the echo of a far-end reference (microphone)
+    pub echo_control: Option<Arc<EchoControl>>,
+    pub noise_suppression: NoiseSuppressionLevel,
+    pub agc: Option<AgcConfig>,
+    /// Receives every resampled frame while a recording is attached
+    pub recording_tap: Option<Arc<RecordingTap>>,
+    /// Rolling window of unsuppressed output for getHistory()
+    pub history: Option<Arc<AudioHistory>>,
+    /// Published for getStats()
+    pub stats: Arc<PipelineStats>,
+    pub vad: VadConfig,
+    /// Runs the VAD when set
+    pub vad_events: Option<VadSink>,
+    /// Receives overflow and stall reports from the DSP thread
+    pub status: StatusReporter,
+    /// Input gap reported as a stall (None: sources that end, like files)
+    pub stall_timeout: Option<Duration>,
+}
+
+impl PipelineConfig {
+    /// Microphone config (standard threshold)
+    pub fn for_microphone() -> Self {
+        Self {
+            name: "MicrophoneCapture",
+            suppression: SilenceSuppressionConfig::for_microphone(),
+            format: OutputFormat::default(),
+            resampler_quality: ResamplerQuality::Balanced,
+            channel_mode: ChannelMode::default(),
+            echo_reference: None,
+            echo_control: None,
+            noise_suppression: NoiseSuppressionLevel::Off,
+            agc: None,
+            recording_tap: None,
+            history: None,
+            stats: Arc::new(PipelineStats::default()),
+            vad: VadConfig::default(),
+            vad_events: None,
+            status: StatusReporter::default(),
+            stall_timeout: Some(STALL_TIMEOUT),
+        }
+    }
+
+    /// System audio config (lower threshold for quieter system audio)
+    pub fn for_system_audio() -> Self {
+        Self {
+            name: "SystemAudioCapture",
+            suppression: SilenceSuppressionConfig::for_system_audio(),
+            format: OutputFormat::default(),
+            resampler_quality: ResamplerQuality::Balanced,
+            channel_mode: ChannelMode::default(),
+            echo_reference: None,
+            echo_control: None,
+            noise_suppression: NoiseSuppressionLevel::Off,
+            agc: None,
+            recording_tap: None,
+            history: None,
+            stats: Arc::new(PipelineStats::default()),
+            vad: VadConfig::default(),
+            vad_events: None,
+            status: StatusReporter::default(),
+            stall_timeout: Some(STALL_TIMEOUT),
+        }
+    }
+}
+
+/// One frame leaving the pipeline
+#[derive(Debug, Clone)]
+pub struct OutputFrame {
+    pub samples: Vec<i16>,
+    /// Capture time of the first sample
+    pub timestamp: Instant,
+    /// Emitted frames before this one (gaps mean frames were lost)
+    pub sequence: u64,
+    pub kind: FrameKind,
+    /// RMS level (i16 scale) of the processed audio, before gating
+    pub rms: f32,
+}
+
+/// DSP stages shared by all captures, independent of threading
+///
+/// Feed raw interleaved samples with `push`, receive output frames
+/// through the `emit` closure.
+pub struct FrameProcessor {
+    downmix: Downmixer,
+    frame_samples: usize,
+    input_sample_rate: u32,
+    output_sample_rate: f64,
+    resampler_quality: ResamplerQuality,
+    resampler: StreamingResampler,
+    /// Maps output samples to capture timestamps
+    clock: CaptureClock,
+    /// Output sample the current source's clock starts at
+    clock_origin: u64,
+    /// Output samples produced so far
+    output_samples: u64,
+    /// Frames emitted so far
+    sequence: u64,
+    echo_reference: Option<Arc<EchoReference>>,
+    echo: Option<EchoStage>,
+    noise: Option<NoiseSuppressor>,
+    agc: Option<AutomaticGainControl>,
+    recording_tap: Option<Arc<RecordingTap>>,
+    history: Option<Arc<AudioHistory>>,
+    vad: Option<(VadIndicator, VadSink)>,
+    /// The VAD follows the suppressor's detector instead of its RMS thresholds
+    vad_follows_detector: bool,
+    suppressor: SilenceSuppressor,
+    stats: Arc<PipelineStats>,
+    mono_batch: Vec<f32>,
+    frame_buffer: Vec<i16>,
+}
+
+impl FrameProcessor {
+    pub fn new(input_sample_rate: u32, channels: u16, config: PipelineConfig) -> Self {
+        let suppression = config.suppression;
+        let format = config.format;
+        let vad = config.vad_events
+            .map(|sink| (VadIndicator::with_config(config.vad, format.sample_rate), sink));
+        let vad_follows_detector = !matches!(suppression.detector, SpeechDetectorKind::Rms);
+        Self {
+            downmix: Downmixer::new(config.channel_mode, channels, input_sample_rate),
+            frame_samples: format.frame_samples(),
+            input_sample_rate,
+            output_sample_rate: format.sample_rate as f64,
+            resampler_quality: config.resampler_quality,
+            resampler: StreamingResampler::with_quality(
+                input_sample_rate as f64,
+                format.sample_rate as f64,
+                config.resampler_quality,
+            ),
+            clock: CaptureClock::new(input_sample_rate),
+            clock_origin: 0,
+            output_samples: 0,
+            sequence: 0,
+            echo_reference: config.echo_reference,
+            echo: config.echo_control
+                .map(|control| EchoStage::new(control, EchoCancelConfig::for_format(format))),
+            noise: match config.noise_suppression {
+                NoiseSuppressionLevel::Off => None,
+                level => Some(NoiseSuppressor::new(level, format)),
+            },
+            agc: config.agc.map(|agc| AutomaticGainControl::new(agc, format)),
+            recording_tap: config.recording_tap,
+            history: config.history,
+            vad,
+            vad_follows_detector,
+            suppressor: SilenceSuppressor::with_sample_rate(suppression, format.sample_rate),
+            stats: config.stats,
+            mono_batch: Vec::with_capacity(MAX_BATCH_FRAMES),
+            frame_buffer: Vec::with_capacity(format.frame_samples() * 4),
+        }
+    }
+
+    /// Process interleaved samples (length must be a multiple of channels)
+    pub fn push(&mut self, interleaved: &[f32], emit: &mut impl FnMut(OutputFrame)) {
+        self.push_at(interleaved, Instant::now(), emit);
+    }
+
+    /// `push` for samples that had all been captured by `now`
+    pub fn push_at(&mut self, interleaved: &[f32], now: Instant, emit: &mut impl FnMut(OutputFrame)) {
+        self.clock.observe(interleaved.len() / self.downmix.channels(), now);
+
+        // 1. Downmix to mono (average, one channel or the loudest)
+        self.mono_batch.clear();
+        self.downmix.process(interleaved, &mut self.mono_batch);
+        if self.mono_batch.is_empty() {
+            return;
+        }
+
+        // 2. Resample
+        let resampled = self.resampler.resample(&self.mono_batch);
+        self.frame_buffer.extend(resampled);
+
+        while self.frame_buffer.len() >= self.frame_samples {
+            let mut frame: Vec<i16> = self.frame_buffer.drain(0..self.frame_samples).collect();
+            let frame_start = self.output_samples;
+            let timestamp = self.sample_timestamp(frame_start).unwrap_or(now);
+            self.output_samples += self.frame_samples as u64;
+
+            // 3. Recording (continuous audio, independent of the gate)
+            if let Some(tap) = &self.recording_tap {
+                tap.push(&frame, timestamp);
+            }
+
+            // 4. Far-end reference for a microphone's echo canceller
+            if let Some(reference) = &self.echo_reference {
+                reference.push(&frame, timestamp);
+            }
+
+            // 5. Echo cancellation (before gating, so echo doesn't open it)
+            if let Some(echo) = &mut self.echo {
+                echo.process(&mut frame, timestamp);
+            }
+
+            // 6. Noise suppression (before gating, so noise doesn't open it);
+            // from here on frames lag their input by its latency
+            if let Some(noise) = &mut self.noise {
+                noise.process(&mut frame);
+            }
+            let processed_timestamp = self.processed_timestamp(frame_start).unwrap_or(timestamp);
+
+            // 7. Gain control (before gating, so gate and STT see normalized levels)
+            if let Some(agc) = &mut self.agc {
+                agc.process(&mut frame);
+                self.stats.set_agc_gain_db(agc.gain_db());
+            }
+
+            // 8. History (everything, including what the gate drops)
+            if let Some(history) = &self.history {
+                history.push(&frame);
+            }
+
+            // 9. Silence Suppression
+            let action = self.suppressor.process(&frame);
+            self.stats.set_speech_detector_ms(self.suppressor.detector_ms());
+            self.stats.set_silence_gate(self.suppressor.noise_floor_rms(), self.suppressor.threshold_rms());
+
+            // 10. Speech start / end events (UI only, never gates audio);
+            // a spectral / neural detector's decision is reused, not recomputed
+            if let Some((vad, sink)) = &mut self.vad {
+                if self.vad_follows_detector {
+                    vad.update_with_decision(&frame, self.suppressor.last_speech());
+                } else {
+                    vad.update(&frame);
+                }
+                if let Some(event) = vad.take_event() {
+                    let at = capture_time(
+                        &self.clock,
+                        &self.resampler,
+                        self.output_sample_rate,
+                        self.clock_origin,
+                        event.sample,
+                        self.noise.as_ref().map_or(0, |noise| noise.latency_samples()),
+                    ).unwrap_or(processed_timestamp);
+                    sink(event, at);
+                }
+            }
+            let (samples, kind) = match action {
+                FrameAction::Send(audio) => (audio, FrameKind::Speech),
+                FrameAction::SendWithPreroll { preroll, frame: audio } => {
+                    // Pre-roll frames directly precede this one on the sample clock
+                    let count = preroll.len() as u64;
+                    for (i, samples) in preroll.into_iter().enumerate() {
+                        let start = frame_start.saturating_sub((count - i as u64) * self.frame_samples as u64);
+                        emit(OutputFrame {
+                            rms: calculate_rms(&samples),
+                            samples,
+                            timestamp: self.processed_timestamp(start).unwrap_or(processed_timestamp),
+                            sequence: self.sequence,
+                            kind: FrameKind::Preroll,
+                        });
+                        self.sequence += 1;
+                    }
+                    (audio, FrameKind::Speech)
+                }
+                FrameAction::Hangover(audio) => (audio, FrameKind::Hangover),
+                FrameAction::SendSilence => (generate_silence_frame(self.frame_samples), FrameKind::Keepalive),
+                FrameAction::Suppress => {
+                    // Do nothing (bandwidth saving)
+                    continue;
+                }
+            };
+            emit(OutputFrame {
+                samples,
+                timestamp: processed_timestamp,
+                sequence: self.sequence,
+                kind,
+                rms: self.suppressor.last_rms(),
+            });
+            self.sequence += 1;
+        }
+
+        let (_, suppressed) = self.suppressor.stats();
+        self.stats.set_frame_counts(self.sequence, self.suppressor.keepalives(), suppressed);
+    }
+
+    /// Statistics this processor publishes to
+    pub fn stats(&self) -> &Arc<PipelineStats> {
+        &self.stats
+    }
+
+    pub fn channels(&self) -> usize {
+        self.downmix.channels()
+    }
+
+    /// Continue with samples from a new source (e.g. another device)
+    ///
+    /// Every stage keeps its state and the sequence carries on, except the
+    /// echo canceller, which relearns the new device's echo path. The
+    /// resampler is only rebuilt if the input rate changed; timestamps
+    /// follow the new source's clock from the next output sample on.
+    pub fn switch_input(&mut self, input_sample_rate: u32, channels: u16) {
+        self.downmix.set_input(channels, input_sample_rate);
+        if let Some(echo) = &mut self.echo {
+            echo.reset();
+        }
+        if input_sample_rate != self.input_sample_rate {
+            self.resampler = StreamingResampler::with_quality(
+                input_sample_rate as f64,
+                self.output_sample_rate,
+                self.resampler_quality,
+            );
+            self.input_sample_rate = input_sample_rate;
+        }
+        self.clock = CaptureClock::new(input_sample_rate);
+        self.clock_origin = self.output_samples + self.frame_buffer.len() as u64;
+        self.stats.set_input_sample_rate(input_sample_rate);
+    }
+
+    /// Capture time of an output sample
+    fn sample_timestamp(&self, sample: u64) -> Option<Instant> {
+        capture_time(&self.clock, &self.resampler, self.output_sample_rate, self.clock_origin, sample, 0)
+    }
+
+    /// Capture time of an output sample taken after noise suppression
+    fn processed_timestamp(&self, sample: u64) -> Option<Instant> {
+        let latency = self.noise.as_ref().map_or(0, |noise| noise.latency_samples());
+        capture_time(&self.clock, &self.resampler, self.output_sample_rate, self.clock_origin, sample, latency)
+    }
+}
+
+/// Capture time of output sample `sample` (resampler delay and `latency`
+/// samples of later stages removed), on a clock that started at output
+/// sample `origin`
+fn capture_time(
+    clock: &CaptureClock,
+    resampler: &StreamingResampler,
+    output_sample_rate: f64,
+    origin: u64,
+    sample: u64,
+    latency: usize,
+) -> Option<Instant> {
+    let delay = resampler.latency_samples() + latency as f64;
+    clock.timestamp((sample as f64 - origin as f64 - delay) / output_sample_rate)
+}
+
+/// A started source's ring buffer and signals, ready for a DSP thread
+pub struct SourceHandoff {
+    consumer: HeapCons<f32>,
+    sample_rate: u32,
+    channels: u16,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
+}
+
+impl SourceHandoff {
+    /// Start `source` and take its consumer
+    pub fn start(source: &mut dyn AudioSource) -> Result<Self> {
+        source.start()?;
+        let consumer = source.take_consumer()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get consumer"))?;
+        Ok(Self {
+            consumer,
+            sample_rate: source.sample_rate(),
+            channels: source.channels(),
+            notifier: source.data_notifier(),
+            overflow: source.overflow_counter(),
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+/// Source side of a running DSP thread, shared with its SourceSwitch handles
+struct InputSlot {
+    /// Notifier the DSP thread currently parks on
+    notifier: Mutex<DataNotifier>,
+    switch_pending: AtomicBool,
+    pending: Mutex<Option<SourceHandoff>>,
+}
+
+impl InputSlot {
+    fn wake(&self) {
+        self.notifier.lock().unwrap_or_else(|e| e.into_inner()).notify();
+    }
+
+    /// Next source, if a switch was requested (one atomic load otherwise)
+    fn take_pending(&self) -> Option<SourceHandoff> {
+        if !self.switch_pending.swap(false, Ordering::AcqRel) {
+            return None;
+        }
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+/// Replaces the source of a running pipeline
+#[derive(Clone)]
+pub struct SourceSwitch {
+    input: Arc<InputSlot>,
+}
+
+impl SourceSwitch {
+    /// Hand a started source to the DSP thread. Samples still queued in
+    /// the previous source are dropped, so it may be stopped right away.
+    pub fn switch(&self, handoff: SourceHandoff) {
+        *self.input.pending.lock().unwrap_or_else(|e| e.into_inner()) = Some(handoff);
+        self.input.switch_pending.store(true, Ordering::Release);
+        self.input.wake();
+    }
+}
+
+/// Owns the DSP thread of one capture
+pub struct CapturePipeline {
+    stop_signal: Arc<AtomicBool>,
+    input: Arc<InputSlot>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl CapturePipeline {
+    /// Start the source and spawn the DSP thread
+    ///
+    /// `emit` is called on the DSP thread for every frame that should reach STT.
+    pub fn start<F>(source: &mut dyn AudioSource, config: PipelineConfig, emit: F) -> Result<Self>
+    where
+        F: FnMut(OutputFrame) + Send + 'static,
+    {
+        let handoff = SourceHandoff::start(source)?;
+        Ok(Self::spawn(handoff, config, emit))
+    }
+
+    /// Spawn the DSP thread on an already started source
+    pub fn spawn<F>(source: SourceHandoff, config: PipelineConfig, mut emit: F) -> Self
+    where
+        F: FnMut(OutputFrame) + Send + 'static,
+    {
+        config.stats.start_session(source.sample_rate);
+        let mut monitor = SourceMonitor::new(
+            config.stats.clone(),
+            source.overflow.clone(),
+            config.status.clone(),
+            config.stall_timeout,
+        );
+        let name = config.name;
+        let mut processor = FrameProcessor::new(source.sample_rate, source.channels, config);
+
+        let stop_signal = Arc::new(AtomicBool::new(false));
+        let stop_clone = stop_signal.clone();
+        let input = Arc::new(InputSlot {
+            notifier: Mutex::new(source.notifier.clone()),
+            switch_pending: AtomicBool::new(false),
+            pending: Mutex::new(None),
+        });
+        let input_clone = input.clone();
+
+        let thread = thread::spawn(move || {
+            println!("[{}] DSP thread started (suppression active)", name);
+            run_dsp_loop(source, &mut processor, &mut monitor, &input_clone, &stop_clone, &mut emit);
+            println!("[{}] DSP thread stopped.", name);
+        });
+
+        Self {
+            stop_signal,
+            input,
+            thread: Some(thread),
+        }
+    }
+
+    /// Handle for replacing the source while the pipeline runs
+    pub fn source_switch(&self) -> SourceSwitch {
+        SourceSwitch { input: self.input.clone() }
+    }
+
+    /// Stop and join the DSP thread
+    pub fn stop(&mut self) {
+        self.stop_signal.store(true, Ordering::SeqCst);
+        // Wake the DSP thread so it sees the stop request right away
+        self.input.wake();
+        if let Some(handle) = self.thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for CapturePipeline {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Source health seen from the DSP thread: ring buffer, overflows, stalls
+struct SourceMonitor {
+    stats: Arc<PipelineStats>,
+    overflow: OverflowCounter,
+    status: StatusReporter,
+    stall_timeout: Option<Duration>,
+    /// The source may have counted drops before this start()
+    dropped_before: u64,
+    /// Drops counted by sources switched away from
+    dropped_earlier: u64,
+    reported_dropped: u64,
+    last_overflow_report: Option<Instant>,
+    /// The source's abandoned overflow counter was reported as a failure
+    abandon_reported: bool,
+    last_input: Instant,
+}
+
+impl SourceMonitor {
+    fn new(
+        stats: Arc<PipelineStats>,
+        overflow: OverflowCounter,
+        status: StatusReporter,
+        stall_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            dropped_before: overflow.dropped(),
+            dropped_earlier: 0,
+            stats,
+            overflow,
+            status,
+            stall_timeout,
+            reported_dropped: 0,
+            last_overflow_report: None,
+            abandon_reported: false,
+            last_input: Instant::now(),
+        }
+    }
+
+    /// Before draining: publish the ring buffer state, report new overflows
+    fn before_drain(&mut self, consumer: &HeapCons<f32>, now: Instant) {
+        let dropped = self.dropped();
+        self.stats.set_ring_buffer(consumer.occupied_len(), consumer.capacity().get(), dropped);
+
+        let due = self.last_overflow_report.is_none_or(|t| now - t >= OVERFLOW_REPORT_INTERVAL);
+        if dropped > self.reported_dropped && due {
+            self.status.report(
+                StatusCode::BufferOverflow,
+                format!("{} input samples dropped (ring buffer full)", dropped - self.reported_dropped),
+            );
+            self.reported_dropped = dropped;
+            self.last_overflow_report = Some(now);
+        }
+
+        if self.overflow.abandoned() && !self.abandon_reported {
+            self.abandon_reported = true;
+            self.status.fail(StatusCode::BufferOverflow, "The source stopped after continuous ring buffer overflows");
+        }
+    }
+
+    fn dropped(&self) -> u64 {
+        self.dropped_earlier + self.overflow.dropped() - self.dropped_before
+    }
+
+    /// The DSP thread moved on to a new source
+    fn switch_source(&mut self, overflow: OverflowCounter) {
+        self.dropped_earlier = self.dropped();
+        self.dropped_before = overflow.dropped();
+        self.overflow = overflow;
+        self.abandon_reported = false;
+        self.last_input = Instant::now();
+    }
+
+    /// After a drain that produced input
+    fn on_input(&mut self, started: Instant) {
+        self.last_input = Instant::now();
+        self.status.transition(CaptureState::Stalled, CaptureState::Running, "Audio resumed");
+
+        let elapsed_ms = (self.last_input - started).as_secs_f32() * 1000.0;
+        let latency_ms = self.stats.dsp_latency_ms();
+        self.stats.set_dsp_latency_ms(latency_ms + LATENCY_SMOOTHING * (elapsed_ms - latency_ms));
+    }
+
+    /// After a drain that found the ring buffer empty
+    fn on_idle(&mut self, now: Instant) {
+        let Some(timeout) = self.stall_timeout else { return };
+        let silent = now.saturating_duration_since(self.last_input);
+        if silent >= timeout && self.status.transition(CaptureState::Running, CaptureState::Stalled, "") {
+            self.status.report(
+                StatusCode::StreamStalled,
+                format!("No audio from the source for {:.1}s", silent.as_secs_f64()),
+            );
+        }
+    }
+}
+
+fn run_dsp_loop(
+    source: SourceHandoff,
+    processor: &mut FrameProcessor,
+    monitor: &mut SourceMonitor,
+    input: &InputSlot,
+    stop_signal: &AtomicBool,
+    emit: &mut impl FnMut(OutputFrame),
+) {
+    let SourceHandoff { mut consumer, mut notifier, .. } = source;
+    notifier.register_current_thread();
+    let mut channels = processor.channels();
+    let mut max_batch = MAX_BATCH_FRAMES * channels;
+    let mut raw_batch: Vec<f32> = Vec::with_capacity(max_batch + channels);
+    let wait_timeout = Duration::from_millis(DSP_WAIT_TIMEOUT_MS);
+
+    loop {
+        if stop_signal.load(Ordering::Relaxed) {
+            break;
+        }
+
+        // 0. Move to a new source (partial channel frames of the old one are dropped)
+        if let Some(next) = input.take_pending() {
+            next.notifier.register_current_thread();
+            *input.notifier.lock().unwrap_or_else(|e| e.into_inner()) = next.notifier.clone();
+            monitor.switch_source(next.overflow);
+            processor.switch_input(next.sample_rate, next.channels);
+            consumer = next.consumer;
+            notifier = next.notifier;
+            channels = processor.channels();
+            max_batch = MAX_BATCH_FRAMES * channels;
+            raw_batch.clear();
+            raw_batch.reserve(max_batch + channels);
+        }
+
+        // 1. Drain ring buffer (lock-free), keeping partial channel frames for later
+        let started = Instant::now();
+        monitor.before_drain(&consumer, started);
+        while raw_batch.len() < max_batch {
+            match consumer.try_pop() {
+                Some(sample) => raw_batch.push(sample),
+                None => break,
+            }
+        }
+        let whole = raw_batch.len() - raw_batch.len() % channels;
+        let drained = whole > 0;
+
+        // 2. Resample + suppress + emit
+        if drained {
+            processor.push(&raw_batch[..whole], emit);
+            raw_batch.drain(..whole);
+            monitor.on_input(started);
+        }
+
+        // 3. Park until the producer pushes again (no polling)
+        if !drained {
+            monitor.on_idle(started);
+            notifier.wait_timeout(wait_timeout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_config::FRAME_SAMPLES;
+
+    #[test]
+    fn test_stereo_48k_to_16k_frames() {
+        let mut processor = FrameProcessor::new(48000, 2, PipelineConfig::for_microphone());
+
+        // 1 second of loud interleaved stereo, fed in 10ms callbacks
+        let tone: Vec<f32> = (0..48000)
+            .flat_map(|i| {
+                let s = 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin();
+                [s, s]
+            })
+            .collect();
+
+        let mut frames = Vec::new();
+        for chunk in tone.chunks(960) {
+            processor.push(chunk, &mut |frame| frames.push(frame.samples));
+        }
+
+        // Speech is never suppressed: every 20ms frame arrives
+        assert!(frames.len() >= 49 && frames.len() <= 50);
+        assert!(frames.iter().all(|f| f.len() == FRAME_SAMPLES));
+    }
+
+    #[test]
+    fn test_configured_output_formats() {
+        // 8kHz telephony in 10ms frames, 24kHz in 40ms frames
+        for (sample_rate, frame_ms, frame_len) in [(8000, 10, 80), (24000, 40, 960)] {
+            let config = PipelineConfig {
+                format: OutputFormat { sample_rate, frame_ms },
+                noise_suppression: NoiseSuppressionLevel::Moderate,
+                agc: Some(AgcConfig::default()),
+                ..PipelineConfig::for_microphone()
+            };
+            let mut processor = FrameProcessor::new(48000, 1, config);
+
+            let tone: Vec<f32> = (0..48000)
+                .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin())
+                .collect();
+            let mut frames = Vec::new();
+            for chunk in tone.chunks(480) {
+                processor.push(chunk, &mut |frame| frames.push(frame.samples));
+            }
+
+            let expected = 1000 / frame_ms as usize;
+            assert!(frames.len() + 1 >= expected && frames.len() <= expected, "{} frames", frames.len());
+            assert!(frames.iter().all(|f| f.len() == frame_len));
+        }
+    }
+
+    #[test]
+    fn test_switch_input_resets_echo_canceller() {
+        let control = Arc::new(EchoControl::default());
+        control.set_enabled(true);
+        control.set_reference(Some(Arc::new(EchoReference::new())));
+        let config = PipelineConfig {
+            echo_control: Some(control),
+            ..PipelineConfig::for_microphone()
+        };
+        let mut processor = FrameProcessor::new(16_000, 1, config);
+
+        let tone: Vec<f32> = (0..3200)
+            .map(|i| 0.3 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16_000.0).sin())
+            .collect();
+        processor.push(&tone, &mut |_| {});
+        assert!(processor.echo.as_ref().unwrap().is_adapting());
+
+        // The laptop mic's echo path says nothing about the new device's
+        processor.switch_input(48_000, 1);
+        assert!(!processor.echo.as_ref().unwrap().is_adapting());
+        assert_eq!(processor.input_sample_rate, 48_000);
+    }
+
+    #[test]
+    fn test_history_keeps_suppressed_audio() {
+        let history = Arc::new(AudioHistory::new(16_000, Duration::from_secs(5)));
+        let config = PipelineConfig {
+            history: Some(history.clone()),
+            ..PipelineConfig::for_microphone()
+        };
+        let mut processor = FrameProcessor::new(16_000, 1, config);
+
+        // 1s of background hum far below the speech threshold
+        let quiet: Vec<f32> = (0..16_000)
+            .map(|i| 0.002 * (2.0 * std::f32::consts::PI * 100.0 * i as f32 / 16_000.0).sin())
+            .collect();
+        let mut emitted = 0;
+        for chunk in quiet.chunks(160) {
+            processor.push(chunk, &mut |_| emitted += 1);
+        }
+
+        // The gate dropped most frames, history holds every one
+        assert!(emitted < 25, "{} frames emitted", emitted);
+        let kept = history.range(Duration::from_secs(5), Duration::ZERO);
+        assert_eq!(kept.len(), 50 * FRAME_SAMPLES);
+        assert!(kept[FRAME_SAMPLES..].iter().any(|&s| s.abs() > 30));
+    }
+
+    #[test]
+    fn test_frame_metadata() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let config = PipelineConfig {
+            vad_events: Some(Box::new(move |event, at| tx.send((event, at)).unwrap())),
+            ..PipelineConfig::for_microphone()
+        };
+        let mut processor = FrameProcessor::new(16_000, 1, config);
+
+        // 0.5s of tone, then 1s of silence, fed in 10ms callbacks
+        let audio: Vec<f32> = (0..24_000)
+            .map(|i| match i {
+                0..8000 => 0.3 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16_000.0).sin(),
+                _ => 0.0,
+            })
+            .collect();
+        let start = Instant::now();
+        let mut frames = Vec::new();
+        for (i, chunk) in audio.chunks(160).enumerate() {
+            let now = start + Duration::from_millis(10 * (i as u64 + 1));
+            processor.push_at(chunk, now, &mut |frame| frames.push(frame));
+        }
+
+        // Tone, then the 200ms hangover, then a keepalive every 100ms
+        let kinds: Vec<FrameKind> = frames.iter().map(|f| f.kind).collect();
+        let speech = kinds.iter().take_while(|&&k| k == FrameKind::Speech).count();
+        let hangover = kinds[speech..].iter().take_while(|&&k| k == FrameKind::Hangover).count();
+        assert!((25..=26).contains(&speech), "{} speech frames", speech);
+        assert_eq!(hangover, 10);
+        assert!(kinds[speech + hangover..].iter().all(|&k| k == FrameKind::Keepalive));
+        assert!(frames.len() - speech - hangover >= 7);
+        assert!(frames[0].rms > 5000.0 && frames[30].rms < 1.0);
+
+        // Sequence counts emitted frames; timestamps follow capture time
+        assert!(frames.iter().enumerate().all(|(i, f)| f.sequence == i as u64));
+        let offset_ms = |f: &OutputFrame| (f.timestamp - start).as_secs_f64() * 1000.0;
+        assert!((offset_ms(&frames[20]) - 400.0).abs() < 2.0, "{:.2}ms", offset_ms(&frames[20]));
+        let keepalive = speech + hangover;
+        assert!(offset_ms(&frames[keepalive + 1]) - offset_ms(&frames[keepalive]) > 90.0);
+
+        // getStats() counters match what was emitted
+        let stats = processor.stats().snapshot();
+        assert_eq!(stats.frames_emitted as usize, frames.len());
+        assert_eq!(stats.keepalive_frames as usize, frames.len() - speech - hangover);
+        assert!(stats.suppressed_frames > 30.0);
+
+        // VAD events are stamped where speech started and went quiet
+        let events: Vec<_> = rx.try_iter().collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].0.kind, crate::vad::VadEventKind::SpeechEnd);
+        let event_ms = |at: Instant| (at - start).as_secs_f64() * 1000.0;
+        assert!(event_ms(events[0].1).abs() < 2.0, "{:.2}ms", event_ms(events[0].1));
+        assert!((event_ms(events[1].1) - 500.0).abs() < 25.0, "{:.2}ms", event_ms(events[1].1));
+    }
+
+    #[test]
+    fn test_timestamps_exclude_noise_suppression_latency() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let config = PipelineConfig {
+            noise_suppression: NoiseSuppressionLevel::Moderate,
+            vad_events: Some(Box::new(move |event, at| tx.send((event, at)).unwrap())),
+            ..PipelineConfig::for_microphone()
+        };
+        let mut processor = FrameProcessor::new(16_000, 1, config);
+
+        // 0.5s of silence, then a tone starting exactly on a frame boundary
+        let audio: Vec<f32> = (0..16_000)
+            .map(|i| match i {
+                0..8000 => 0.0,
+                _ => 0.3 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16_000.0).sin(),
+            })
+            .collect();
+        let start = Instant::now();
+        let mut frames = Vec::new();
+        for (i, chunk) in audio.chunks(160).enumerate() {
+            let now = start + Duration::from_millis(10 * (i as u64 + 1));
+            processor.push_at(chunk, now, &mut |frame| frames.push(frame));
+        }
+
+        // The suppressor delays audio by a frame; the stamps must not move with it
+        let offset_ms = |at: Instant| (at - start).as_secs_f64() * 1000.0;
+        let onset = frames.iter().find(|f| f.rms > 1000.0).unwrap();
+        assert!((offset_ms(onset.timestamp) - 500.0).abs() < 2.0, "onset frame at {:.2}ms", offset_ms(onset.timestamp));
+        let events: Vec<_> = rx.try_iter().collect();
+        assert_eq!(events[0].0.kind, crate::vad::VadEventKind::SpeechStart);
+        assert!((offset_ms(events[0].1) - 500.0).abs() < 2.0, "speech start at {:.2}ms", offset_ms(events[0].1));
+    }
+
+    #[test]
+    fn test_monitor_reports_overflow_and_stall() {
+        use ringbuf::traits::Split;
+
+        let status = StatusReporter::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        status.set_sink(Some(Box::new(move |event: crate::capture_status::StatusEvent| {
+            tx.send((event.code, event.state)).unwrap()
+        })));
+        status.set_state(CaptureState::Running, "");
+
+        let stats = Arc::new(PipelineStats::default());
+        let overflow = OverflowCounter::new();
+        let mut monitor = SourceMonitor::new(stats.clone(), overflow.clone(), status.clone(), Some(STALL_TIMEOUT));
+        let (_producer, consumer) = ringbuf::HeapRb::<f32>::new(1024).split();
+
+        // Drops are reported once per interval, counted every time
+        let start = Instant::now();
+        overflow.record(480, 0);
+        monitor.before_drain(&consumer, start);
+        overflow.record(480, 470);
+        monitor.before_drain(&consumer, start + Duration::from_secs(1));
+        assert_eq!(stats.snapshot().samples_dropped, 490.0);
+
+        // Silence past the timeout stalls; input resumes running
+        monitor.on_idle(Instant::now() + STALL_TIMEOUT);
+        monitor.on_input(Instant::now());
+
+        // A producer giving up fails the capture once
+        assert!(overflow.abandon());
+        assert!(!overflow.abandon());
+        monitor.before_drain(&consumer, start + Duration::from_secs(2));
+        monitor.before_drain(&consumer, start + Duration::from_secs(3));
+
+        let codes: Vec<_> = rx.try_iter().collect();
+        assert_eq!(codes, [
+            (StatusCode::StateChanged, CaptureState::Running),
+            (StatusCode::BufferOverflow, CaptureState::Running),
+            (StatusCode::StateChanged, CaptureState::Stalled),
+            (StatusCode::StreamStalled, CaptureState::Stalled),
+            (StatusCode::StateChanged, CaptureState::Running),
+            (StatusCode::BufferOverflow, CaptureState::Failed),
+        ]);
+    }
+
+    #[test]
+    fn test_preroll_precedes_onset() {
+        let mut config = PipelineConfig::for_microphone();
+        config.suppression.silence_keepalive_interval = Duration::from_secs(10);
+        let mut processor = FrameProcessor::new(16_000, 1, config);
+
+        // 1s of silence, 100ms of a soft onset below the threshold, then speech
+        let audio: Vec<f32> = (0..24_000)
+            .map(|i| {
+                let tone = (2.0 * std::f32::consts::PI * 300.0 * i as f32 / 16_000.0).sin();
+                match i {
+                    0..16_000 => 0.0,
+                    16_000..17_600 => 0.002 * tone,
+                    _ => 0.3 * tone,
+                }
+            })
+            .collect();
+        let start = Instant::now();
+        let mut frames = Vec::new();
+        for (i, chunk) in audio.chunks(160).enumerate() {
+            let now = start + Duration::from_millis(10 * (i as u64 + 1));
+            processor.push_at(chunk, now, &mut |frame| frames.push(frame));
+        }
+
+        // Start-up hangover, then the 60ms pre-roll right before speech
+        let kinds: Vec<FrameKind> = frames.iter().map(|f| f.kind).collect();
+        let onset = kinds.iter().position(|&k| k == FrameKind::Speech).unwrap();
+        assert_eq!(kinds[onset - 4..onset], [FrameKind::Hangover, FrameKind::Preroll, FrameKind::Preroll, FrameKind::Preroll]);
+        assert!(frames[onset - 1].rms > 10.0 && frames[onset - 1].rms < 100.0);
+
+        // Contiguous sequence numbers and 20ms steps in capture time
+        assert!(frames.iter().enumerate().all(|(i, f)| f.sequence == i as u64));
+        for pair in frames[onset - 3..=onset].windows(2) {
+            let step_ms = (pair[1].timestamp - pair[0].timestamp).as_secs_f64() * 1000.0;
+            assert!((step_ms - 20.0).abs() < 1.0, "{:.2}ms", step_ms);
+        }
+    }
+
+    #[test]
+    fn test_preroll_never_overlaps_keepalives() {
+        let mut config = PipelineConfig::for_microphone();
+        config.suppression.preroll = Duration::from_millis(300);
+        assert_eq!(config.suppression.silence_keepalive_interval, Duration::from_millis(100));
+        let mut processor = FrameProcessor::new(16_000, 1, config);
+
+        // 1s of silence, 300ms of a soft onset below the threshold, then speech
+        let audio: Vec<f32> = (0..25_600)
+            .map(|i| {
+                let tone = (2.0 * std::f32::consts::PI * 300.0 * i as f32 / 16_000.0).sin();
+                match i {
+                    0..16_000 => 0.0,
+                    16_000..20_800 => 0.002 * tone,
+                    _ => 0.3 * tone,
+                }
+            })
+            .collect();
+        let start = Instant::now();
+        let mut frames = Vec::new();
+        for (i, chunk) in audio.chunks(160).enumerate() {
+            let now = start + Duration::from_millis(10 * (i as u64 + 1));
+            processor.push_at(chunk, now, &mut |frame| frames.push(frame));
+        }
+
+        // Keepalives went out during the onset; the pre-roll starts after
+        // the last one instead of re-sending the slots they took
+        let kinds: Vec<FrameKind> = frames.iter().map(|f| f.kind).collect();
+        let onset = kinds.iter().position(|&k| k == FrameKind::Speech).unwrap();
+        let preroll = kinds[..onset].iter().rev().take_while(|&&k| k == FrameKind::Preroll).count();
+        assert!((1..5).contains(&preroll), "{:?}", &kinds[onset.saturating_sub(20)..=onset]);
+        assert_eq!(kinds[onset - preroll - 1], FrameKind::Keepalive);
+        assert!(frames[onset - preroll..onset].iter().all(|f| f.rms > 10.0 && f.rms < 100.0));
+
+        // Timestamps never go backwards: keepalive, pre-roll and speech
+        // follow each other 20ms apart
+        assert!(
+            frames.windows(2).all(|pair| pair[1].timestamp > pair[0].timestamp),
+            "{:?}",
+            frames.iter().map(|f| (f.kind, f.timestamp - start)).collect::<Vec<_>>()
+        );
+        for pair in frames[onset - preroll - 1..=onset].windows(2) {
+            let step_ms = (pair[1].timestamp - pair[0].timestamp).as_secs_f64() * 1000.0;
+            assert!((step_ms - 20.0).abs() < 1.0, "{:.2}ms", step_ms);
+        }
+    }
+}
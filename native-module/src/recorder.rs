@@ -0,0 +1,649 @@
+// Meeting Recorder - writes captured audio to WAV or FLAC
+//
+// Each capture owns a RecordingTap that sees every resampled frame before
+// echo cancellation, noise suppression and the silence gate, so the file
+// holds continuous audio even while STT frames are suppressed.
+//
+// Frames carry capture timestamps; StreamAligner puts the sources on one
+// timeline (gaps filled with silence, clock drift corrected) before they
+// are summed to mono or written as stereo (mic left, system right).
+//
+// The DSP threads only copy frames into a bounded channel (never block);
+// mixing, encoding and disk IO happen on the recorder's writer thread.
+// Attaching or detaching taps takes effect on the next frame, so recording
+// starts and stops without restarting capture.
+//
+// Crash safety: the header is rewritten every CHECKPOINT_INTERVAL (WAV data
+// size, FLAC total samples), so a crash leaves a playable file missing at
+// most the last interval.
+
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::flac_encoder::FlacWriter;
+use crate::stream_align::StreamAligner;
+
+/// How often the file header is brought up to date
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Frames buffered per source before the tap starts dropping (~10s of 20ms frames)
+const TAP_QUEUE_FRAMES: usize = 500;
+
+/// How far one source may run ahead before a silent source is padded with zeros
+const MAX_SOURCE_LAG_MS: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Wav,
+    Flac,
+}
+
+impl RecordingFormat {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "wav" => Ok(Self::Wav),
+            "flac" => Ok(Self::Flac),
+            other => Err(anyhow!("Unknown recording format: {}", other)),
+        }
+    }
+
+    /// FLAC for a ".flac" extension, WAV otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("flac") => Self::Flac,
+            _ => Self::Wav,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Flac => "flac",
+        }
+    }
+}
+
+/// How the sources map onto the output channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelLayout {
+    /// All sources summed into one channel
+    Mono,
+    /// Source 0 left, source 1 right
+    Stereo,
+}
+
+impl ChannelLayout {
+    pub fn channels(&self) -> u16 {
+        match self {
+            Self::Mono => 1,
+            Self::Stereo => 2,
+        }
+    }
+}
+
+// ============================================================================
+// TAP (DSP thread side)
+// ============================================================================
+
+struct TapFrame {
+    source: usize,
+    /// Capture time of the first sample
+    timestamp: Instant,
+    samples: Vec<i16>,
+}
+
+struct TapSender {
+    source: usize,
+    tx: SyncSender<TapFrame>,
+    progress: Arc<RecordingProgress>,
+}
+
+/// Per-capture hook the pipeline pushes resampled frames into
+#[derive(Default)]
+pub struct RecordingTap {
+    sender: Mutex<Option<TapSender>>,
+}
+
+impl RecordingTap {
+    /// Called on the DSP thread for every frame; no-op when not recording
+    pub fn push(&self, frame: &[i16], timestamp: Instant) {
+        let guard = self.sender.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = guard.as_ref() {
+            let frame = TapFrame { source: sender.source, timestamp, samples: frame.to_vec() };
+            if sender.tx.try_send(frame).is_err() {
+                sender.progress.dropped_frames.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.sender.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+    }
+
+    fn attach(&self, sender: TapSender) {
+        *self.sender.lock().unwrap_or_else(|e| e.into_inner()) = Some(sender);
+    }
+
+    fn detach(&self) {
+        *self.sender.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+// ============================================================================
+// RECORDER (writer thread)
+// ============================================================================
+
+/// Counters the writer thread publishes for getInfo()
+#[derive(Default)]
+struct RecordingProgress {
+    /// Samples per channel written
+    samples: AtomicU64,
+    /// File size so far
+    bytes: AtomicU64,
+    dropped_frames: AtomicU64,
+}
+
+pub struct RecorderConfig {
+    /// Output file; None records nothing to disk (frame sink only)
+    pub path: Option<PathBuf>,
+    pub format: RecordingFormat,
+    pub sample_rate: u32,
+    pub layout: ChannelLayout,
+    /// Samples per channel handed to the frame sink at a time
+    pub frame_samples: usize,
+}
+
+/// Receives interleaved aligned frames on the writer thread
+pub type AlignedFrameSink = Box<dyn FnMut(Vec<i16>) + Send>;
+
+pub struct Recorder {
+    config: RecorderConfig,
+    taps: Vec<Arc<RecordingTap>>,
+    progress: Arc<RecordingProgress>,
+    thread: Option<thread::JoinHandle<Result<()>>>,
+}
+
+impl Recorder {
+    /// Create the file and start recording every tap (in tap order)
+    pub fn start(
+        config: RecorderConfig,
+        taps: Vec<Arc<RecordingTap>>,
+        sink: Option<AlignedFrameSink>,
+    ) -> Result<Self> {
+        if taps.is_empty() {
+            return Err(anyhow!("No capture to record"));
+        }
+        if config.layout == ChannelLayout::Stereo && taps.len() != 2 {
+            return Err(anyhow!("Stereo recording needs exactly two captures"));
+        }
+        if taps.iter().any(|tap| tap.is_recording()) {
+            return Err(anyhow!("Capture is already being recorded"));
+        }
+
+        let progress = Arc::new(RecordingProgress::default());
+        let writer = match &config.path {
+            Some(path) => {
+                let file = File::create(path)
+                    .map_err(|e| anyhow!("Failed to create {}: {}", path.display(), e))?;
+                Some(FileWriter::new(
+                    CountingWriter::new(BufWriter::new(file), progress.clone()),
+                    config.format,
+                    config.sample_rate,
+                    config.layout.channels(),
+                )?)
+            }
+            None => None,
+        };
+
+        let (tx, rx) = mpsc::sync_channel(TAP_QUEUE_FRAMES * taps.len());
+        for (source, tap) in taps.iter().enumerate() {
+            tap.attach(TapSender { source, tx: tx.clone(), progress: progress.clone() });
+        }
+        drop(tx);
+
+        let mut output = AlignedOutput {
+            aligner: StreamAligner::new(
+                taps.len(),
+                config.sample_rate,
+                config.sample_rate as usize * MAX_SOURCE_LAG_MS / 1000,
+            ),
+            layout: config.layout,
+            writer,
+            sink,
+            frame_samples: config.frame_samples.max(1),
+            pending: Vec::new(),
+        };
+        let progress_clone = progress.clone();
+        let thread = thread::spawn(move || {
+            let result = run_writer(rx, &mut output, &progress_clone);
+            if let Err(e) = &result {
+                eprintln!("[Recorder] Writer failed: {}", e);
+            }
+            result
+        });
+
+        match &config.path {
+            Some(path) => println!("[Recorder] Recording {} source(s) to {} ({}, {}Hz, {:?})",
+                taps.len(), path.display(), config.format.as_str(), config.sample_rate, config.layout),
+            None => println!("[Recorder] Mixing {} source(s) ({}Hz, {:?})",
+                taps.len(), config.sample_rate, config.layout),
+        }
+
+        Ok(Self {
+            config,
+            taps,
+            progress,
+            thread: Some(thread),
+        })
+    }
+
+    /// Detach the taps, flush the remaining audio and finalize the file
+    pub fn stop(&mut self) -> Result<()> {
+        for tap in &self.taps {
+            tap.detach();
+        }
+        match self.thread.take() {
+            Some(handle) => handle.join().map_err(|_| anyhow!("Recorder thread panicked"))?,
+            None => Ok(()),
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.config.path.as_deref()
+    }
+
+    pub fn format(&self) -> RecordingFormat {
+        self.config.format
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.progress.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn duration(&self) -> Duration {
+        let samples = self.progress.samples.load(Ordering::Relaxed);
+        Duration::from_secs_f64(samples as f64 / self.config.sample_rate as f64)
+    }
+
+    /// Frames lost because the writer fell behind
+    pub fn dropped_frames(&self) -> u64 {
+        self.progress.dropped_frames.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+fn run_writer(
+    rx: Receiver<TapFrame>,
+    output: &mut AlignedOutput,
+    progress: &RecordingProgress,
+) -> Result<()> {
+    let mut last_checkpoint = Instant::now();
+
+    loop {
+        match rx.recv_timeout(CHECKPOINT_INTERVAL) {
+            Ok(frame) => {
+                output.aligner.push(frame.source, &frame.samples, frame.timestamp);
+                let written = output.drain(false)?;
+                progress.samples.fetch_add(written as u64, Ordering::Relaxed);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // Every tap detached: recording stopped
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+            if let Some(writer) = &mut output.writer {
+                writer.checkpoint()?;
+            }
+            last_checkpoint = Instant::now();
+        }
+    }
+
+    let written = output.drain(true)?;
+    progress.samples.fetch_add(written as u64, Ordering::Relaxed);
+    match output.writer.take() {
+        Some(writer) => writer.finalize(),
+        None => Ok(()),
+    }
+}
+
+/// Aligned sources on their way to the file and / or the frame sink
+struct AlignedOutput {
+    aligner: StreamAligner,
+    layout: ChannelLayout,
+    writer: Option<FileWriter>,
+    sink: Option<AlignedFrameSink>,
+    frame_samples: usize,
+    /// Interleaved samples short of a whole sink frame
+    pending: Vec<i16>,
+}
+
+impl AlignedOutput {
+    /// Write whatever the aligner can release; returns samples per channel
+    fn drain(&mut self, flush: bool) -> Result<usize> {
+        let sources = self.aligner.drain(flush);
+        let count = sources.first().map_or(0, |s| s.len());
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let interleaved: Vec<i16> = match self.layout {
+            ChannelLayout::Mono => (0..count)
+                .map(|i| {
+                    let sum: i32 = sources.iter().map(|s| s[i] as i32).sum();
+                    sum.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+                })
+                .collect(),
+            ChannelLayout::Stereo => (0..count)
+                .flat_map(|i| [sources[0][i], sources[1][i]])
+                .collect(),
+        };
+
+        if let Some(writer) = &mut self.writer {
+            writer.write(&interleaved)?;
+        }
+        if let Some(sink) = &mut self.sink {
+            let frame_len = self.frame_samples * self.layout.channels() as usize;
+            self.pending.extend_from_slice(&interleaved);
+            while self.pending.len() >= frame_len {
+                sink(self.pending.drain(..frame_len).collect());
+            }
+        }
+        Ok(count)
+    }
+}
+
+// ============================================================================
+// FILE WRITERS
+// ============================================================================
+
+type RecordingFile = CountingWriter<BufWriter<File>>;
+
+enum FileWriter {
+    Wav(hound::WavWriter<RecordingFile>),
+    Flac(FlacWriter<RecordingFile>),
+}
+
+impl FileWriter {
+    fn new(file: RecordingFile, format: RecordingFormat, sample_rate: u32, channels: u16) -> Result<Self> {
+        Ok(match format {
+            RecordingFormat::Wav => {
+                let spec = hound::WavSpec {
+                    channels,
+                    sample_rate,
+                    bits_per_sample: 16,
+                    sample_format: hound::SampleFormat::Int,
+                };
+                Self::Wav(hound::WavWriter::new(file, spec)?)
+            }
+            RecordingFormat::Flac => Self::Flac(FlacWriter::new(file, sample_rate, channels)?),
+        })
+    }
+
+    fn write(&mut self, samples: &[i16]) -> Result<()> {
+        match self {
+            Self::Wav(wav) => {
+                let mut writer = wav.get_i16_writer(samples.len() as u32);
+                for &sample in samples {
+                    writer.write_sample(sample);
+                }
+                writer.flush()?;
+            }
+            Self::Flac(flac) => flac.write_samples(samples)?,
+        }
+        Ok(())
+    }
+
+    /// Update the header so the file is playable as it stands
+    fn checkpoint(&mut self) -> Result<()> {
+        match self {
+            Self::Wav(wav) => wav.flush()?,
+            Self::Flac(flac) => flac.checkpoint()?,
+        }
+        Ok(())
+    }
+
+    fn finalize(self) -> Result<()> {
+        match self {
+            Self::Wav(wav) => wav.finalize()?,
+            Self::Flac(flac) => flac.finalize()?,
+        }
+        Ok(())
+    }
+}
+
+/// Tracks the file size (furthest byte written) for progress reporting
+struct CountingWriter<W> {
+    inner: W,
+    position: u64,
+    progress: Arc<RecordingProgress>,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(inner: W, progress: Arc<RecordingProgress>) -> Self {
+        Self { inner, position: 0, progress }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.position += written as u64;
+        self.progress.bytes.fetch_max(self.position, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for CountingWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flac_encoder::decode;
+    use crate::test_fixtures;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rustyn-recorder-{}-{}", std::process::id(), name))
+    }
+
+    fn mono_config(path: &Path, format: RecordingFormat) -> RecorderConfig {
+        RecorderConfig {
+            path: Some(path.to_path_buf()),
+            format,
+            sample_rate: 16_000,
+            layout: ChannelLayout::Mono,
+            frame_samples: 320,
+        }
+    }
+
+    /// Push 20ms frames stamped on a steady clock starting at `start`
+    fn feed(tap: &RecordingTap, samples: &[i16], start: Instant) {
+        for (i, frame) in samples.chunks(320).enumerate() {
+            tap.push(frame, start + Duration::from_millis(20 * i as u64));
+        }
+    }
+
+    #[test]
+    fn test_records_wav_and_flac() {
+        let speech = test_fixtures::to_i16(&test_fixtures::speech(2.0, 41));
+        for format in [RecordingFormat::Wav, RecordingFormat::Flac] {
+            let path = temp_path(&format!("single.{}", format.as_str()));
+            let tap = Arc::new(RecordingTap::default());
+            let start = Instant::now();
+            feed(&tap, &speech, start); // before recording: ignored
+
+            let mut recorder = Recorder::start(mono_config(&path, format), vec![tap.clone()], None).unwrap();
+            feed(&tap, &speech, start);
+            recorder.stop().unwrap();
+            feed(&tap, &speech, start); // after recording: ignored
+
+            assert_eq!(recorder.duration(), Duration::from_secs(2));
+            let data = std::fs::read(&path).unwrap();
+            assert_eq!(recorder.bytes_written(), data.len() as u64);
+            let decoded = match format {
+                RecordingFormat::Wav => hound::WavReader::open(&path).unwrap()
+                    .into_samples::<i16>().map(|s| s.unwrap()).collect::<Vec<_>>(),
+                RecordingFormat::Flac => decode::decode(&data).3,
+            };
+            assert_eq!(decoded, speech);
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+
+    #[test]
+    fn test_wav_playable_after_crash() {
+        let path = temp_path("crash.wav");
+        let tap = Arc::new(RecordingTap::default());
+        let recorder = Recorder::start(mono_config(&path, RecordingFormat::Wav), vec![tap.clone()], None).unwrap();
+        feed(&tap, &vec![1000i16; 16_000], Instant::now());
+        thread::sleep(CHECKPOINT_INTERVAL + Duration::from_millis(500));
+
+        // Crash: the writer never finalizes
+        std::mem::forget(recorder);
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert!(reader.duration() >= 16_000, "{} samples", reader.duration());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_stereo_file_and_interleaved_frames() {
+        let path = temp_path("stereo.wav");
+        let (mic, system) = (Arc::new(RecordingTap::default()), Arc::new(RecordingTap::default()));
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let frames_clone = frames.clone();
+        let mut recorder = Recorder::start(
+            RecorderConfig { layout: ChannelLayout::Stereo, ..mono_config(&path, RecordingFormat::Wav) },
+            vec![mic.clone(), system.clone()],
+            Some(Box::new(move |frame| frames_clone.lock().unwrap().push(frame))),
+        ).unwrap();
+
+        // System audio starts 100ms later than the microphone
+        let start = Instant::now();
+        for i in 0..50u64 {
+            let timestamp = start + Duration::from_millis(20 * i);
+            mic.push(&[1000; 320], timestamp);
+            if i >= 5 {
+                system.push(&[-1000; 320], timestamp);
+            }
+        }
+        recorder.stop().unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        let samples: Vec<i16> = reader.into_samples().map(|s| s.unwrap()).collect();
+        let (left, right): (Vec<i16>, Vec<i16>) = samples.chunks(2).map(|p| (p[0], p[1])).unzip();
+        assert!(left.iter().all(|&s| s == 1000));
+        assert!(right[..1600].iter().all(|&s| s == 0));
+        assert!(right[1600..].iter().all(|&s| s == -1000));
+
+        let frames = frames.lock().unwrap();
+        assert_eq!(frames.len(), 50);
+        assert!(frames.iter().all(|f| f.len() == 640));
+        assert_eq!(frames.concat(), samples);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Sample index of every click (start of a loud burst after quiet)
+    fn click_onsets(channel: &[i16]) -> Vec<usize> {
+        let mut onsets = Vec::new();
+        let mut quiet_since = 0;
+        for (i, &s) in channel.iter().enumerate() {
+            if s.unsigned_abs() > 6000 {
+                if i - quiet_since > 1600 {
+                    onsets.push(i);
+                }
+                quiet_since = i;
+            }
+        }
+        onsets
+    }
+
+    #[test]
+    fn test_drifting_sources_stay_aligned() {
+        use crate::pipeline::{FrameProcessor, PipelineConfig};
+
+        // Two offline sources, both nominally 48kHz: the microphone clock is
+        // exact, the system device runs 400ppm fast. Both hear a click at every
+        // whole second of wall time. Counting samples alone, the system clicks
+        // would land 24ms late after a minute.
+        let true_rates = [48_000.0, 48_000.0 * 1.0004];
+        let seconds = 60.0;
+        let click = |wall: f64| {
+            let phase = wall.fract();
+            if wall >= 1.0 && phase < 0.003 {
+                0.6 * (2.0 * std::f64::consts::PI * 2000.0 * phase).sin() as f32
+            } else {
+                0.0
+            }
+        };
+
+        let (tx, rx) = mpsc::sync_channel(100_000);
+        let progress = Arc::new(RecordingProgress::default());
+        let mut processors: Vec<FrameProcessor> = (0..2)
+            .map(|source| {
+                let tap = Arc::new(RecordingTap::default());
+                tap.attach(TapSender { source, tx: tx.clone(), progress: progress.clone() });
+                FrameProcessor::new(48_000, 1, PipelineConfig {
+                    recording_tap: Some(tap),
+                    ..PipelineConfig::for_microphone()
+                })
+            })
+            .collect();
+
+        let base = Instant::now();
+        let mut jitter = test_fixtures::Noise::new(61);
+        let mut delivered = [0usize; 2];
+        let mut aligner = StreamAligner::new(2, 16_000, 8000);
+        let mut channels = [Vec::new(), Vec::new()];
+
+        for step in 1..=(seconds * 100.0) as usize {
+            let wall = step as f64 * 0.010;
+            for source in 0..2 {
+                // Everything the device captured by now, drained a little late
+                let available = (wall * true_rates[source]) as usize;
+                let samples: Vec<f32> = (delivered[source]..available)
+                    .map(|n| click(n as f64 / true_rates[source]))
+                    .collect();
+                delivered[source] = available;
+                let drained_at = wall + 0.003 * jitter.next().abs() as f64;
+                processors[source].push_at(&samples, base + Duration::from_secs_f64(drained_at), &mut |_| {});
+            }
+            while let Ok(frame) = rx.try_recv() {
+                aligner.push(frame.source, &frame.samples, frame.timestamp);
+            }
+            for (channel, out) in channels.iter_mut().zip(aligner.drain(false)) {
+                channel.extend(out);
+            }
+        }
+
+        let (left, right) = (click_onsets(&channels[0]), click_onsets(&channels[1]));
+        assert!(left.len() >= 58, "{} clicks", left.len());
+        assert_eq!(left.len(), right.len());
+        let worst_ms = left.iter().zip(&right)
+            .map(|(&l, &r)| (l as f64 - r as f64).abs() / 16.0)
+            .fold(0.0, f64::max);
+        assert!(worst_ms < 2.0, "worst misalignment {:.2}ms over {} clicks", worst_ms, left.len());
+    }
+}
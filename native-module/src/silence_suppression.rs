@@ -9,8 +9,61 @@
 // LATENCY BUDGET:
 // - Speech onset: 0ms delay (immediate)
 // - Hangover: Only affects AFTER speech ends (no latency impact)
+//
+// TIMING:
+// Hangover and keepalive intervals are measured in stream time (frames
+// processed), not wall-clock time. Live captures behave identically, and
+// offline sources replayed faster than real time stay deterministic.
+//
+// PRE-ROLL:
+// The audio of the frames since the gate closed is kept (up to the pre-roll
+// length) and sent just before the first speech frame, so soft onsets the
+// detector only catches late still reach STT. A keepalive occupies its
+// frame's time slot on the wire, so it clears the buffer like speech and
+// hangover frames do: the pre-roll only holds frames after the last one
+// sent, and emitted timestamps never go backwards.
+//
+// THRESHOLD:
+// The background noise floor is tracked continuously (falls quickly to
+// quiet frames, rises slowly through loud ones). In Adaptive mode the RMS
+// speech threshold sits a margin above the floor, clamped to a range, so a
+// noisy room does not hold the gate open and a quiet mic keeps soft
+// syllables. Fixed mode uses speech_threshold_rms as is.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::audio_config::{FRAME_MS, SAMPLE_RATE, VAD_PREROLL_CHUNKS};
+use crate::speech_detector::{SpeechDetector, SpeechDetectorKind};
+
+/// Weight of the newest frame in the detector time average
+const DETECTOR_TIME_SMOOTHING: f32 = 0.05;
 
-use std::time::{Duration, Instant};  // Added for timing
+/// Time constant of the noise floor following quieter frames
+const FLOOR_FALL_SECS: f32 = 0.1;
+
+/// Fastest the noise floor climbs towards louder frames
+const FLOOR_RISE_DB_PER_SEC: f32 = 3.0;
+
+/// VAD_PREROLL_CHUNKS frames of the default frame duration
+pub const DEFAULT_PREROLL: Duration = Duration::from_millis(VAD_PREROLL_CHUNKS as u64 * FRAME_MS as u64);
+
+/// Longest configurable pre-roll
+pub const MAX_PREROLL: Duration = Duration::from_secs(1);
+
+/// Adaptive threshold defaults (suit both microphones and system audio)
+pub const ADAPTIVE_MARGIN_DB: f32 = 9.0;
+pub const ADAPTIVE_MIN_RMS: f32 = 30.0;
+pub const ADAPTIVE_MAX_RMS: f32 = 2000.0;
+
+/// How the RMS speech threshold is chosen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdMode {
+    /// speech_threshold_rms
+    Fixed,
+    /// Noise floor + margin, clamped to [min_rms, max_rms]
+    Adaptive { margin_db: f32, min_rms: f32, max_rms: f32 },
+}
 
 /// Configuration for silence suppression
 /// Optimized for low latency
@@ -24,6 +77,15 @@ pub struct SilenceSuppressionConfig {
     
     /// How often to send a keepalive frame during silence
     pub silence_keepalive_interval: Duration,
+
+    /// How frames are classified (Rms uses the threshold below)
+    pub detector: SpeechDetectorKind,
+
+    /// Fixed speech_threshold_rms, or one that follows the noise floor
+    pub threshold: ThresholdMode,
+
+    /// Suppressed audio sent ahead of a speech onset (zero disables)
+    pub preroll: Duration,
 }
 
 impl Default for SilenceSuppressionConfig {
@@ -32,6 +94,9 @@ impl Default for SilenceSuppressionConfig {
             speech_threshold_rms: 100.0,  // Lower = more sensitive
             speech_hangover: Duration::from_millis(200),  // Shorter = faster cost savings
             silence_keepalive_interval: Duration::from_millis(100),
+            detector: SpeechDetectorKind::Rms,
+            threshold: ThresholdMode::Fixed,
+            preroll: DEFAULT_PREROLL,
         }
     }
 }
@@ -44,6 +109,9 @@ impl SilenceSuppressionConfig {
             speech_threshold_rms: 30.0,  // Very low threshold
             speech_hangover: Duration::from_millis(300),
             silence_keepalive_interval: Duration::from_millis(100),
+            detector: SpeechDetectorKind::Rms,
+            threshold: ThresholdMode::Fixed,
+            preroll: DEFAULT_PREROLL,
         }
     }
     
@@ -53,6 +121,9 @@ impl SilenceSuppressionConfig {
             speech_threshold_rms: 100.0,
             speech_hangover: Duration::from_millis(200),
             silence_keepalive_interval: Duration::from_millis(100),
+            detector: SpeechDetectorKind::Rms,
+            threshold: ThresholdMode::Fixed,
+            preroll: DEFAULT_PREROLL,
         }
     }
 }
@@ -60,11 +131,29 @@ impl SilenceSuppressionConfig {
 /// Silence suppression state machine
 pub struct SilenceSuppressor {
     config: SilenceSuppressionConfig,
+    detector: Box<dyn SpeechDetector>,
     state: SuppressionState,
-    last_speech_time: Instant,
-    last_keepalive_time: Instant,
+    /// Sample rate of the frames passed to `process`
+    sample_rate: u32,
+    /// Audio processed so far
+    stream_time: Duration,
+    last_speech_time: Duration,
+    last_keepalive_time: Duration,
     frames_sent: u64,
     frames_suppressed: u64,
+    frames_keepalive: u64,
+    /// RMS of the last processed frame
+    last_rms: f32,
+    /// Detector decision for the last processed frame
+    last_speech: bool,
+    /// Smoothed time the detector takes per frame, ms
+    detector_ms: f32,
+    /// Background level estimate (RMS, dB re 1 LSB); None before the first frame
+    noise_floor_db: Option<f32>,
+    /// RMS threshold currently applied
+    threshold_rms: f32,
+    /// Suppressed frames since the last frame sent (newest last)
+    preroll: VecDeque<Vec<i16>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -79,57 +168,136 @@ enum SuppressionState {
 pub enum FrameAction {
     /// Send this frame to STT
     Send(Vec<i16>),
+    /// Speech onset: send the pre-roll frames (oldest first), then this frame
+    SendWithPreroll { preroll: Vec<Vec<i16>>, frame: Vec<i16> },
+    /// Send this frame to STT (no speech, but within the hangover)
+    Hangover(Vec<i16>),
     /// Replace with silence keepalive frame
     SendSilence,
     /// Suppress this frame (timing maintained by keepalives)
     Suppress,
 }
 
+/// What an emitted frame carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// Audio above the speech threshold
+    Speech,
+    /// Audio sent during the hangover after speech
+    Hangover,
+    /// Generated silence keeping the stream's timing alive
+    Keepalive,
+    /// Suppressed audio sent just before a speech onset
+    Preroll,
+}
+
+impl FrameKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FrameKind::Speech => "speech",
+            FrameKind::Hangover => "hangover",
+            FrameKind::Keepalive => "keepalive",
+            FrameKind::Preroll => "preroll",
+        }
+    }
+}
+
 impl SilenceSuppressor {
     pub fn new(config: SilenceSuppressionConfig) -> Self {
-        let now = Instant::now();
-        println!("[SilenceSuppressor] Created with threshold={}, hangover={}ms, keepalive={}ms",
+        Self::with_sample_rate(config, SAMPLE_RATE)
+    }
+
+    /// Suppressor for frames at a configured output rate
+    pub fn with_sample_rate(config: SilenceSuppressionConfig, sample_rate: u32) -> Self {
+        let detector = config.detector.create(sample_rate, config.speech_threshold_rms);
+        Self::with_detector(config, sample_rate, detector)
+    }
+
+    /// Suppressor deciding speech with a custom detector
+    /// (`config.detector` and `speech_threshold_rms` are then unused)
+    pub fn with_detector(
+        config: SilenceSuppressionConfig,
+        sample_rate: u32,
+        detector: Box<dyn SpeechDetector>,
+    ) -> Self {
+        println!("[SilenceSuppressor] Created with detector={:?}, threshold={}, hangover={}ms, keepalive={}ms",
+            config.detector,
             config.speech_threshold_rms,
             config.speech_hangover.as_millis(),
             config.silence_keepalive_interval.as_millis()
         );
         Self {
-            config,
+            detector,
             state: SuppressionState::Active, // Start in active to not miss first words
-            last_speech_time: now,
-            last_keepalive_time: now,
+            sample_rate: sample_rate.max(1),
+            stream_time: Duration::ZERO,
+            last_speech_time: Duration::ZERO,
+            last_keepalive_time: Duration::ZERO,
             frames_sent: 0,
             frames_suppressed: 0,
+            frames_keepalive: 0,
+            last_rms: 0.0,
+            last_speech: false,
+            detector_ms: 0.0,
+            noise_floor_db: None,
+            threshold_rms: config.speech_threshold_rms,
+            preroll: VecDeque::new(),
+            config,
         }
     }
     
     /// Process a frame and determine what to do with it
     /// CRITICAL: Speech frames are NEVER delayed
     pub fn process(&mut self, frame: &[i16]) -> FrameAction {
-        let now = Instant::now();
+        // Timestamps refer to the end of the frame
+        let frame_duration = Duration::from_secs_f64(frame.len() as f64 / self.sample_rate as f64);
+        self.stream_time += frame_duration;
+        let now = self.stream_time;
         let rms = calculate_rms(frame);
-        let has_speech = rms >= self.config.speech_threshold_rms;
+        self.last_rms = rms;
+
+        // Threshold from the floor of the frames before this one
+        if let ThresholdMode::Adaptive { margin_db, min_rms, max_rms } = self.config.threshold {
+            if let Some(floor_db) = self.noise_floor_db {
+                self.threshold_rms = db_to_rms(floor_db + margin_db).clamp(min_rms, max_rms);
+                self.detector.set_threshold(self.threshold_rms);
+            }
+        }
+        self.update_noise_floor(rms, frame_duration.as_secs_f32());
+
+        let started = Instant::now();
+        let has_speech = self.detector.is_speech(frame, rms);
+        self.last_speech = has_speech;
+        let elapsed_ms = started.elapsed().as_secs_f32() * 1000.0;
+        self.detector_ms += DETECTOR_TIME_SMOOTHING * (elapsed_ms - self.detector_ms);
         
         // ALWAYS check for speech first - immediate response
         if has_speech {
             self.state = SuppressionState::Active;
             self.last_speech_time = now;
             self.frames_sent += 1;
-            return FrameAction::Send(frame.to_vec());
+            if self.preroll.is_empty() {
+                return FrameAction::Send(frame.to_vec());
+            }
+            let preroll: Vec<Vec<i16>> = self.preroll.drain(..).collect();
+            self.frames_sent += preroll.len() as u64;
+            self.frames_suppressed -= preroll.len() as u64;
+            return FrameAction::SendWithPreroll { preroll, frame: frame.to_vec() };
         }
         
         // No speech detected - check state
         match self.state {
             SuppressionState::Active | SuppressionState::Hangover => {
                 // Check if hangover period has elapsed
-                if now.duration_since(self.last_speech_time) > self.config.speech_hangover {
+                if now - self.last_speech_time > self.config.speech_hangover {
                     self.state = SuppressionState::Suppressed;
                     // Fall through to check keepalive
                 } else {
                     // Still in hangover - send full frame
                     self.state = SuppressionState::Hangover;
+                    self.preroll.clear();
                     self.frames_sent += 1;
-                    return FrameAction::Send(frame.to_vec());
+                    return FrameAction::Hangover(frame.to_vec());
                 }
             }
             SuppressionState::Suppressed => {
@@ -138,12 +306,15 @@ impl SilenceSuppressor {
         }
         
         // In suppressed state - check if time for keepalive
-        if now.duration_since(self.last_keepalive_time) >= self.config.silence_keepalive_interval {
+        if now - self.last_keepalive_time >= self.config.silence_keepalive_interval {
             self.last_keepalive_time = now;
             self.frames_sent += 1;
+            self.frames_keepalive += 1;
+            self.preroll.clear();
             FrameAction::SendSilence
         } else {
             self.frames_suppressed += 1;
+            self.push_preroll(frame, frame_duration);
             FrameAction::Suppress
         }
     }
@@ -152,7 +323,60 @@ impl SilenceSuppressor {
     pub fn stats(&self) -> (u64, u64) {
         (self.frames_sent, self.frames_suppressed)
     }
+
+    /// Keepalive frames among the frames sent
+    pub fn keepalives(&self) -> u64 {
+        self.frames_keepalive
+    }
     
+    /// RMS level (i16 scale) of the last processed frame
+    pub fn last_rms(&self) -> f32 {
+        self.last_rms
+    }
+
+    /// Detector decision for the last processed frame (before hangover)
+    pub fn last_speech(&self) -> bool {
+        self.last_speech
+    }
+
+    /// Estimated background level (RMS, i16 scale)
+    pub fn noise_floor_rms(&self) -> f32 {
+        self.noise_floor_db.map_or(0.0, db_to_rms)
+    }
+
+    /// RMS speech threshold in use (fixed, or derived from the floor)
+    pub fn threshold_rms(&self) -> f32 {
+        self.threshold_rms
+    }
+
+    fn push_preroll(&mut self, frame: &[i16], frame_duration: Duration) {
+        // Whole frames covering at least the pre-roll length
+        let capacity = self.config.preroll.as_secs_f64() / frame_duration.as_secs_f64().max(1e-6);
+        let capacity = capacity.ceil() as usize;
+        if capacity == 0 {
+            return;
+        }
+        if self.preroll.len() == capacity {
+            self.preroll.pop_front();
+        }
+        self.preroll.push_back(frame.to_vec());
+    }
+
+    fn update_noise_floor(&mut self, rms: f32, frame_secs: f32) {
+        let level_db = 20.0 * rms.max(1.0).log10();
+        let floor_db = self.noise_floor_db.get_or_insert(level_db);
+        if level_db < *floor_db {
+            *floor_db += (level_db - *floor_db) * (1.0 - (-frame_secs / FLOOR_FALL_SECS).exp());
+        } else {
+            *floor_db = level_db.min(*floor_db + FLOOR_RISE_DB_PER_SEC * frame_secs);
+        }
+    }
+
+    /// Average time the speech detector spends per frame, ms
+    pub fn detector_ms(&self) -> f32 {
+        self.detector_ms
+    }
+
     /// Get current state for UI
     pub fn is_speech(&self) -> bool {
         matches!(self.state, SuppressionState::Active | SuppressionState::Hangover)
@@ -160,7 +384,8 @@ impl SilenceSuppressor {
     
     /// Reset state (e.g., when meeting ends)
     pub fn reset(&mut self) {
-        let now = Instant::now();
+        let now = self.stream_time;
+        self.preroll.clear();
         self.state = SuppressionState::Active;
         self.last_speech_time = now;
         self.last_keepalive_time = now;
@@ -168,7 +393,7 @@ impl SilenceSuppressor {
 }
 
 /// Calculate RMS of i16 samples efficiently
-fn calculate_rms(samples: &[i16]) -> f32 {
+pub(crate) fn calculate_rms(samples: &[i16]) -> f32 {
     if samples.is_empty() {
         return 0.0;
     }
@@ -179,10 +404,14 @@ fn calculate_rms(samples: &[i16]) -> f32 {
         .map(|&s| (s as f64) * (s as f64))
         .sum();
     
-    let count = (samples.len() + 3) / 4;
+    let count = samples.len().div_ceil(4);
     (sum_of_squares / count as f64).sqrt() as f32
 }
 
+fn db_to_rms(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
 /// Generate a silence frame of given size
 pub fn generate_silence_frame(size: usize) -> Vec<i16> {
     vec![0i16; size]
@@ -205,16 +434,143 @@ mod tests {
         assert!(suppressor.is_speech());
     }
     
+    #[test]
+    fn test_keepalive_interval_at_8khz() {
+        // 10ms frames at 8kHz: keepalive every 100ms = every 10th frame
+        let mut suppressor = SilenceSuppressor::with_sample_rate(SilenceSuppressionConfig {
+            speech_threshold_rms: 100.0,
+            speech_hangover: Duration::from_millis(0),
+            silence_keepalive_interval: Duration::from_millis(100),
+            detector: SpeechDetectorKind::Rms,
+            threshold: ThresholdMode::Fixed,
+            preroll: DEFAULT_PREROLL,
+        }, 8000);
+
+        let silent_frame: Vec<i16> = vec![0; 80];
+        let keepalives = (0..100)
+            .filter(|_| matches!(suppressor.process(&silent_frame), FrameAction::SendSilence))
+            .count();
+        assert_eq!(keepalives, 10);
+    }
+
     #[test]
     fn test_silence_keepalive() {
         let mut suppressor = SilenceSuppressor::new(SilenceSuppressionConfig {
             speech_threshold_rms: 100.0,
             speech_hangover: Duration::from_millis(0),
             silence_keepalive_interval: Duration::from_millis(50),
+            detector: SpeechDetectorKind::Rms,
+            threshold: ThresholdMode::Fixed,
+            preroll: DEFAULT_PREROLL,
         });
         
         let silent_frame: Vec<i16> = vec![0; 320];
         let action = suppressor.process(&silent_frame);
         assert!(matches!(action, FrameAction::SendSilence | FrameAction::Suppress));
     }
+
+    fn adaptive_suppressor() -> SilenceSuppressor {
+        SilenceSuppressor::new(SilenceSuppressionConfig {
+            threshold: ThresholdMode::Adaptive {
+                margin_db: ADAPTIVE_MARGIN_DB,
+                min_rms: ADAPTIVE_MIN_RMS,
+                max_rms: ADAPTIVE_MAX_RMS,
+            },
+            ..SilenceSuppressionConfig::for_microphone()
+        })
+    }
+
+    /// 20ms frames of uniform noise at `level` peak, with `burst_level`
+    /// syllables (200ms every 600ms) from 2s on when given
+    fn noisy_frames(level: f32, burst_level: Option<f32>) -> Vec<Vec<i16>> {
+        let mut rng = crate::test_fixtures::Noise::new(7);
+        (0..250)
+            .map(|i| {
+                let burst = burst_level.filter(|_| i >= 100 && i % 30 < 10);
+                (0..320)
+                    .map(|n| {
+                        let tone = burst.map_or(0.0, |b| b * (n as f32 * 0.2).sin());
+                        (level * rng.next() + tone) as i16
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_adaptive_threshold_closes_gate_in_noisy_room() {
+        // Cafe background around RMS 350: always above the fixed threshold
+        let frames = noisy_frames(600.0, None);
+        let sent = |mut suppressor: SilenceSuppressor| {
+            frames.iter().enumerate()
+                .filter(|(i, f)| {
+                    let action = suppressor.process(f);
+                    *i >= 100 && matches!(action, FrameAction::Send(_) | FrameAction::Hangover(_))
+                })
+                .count()
+        };
+        assert_eq!(sent(SilenceSuppressor::new(SilenceSuppressionConfig::for_microphone())), 150);
+        assert_eq!(sent(adaptive_suppressor()), 0);
+
+        let mut suppressor = adaptive_suppressor();
+        frames.iter().for_each(|f| { suppressor.process(f); });
+        assert!((250.0..450.0).contains(&suppressor.noise_floor_rms()), "floor {}", suppressor.noise_floor_rms());
+        assert!(suppressor.threshold_rms() > 2.5 * suppressor.noise_floor_rms());
+    }
+
+    #[test]
+    fn test_adaptive_threshold_keeps_soft_syllables() {
+        // Quiet condenser mic: floor around RMS 3, syllables around RMS 50
+        let frames = noisy_frames(5.0, Some(70.0));
+        let speech = |mut suppressor: SilenceSuppressor| {
+            frames.iter().enumerate()
+                .filter(|(i, f)| {
+                    let action = suppressor.process(f);
+                    *i >= 100 && matches!(action, FrameAction::Send(_) | FrameAction::SendWithPreroll { .. })
+                })
+                .count()
+        };
+        assert_eq!(speech(SilenceSuppressor::new(SilenceSuppressionConfig::for_microphone())), 0);
+        // 5 syllables of 10 frames
+        assert!(speech(adaptive_suppressor()) >= 48);
+    }
+
+    #[test]
+    fn test_preroll_flushed_at_onset() {
+        let config = SilenceSuppressionConfig {
+            speech_hangover: Duration::ZERO,
+            preroll: Duration::from_millis(110),
+            ..SilenceSuppressionConfig::default()
+        };
+        let mut suppressor = SilenceSuppressor::new(config);
+
+        // Quiet frames 0..=8 (keepalive at frame 4), then speech
+        let quiet = |n: i16| vec![n; 320];
+        let actions: Vec<FrameAction> = (0..9).map(|n| suppressor.process(&quiet(n))).collect();
+        assert!(matches!(actions[4], FrameAction::SendSilence));
+
+        // 110ms of 20ms frames rounds up to 6 frames, but the keepalive took
+        // frame 4's slot on the wire: only the frames after it are flushed
+        match suppressor.process(&vec![1000; 320]) {
+            FrameAction::SendWithPreroll { preroll, frame } => {
+                let firsts: Vec<i16> = preroll.iter().map(|f| f[0]).collect();
+                assert_eq!(firsts, [5, 6, 7, 8]);
+                assert_eq!(frame[0], 1000);
+            }
+            other => panic!("expected pre-roll, got {:?}", other),
+        }
+        // Sent: keepalive, 4 pre-roll frames, speech; frames 0..=3 stay suppressed
+        assert_eq!(suppressor.stats(), (6, 4));
+
+        // A keepalive right before an onset leaves nothing to flush
+        assert!(matches!(suppressor.process(&quiet(9)), FrameAction::SendSilence));
+        assert!(matches!(suppressor.process(&vec![1000; 320]), FrameAction::Send(_)));
+
+        // Speech clears the buffer: the next onset flushes only later frames
+        assert!(matches!(suppressor.process(&quiet(10)), FrameAction::Suppress));
+        match suppressor.process(&vec![1000; 320]) {
+            FrameAction::SendWithPreroll { preroll, .. } => assert_eq!(preroll, [quiet(10)]),
+            other => panic!("expected pre-roll, got {:?}", other),
+        }
+    }
 }
@@ -2,19 +2,17 @@ use anyhow::Result;
 use cidre::{arc, av, cat, cf, core_audio as ca, ns, os};
 use ringbuf::{traits::{Producer, Split}, HeapProd, HeapRb, HeapCons};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::{Arc, Mutex};
-use std::task::{Waker};
+use std::sync::Arc;
 use ca::aggregate_device_keys as agg_keys;
 
-struct WakerState {
-    waker: Option<Waker>,
-    has_data: bool,
-}
+use crate::stats::OverflowCounter;
+use crate::wakeup::DataNotifier;
 
 struct Ctx {
     format: arc::R<av::AudioFormat>,
     producer: HeapProd<f32>,
-    waker_state: Arc<Mutex<WakerState>>,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
     current_sample_rate: Arc<AtomicU32>,
     consecutive_drops: Arc<AtomicU32>,
     should_terminate: Arc<AtomicBool>,
@@ -153,17 +151,16 @@ impl SpeakerInput {
         let rb = HeapRb::<f32>::new(buffer_size);
         let (producer, consumer) = rb.split();
 
-        let waker_state = Arc::new(Mutex::new(WakerState {
-            waker: None,
-            has_data: false,
-        }));
+        let notifier = DataNotifier::new();
+        let overflow = OverflowCounter::new();
 
         let current_sample_rate = Arc::new(AtomicU32::new(asbd.sample_rate as u32));
 
         let mut ctx = Box::new(Ctx {
             format,
             producer,
-            waker_state: waker_state.clone(),
+            notifier: notifier.clone(),
+            overflow: overflow.clone(),
             current_sample_rate: current_sample_rate.clone(),
             consecutive_drops: Arc::new(AtomicU32::new(0)),
             should_terminate: Arc::new(AtomicBool::new(false)),
@@ -178,11 +175,17 @@ impl SpeakerInput {
             _ctx: ctx,
             _tap: self.tap,
             current_sample_rate,
+            notifier,
+            overflow,
         }
     }
 }
 
 fn process_audio_data(ctx: &mut Ctx, data: &[f32]) {
+    if ctx.should_terminate.load(Ordering::Acquire) {
+        return;
+    }
+
     // Debug Logging for signal analysis
     static mut LOG_COUNTER: usize = 0;
     unsafe {
@@ -204,6 +207,7 @@ fn process_audio_data(ctx: &mut Ctx, data: &[f32]) {
     // Processing Logic
     let buffer_size = data.len();
     let pushed = ctx.producer.push_slice(data);
+    ctx.overflow.record(buffer_size, pushed);
 
     if pushed < buffer_size {
         let consecutive = ctx.consecutive_drops.fetch_add(1, Ordering::AcqRel) + 1;
@@ -211,27 +215,19 @@ fn process_audio_data(ctx: &mut Ctx, data: &[f32]) {
             eprintln!("Warning: Audio buffer experiencing drops - system may be overloaded");
         }
         if consecutive > 50 {
+            // Stop pushing; the DSP thread reports the failure (no locks here)
             eprintln!("Critical: Audio buffer overflow - capture stopping");
             ctx.should_terminate.store(true, Ordering::Release);
+            ctx.overflow.abandon();
+            ctx.notifier.notify();
             return;
         }
     } else {
         ctx.consecutive_drops.store(0, Ordering::Release);
     }
 
-    let should_wake = {
-        let mut waker_state = ctx.waker_state.lock().unwrap();
-        if !waker_state.has_data {
-            waker_state.has_data = true;
-            waker_state.waker.take()
-        } else {
-            None
-        }
-    };
-
-    if let Some(waker) = should_wake {
-        waker.wake();
-    }
+    // Lock-free wakeup of the DSP thread
+    ctx.notifier.notify();
 }
 
 pub struct SpeakerStream {
@@ -240,6 +236,8 @@ pub struct SpeakerStream {
     _ctx: Box<Ctx>,
     _tap: ca::TapGuard,
     current_sample_rate: Arc<AtomicU32>,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
 }
 
 impl SpeakerStream {
@@ -250,6 +248,14 @@ impl SpeakerStream {
     pub fn take_consumer(&mut self) -> Option<HeapCons<f32>> {
         self.consumer.take()
     }
+
+    pub fn data_notifier(&self) -> DataNotifier {
+        self.notifier.clone()
+    }
+
+    pub fn overflow_counter(&self) -> OverflowCounter {
+        self.overflow.clone()
+    }
 }
 
 
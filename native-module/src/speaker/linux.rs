@@ -0,0 +1,432 @@
+// PulseAudio / PipeWire system audio capture
+//
+// Records the monitor source of an output sink. The monitor carries exactly
+// what the sink plays, i.e. the other side of a call.
+//
+// We drive `parec` instead of linking libpulse:
+// - Works on PulseAudio and on PipeWire (through pipewire-pulse)
+// - No native build dependency for the module
+// - The server does format/rate conversion, we always read f32 mono
+//
+// Architecture mirrors the other backends:
+// 1. Reader thread: reads raw f32 from parec stdout, pushes to ring buffer
+// 2. DSP thread (pipeline.rs): drains the consumer
+
+use anyhow::Result;
+use ringbuf::{traits::{Producer, Split}, HeapCons, HeapProd, HeapRb};
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::audio_config::RING_BUFFER_SAMPLES;
+use crate::audio_source::AudioSource;
+use crate::capture_status::{StatusCode, StatusReporter};
+use crate::device_watch::DeviceDescription;
+use crate::stats::OverflowCounter;
+use crate::wakeup::DataNotifier;
+
+/// Rate requested from the server (it resamples the monitor for us)
+const CAPTURE_SAMPLE_RATE: u32 = 48000;
+
+/// parec latency hint - small reads keep DSP latency low
+const CAPTURE_LATENCY_MS: u32 = 10;
+
+/// Bytes read from parec per iteration (10ms of f32 mono at 48kHz)
+const READ_CHUNK_BYTES: usize = 480 * 4;
+
+/// List sinks as (sink name, description)
+///
+/// The sink name is the id accepted by `SpeakerInput::new`.
+pub fn list_output_devices() -> Result<Vec<(String, String)>> {
+    let output = pactl(&["list", "sinks"])?;
+    Ok(parse_sinks(&output).into_iter().map(|sink| (sink.id, sink.name)).collect())
+}
+
+/// Sinks with their sample spec (the server converts to whatever we request)
+pub fn describe_output_devices() -> Result<Vec<DeviceDescription>> {
+    let default = default_sink().ok();
+    let mut sinks = parse_sinks(&pactl(&["list", "sinks"])?);
+    for sink in &mut sinks {
+        sink.is_default = default.as_ref() == Some(&sink.id);
+    }
+    Ok(sinks)
+}
+
+/// Name of the default sink (None if the server does not answer)
+pub fn default_output_device() -> Option<String> {
+    default_sink().ok()
+}
+
+pub struct SpeakerInput {
+    monitor_source: String,
+    status: StatusReporter,
+}
+
+impl SpeakerInput {
+    /// Resolve the monitor source for `device_id` (a sink name, a monitor
+    /// source name, or None/"default" for the default sink).
+    /// `status` receives failures of a running parec
+    pub fn new(device_id: Option<String>, status: StatusReporter) -> Result<Self> {
+        if !parec_available() {
+            return Err(anyhow::anyhow!(
+                "parec not found (install pulseaudio-utils; PipeWire needs pipewire-pulse)"
+            ));
+        }
+
+        let monitor_source = match device_id {
+            Some(ref id) if id.ends_with(".monitor") => id.clone(),
+            Some(ref id) if !id.is_empty() && id != "default" => {
+                let sinks = list_output_devices()?;
+                if !sinks.iter().any(|(name, _)| name == id) {
+                    return Err(anyhow::anyhow!("Output device not found: {}", id));
+                }
+                format!("{}.monitor", id)
+            }
+            _ => format!("{}.monitor", default_sink()?),
+        };
+
+        println!("[PulseMonitor] Monitor source: {}", monitor_source);
+        Ok(Self { monitor_source, status })
+    }
+
+    /// Spawn parec; fails if it cannot be started
+    pub fn stream(self) -> Result<SpeakerStream> {
+        let rb = HeapRb::<f32>::new(RING_BUFFER_SAMPLES);
+        let (producer, consumer) = rb.split();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let notifier = DataNotifier::new();
+        let overflow = OverflowCounter::new();
+
+        let mut child = Command::new("parec")
+            .arg(format!("--device={}", self.monitor_source))
+            .arg("--format=float32le")
+            .arg(format!("--rate={}", CAPTURE_SAMPLE_RATE))
+            .arg("--channels=1")
+            .arg("--raw")
+            .arg(format!("--latency-msec={}", CAPTURE_LATENCY_MS))
+            .arg("--client-name=rustyn")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to spawn parec: {}", e))?;
+
+        let Some(stdout) = child.stdout.take() else {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow::anyhow!("parec started without an output pipe"));
+        };
+        let shutdown_clone = shutdown.clone();
+        let notifier_clone = notifier.clone();
+        let overflow_clone = overflow.clone();
+        let status = self.status.clone();
+        let reader_thread = thread::spawn(move || {
+            read_loop(stdout, producer, notifier_clone, overflow_clone, status, shutdown_clone)
+        });
+        println!("[PulseMonitor] Recording {} at {}Hz", self.monitor_source, CAPTURE_SAMPLE_RATE);
+
+        Ok(SpeakerStream {
+            consumer: Some(consumer),
+            child: Some(child),
+            reader_thread: Some(reader_thread),
+            shutdown,
+            notifier,
+            overflow,
+        })
+    }
+}
+
+/// Read raw f32le from parec and push to the ring buffer
+fn read_loop(
+    mut stdout: impl Read,
+    mut producer: HeapProd<f32>,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
+    status: StatusReporter,
+    shutdown: Arc<AtomicBool>,
+) {
+    let mut bytes = [0u8; READ_CHUNK_BYTES];
+    let mut samples = [0f32; READ_CHUNK_BYTES / 4];
+    // Bytes carried over when a read ends mid-sample
+    let mut pending = 0usize;
+
+    while !shutdown.load(Ordering::Relaxed) {
+        let n = match stdout.read(&mut bytes[pending..]) {
+            Ok(0) => {
+                // parec exited: killed on drop, or the monitor source went away
+                if !shutdown.load(Ordering::Relaxed) {
+                    status.fail(StatusCode::DeviceLost, "parec exited (monitor source gone?)");
+                }
+                break;
+            }
+            Ok(n) => n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                eprintln!("[PulseMonitor] Read error: {}", e);
+                status.fail(StatusCode::StreamError, format!("Reading from parec failed: {}", e));
+                break;
+            }
+        };
+
+        let available = pending + n;
+        let count = available / 4;
+        for (i, sample) in samples.iter_mut().take(count).enumerate() {
+            let b = &bytes[i * 4..i * 4 + 4];
+            *sample = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        }
+        let pushed = producer.push_slice(&samples[..count]);
+        overflow.record(count, pushed);
+        notifier.notify();
+
+        pending = available % 4;
+        bytes.copy_within(count * 4..available, 0);
+    }
+
+    println!("[PulseMonitor] Reader thread stopped.");
+}
+
+pub struct SpeakerStream {
+    consumer: Option<HeapCons<f32>>,
+    child: Option<Child>,
+    reader_thread: Option<thread::JoinHandle<()>>,
+    shutdown: Arc<AtomicBool>,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
+}
+
+impl SpeakerStream {
+    pub fn sample_rate(&self) -> u32 {
+        CAPTURE_SAMPLE_RATE
+    }
+
+    pub fn take_consumer(&mut self) -> Option<HeapCons<f32>> {
+        self.consumer.take()
+    }
+}
+
+/// parec runs from `stream()` until drop, so start/stop are no-ops
+impl AudioSource for SpeakerStream {
+    fn sample_rate(&self) -> u32 {
+        CAPTURE_SAMPLE_RATE
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn take_consumer(&mut self) -> Option<HeapCons<f32>> {
+        self.consumer.take()
+    }
+
+    fn data_notifier(&self) -> DataNotifier {
+        self.notifier.clone()
+    }
+
+    fn overflow_counter(&self) -> OverflowCounter {
+        self.overflow.clone()
+    }
+
+    fn start(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SpeakerStream {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Killing parec closes its stdout, which unblocks the reader
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        if let Some(handle) = self.reader_thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn parec_available() -> bool {
+    Command::new("parec")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+fn pactl(args: &[&str]) -> Result<String> {
+    let output = Command::new("pactl")
+        .args(args)
+        .env("LC_ALL", "C")
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to run pactl: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "pactl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Name of the default sink
+fn default_sink() -> Result<String> {
+    // `get-default-sink` needs pactl >= 15, `info` works everywhere
+    if let Ok(out) = pactl(&["get-default-sink"]) {
+        let name = out.trim();
+        if !name.is_empty() {
+            return Ok(name.to_string());
+        }
+    }
+    let info = pactl(&["info"])?;
+    parse_default_sink(&info).ok_or_else(|| anyhow::anyhow!("No default sink"))
+}
+
+fn parse_default_sink(info: &str) -> Option<String> {
+    info.lines()
+        .find_map(|line| line.trim().strip_prefix("Default Sink:"))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Parse `pactl list sinks` (id: sink name, name: description)
+fn parse_sinks(output: &str) -> Vec<DeviceDescription> {
+    let mut list = Vec::new();
+    let mut sink: Option<DeviceDescription> = None;
+
+    for line in output.lines() {
+        let line = line.trim();
+        if line.starts_with("Sink #") {
+            list.extend(sink.take());
+            sink = Some(DeviceDescription {
+                id: String::new(),
+                name: String::new(),
+                is_default: false,
+                sample_rates: Vec::new(),
+                channels: 0,
+                sample_formats: Vec::new(),
+                host_api: "PulseAudio".to_string(),
+            });
+        } else if let Some(sink) = sink.as_mut() {
+            if let Some(n) = line.strip_prefix("Name:") {
+                sink.id = n.trim().to_string();
+            } else if let Some(desc) = line.strip_prefix("Description:") {
+                sink.name = desc.trim().to_string();
+            } else if let Some(spec) = line.strip_prefix("Sample Specification:") {
+                parse_sample_spec(spec, sink);
+            }
+        }
+    }
+    list.extend(sink);
+    list.retain(|sink| !sink.id.is_empty());
+    list
+}
+
+/// "float32le 2ch 48000Hz"
+fn parse_sample_spec(spec: &str, sink: &mut DeviceDescription) {
+    for part in spec.split_whitespace() {
+        if let Some(channels) = part.strip_suffix("ch").and_then(|c| c.parse().ok()) {
+            sink.channels = channels;
+        } else if let Some(rate) = part.strip_suffix("Hz").and_then(|r| r.parse().ok()) {
+            sink.sample_rates = vec![rate];
+        } else {
+            let format = match part {
+                "float32le" | "float32be" => "f32",
+                "s16le" | "s16be" => "i16",
+                "s24le" | "s24be" | "s24-32le" | "s24-32be" => "i24",
+                "s32le" | "s32be" => "i32",
+                other => other,
+            };
+            sink.sample_formats = vec![format.to_string()];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACTL_SINKS: &str = "Sink #0
+\tState: SUSPENDED
+\tName: alsa_output.pci-0000_00_1f.3.analog-stereo
+\tDescription: Built-in Audio Analog Stereo
+\tDriver: PipeWire
+\tSample Specification: float32le 2ch 48000Hz
+
+Sink #57
+\tState: RUNNING
+\tName: rustyn_null
+\tDescription: Null Output
+\tDriver: module-null-sink.c
+";
+
+    #[test]
+    fn test_parse_sinks() {
+        let sinks = parse_sinks(PACTL_SINKS);
+        assert_eq!(sinks.len(), 2);
+        assert_eq!(sinks[0].id, "alsa_output.pci-0000_00_1f.3.analog-stereo");
+        assert_eq!((sinks[0].channels, sinks[0].sample_rates.as_slice()), (2, [48000].as_slice()));
+        assert_eq!(sinks[0].sample_formats, ["f32"]);
+        assert_eq!((sinks[1].id.as_str(), sinks[1].name.as_str()), ("rustyn_null", "Null Output"));
+    }
+
+    #[test]
+    fn test_parse_default_sink() {
+        let info = "Server Name: PulseAudio (on PipeWire 1.0.5)\nDefault Sink: rustyn_null\nDefault Source: mic\n";
+        assert_eq!(parse_default_sink(info).as_deref(), Some("rustyn_null"));
+        assert_eq!(parse_default_sink("Server Name: x\n"), None);
+    }
+
+    /// Needs a running PulseAudio/PipeWire server:
+    /// `cargo test -- --ignored null_sink`
+    #[test]
+    #[ignore]
+    fn test_null_sink_capture() {
+        use ringbuf::traits::Consumer;
+        use std::io::Write;
+        use std::time::{Duration, Instant};
+
+        let module = pactl(&["load-module", "module-null-sink", "sink_name=rustyn_test"])
+            .expect("load null sink");
+
+        let mut stream = SpeakerInput::new(Some("rustyn_test".to_string()), StatusReporter::new())
+            .expect("open monitor")
+            .stream()
+            .expect("spawn parec");
+        let mut consumer = stream.take_consumer().unwrap();
+
+        // Play a 440Hz tone into the null sink
+        let mut player = Command::new("pacat")
+            .args(["--device=rustyn_test", "--format=float32le", "--rate=48000", "--channels=1", "--raw"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .expect("spawn pacat");
+        let tone: Vec<u8> = (0..48000)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin())
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        player.stdin.take().unwrap().write_all(&tone).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(3);
+        let mut peak = 0.0f32;
+        while Instant::now() < deadline && peak < 0.1 {
+            while let Some(s) = consumer.try_pop() {
+                peak = peak.max(s.abs());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = player.wait();
+        drop(stream);
+        let _ = pactl(&["unload-module", module.trim()]);
+        assert!(peak >= 0.1, "monitor source delivered no signal (peak {})", peak);
+    }
+}
@@ -1,12 +1,17 @@
 use anyhow::Result;
 use ringbuf::HeapCons;
+use crate::audio_source::AudioSource;
+use crate::capture_status::{StatusCode, StatusReporter};
+use crate::stats::OverflowCounter;
+use crate::wakeup::DataNotifier;
 use super::core_audio;
 use super::sck;
 
-pub use super::sck::list_output_devices;
+pub use super::sck::{default_output_device, describe_output_devices, list_output_devices};
 
 pub struct SpeakerInput {
     backend: BackendInput,
+    status: StatusReporter,
 }
 
 enum BackendInput {
@@ -15,7 +20,8 @@ enum BackendInput {
 }
 
 impl SpeakerInput {
-    pub fn new(device_id: Option<String>) -> Result<Self> {
+    /// `status` receives the backend fallback and stream errors
+    pub fn new(device_id: Option<String>, status: StatusReporter) -> Result<Self> {
         let force_sck = device_id.as_deref() == Some("sck");
         
         if !force_sck {
@@ -24,10 +30,14 @@ impl SpeakerInput {
             match core_audio::SpeakerInput::new(device_id.clone()) {
                 Ok(input) => {
                      println!("[SpeakerInput] CoreAudio Tap backend initialized.");
-                     return Ok(Self { backend: BackendInput::CoreAudio(input) });
+                     return Ok(Self { backend: BackendInput::CoreAudio(input), status });
                 },
                 Err(e) => {
                     println!("[SpeakerInput] CoreAudio Tap initialization failed: {}. Falling back to ScreenCaptureKit.", e);
+                    status.report(
+                        StatusCode::BackendFallback,
+                        format!("CoreAudio tap failed ({}), using ScreenCaptureKit", e),
+                    );
                 }
             }
         } else {
@@ -36,7 +46,7 @@ impl SpeakerInput {
         
         // Fallback to ScreenCaptureKit
         let input = sck::SpeakerInput::new(device_id)?;
-        Ok(Self { backend: BackendInput::Sck(input) })
+        Ok(Self { backend: BackendInput::Sck(input), status })
     }
     
     pub fn stream(self) -> SpeakerStream {
@@ -53,7 +63,7 @@ impl SpeakerInput {
                 SpeakerStream { backend: BackendStream::CoreAudio(stream) }
             },
             BackendInput::Sck(input) => {
-                let stream = input.stream();
+                let stream = input.stream(self.status);
                 SpeakerStream { backend: BackendStream::Sck(stream) }
             }
         }
@@ -83,6 +93,53 @@ impl SpeakerStream {
              BackendStream::Sck(s) => s.take_consumer(),
         }
     }
+
+    pub fn data_notifier(&self) -> DataNotifier {
+        match &self.backend {
+             BackendStream::CoreAudio(s) => s.data_notifier(),
+             BackendStream::Sck(s) => s.data_notifier(),
+        }
+    }
+
+    pub fn overflow_counter(&self) -> OverflowCounter {
+        match &self.backend {
+             BackendStream::CoreAudio(s) => s.overflow_counter(),
+             BackendStream::Sck(s) => s.overflow_counter(),
+        }
+    }
 }
 
 
+
+/// Both backends start capturing in `stream()` and stop on drop,
+/// so start/stop are no-ops
+impl AudioSource for SpeakerStream {
+    fn sample_rate(&self) -> u32 {
+        SpeakerStream::sample_rate(self)
+    }
+
+    /// Both taps are configured mono
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn take_consumer(&mut self) -> Option<HeapCons<f32>> {
+        SpeakerStream::take_consumer(self)
+    }
+
+    fn data_notifier(&self) -> DataNotifier {
+        SpeakerStream::data_notifier(self)
+    }
+
+    fn overflow_counter(&self) -> OverflowCounter {
+        SpeakerStream::overflow_counter(self)
+    }
+
+    fn start(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,5 +1,11 @@
 // removed unused anyhow::Result
 
+use crate::audio_source::AudioSource;
+use crate::capture_status::StatusReporter;
+use crate::device_watch::{DeviceCache, DeviceDescription, DeviceHost, SharedDeviceHost, DEVICE_POLL_INTERVAL};
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+
 #[cfg(target_os = "macos")]
 mod core_audio;
 #[cfg(target_os = "macos")]
@@ -14,28 +20,108 @@ pub use macos::SpeakerInput;
 pub use macos::SpeakerStream;
 #[cfg(target_os = "macos")]
 pub use macos::list_output_devices;
+#[cfg(target_os = "macos")]
+pub use macos::{default_output_device, describe_output_devices};
 
 #[cfg(target_os = "windows")]
 pub mod windows;
 #[cfg(target_os = "windows")]
 pub use windows::SpeakerInput;
 #[cfg(target_os = "windows")]
+pub use windows::SpeakerStream;
+#[cfg(target_os = "windows")]
 pub use windows::list_output_devices;
+#[cfg(target_os = "windows")]
+pub use windows::{default_output_device, describe_output_devices};
 
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+#[cfg(target_os = "linux")]
+pub mod linux;
+#[cfg(target_os = "linux")]
+pub use linux::SpeakerInput;
+#[cfg(target_os = "linux")]
+pub use linux::SpeakerStream;
+#[cfg(target_os = "linux")]
+pub use linux::list_output_devices;
+#[cfg(target_os = "linux")]
+pub use linux::{default_output_device, describe_output_devices};
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
 pub mod fallback {
     use anyhow::Result;
     pub struct SpeakerInput;
     impl SpeakerInput {
-        pub fn new(_device_id: Option<String>) -> Result<Self> {
+        pub fn new(_device_id: Option<String>, _status: crate::capture_status::StatusReporter) -> Result<Self> {
             Err(anyhow::anyhow!("Unsupported platform"))
         }
     }
     pub fn list_output_devices() -> Result<Vec<(String, String)>> {
         Ok(Vec::new())
     }
+    pub fn default_output_device() -> Option<String> {
+        None
+    }
+    pub fn describe_output_devices() -> Result<Vec<crate::device_watch::DeviceDescription>> {
+        Ok(Vec::new())
+    }
 }
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
 pub use fallback::SpeakerInput;
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
-pub use fallback::list_output_devices;
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+pub use fallback::{default_output_device, describe_output_devices, list_output_devices};
+
+/// Output device snapshot shared by every capture and device monitor
+static OUTPUT_DEVICES: Lazy<Arc<DeviceCache>> = Lazy::new(|| Arc::new(DeviceCache::new(DEVICE_POLL_INTERVAL)));
+
+/// Output devices of the platform backend, for DeviceSupervisor
+///
+/// Device ids are the ids of `list_output_devices`.
+pub struct OutputDeviceHost;
+
+impl OutputDeviceHost {
+    /// The output host, enumerated at most once per poll interval process-wide
+    pub fn shared() -> SharedDeviceHost<Self> {
+        SharedDeviceHost::new(Self, OUTPUT_DEVICES.clone())
+    }
+}
+
+impl DeviceHost for OutputDeviceHost {
+    fn default_device(&self) -> Option<String> {
+        default_output_device()
+    }
+
+    fn devices(&self) -> anyhow::Result<Vec<String>> {
+        let mut ids: Vec<String> = list_output_devices()?.into_iter().map(|(id, _)| id).collect();
+        if cfg!(target_os = "macos") {
+            // Pseudo device forcing the ScreenCaptureKit backend
+            ids.push("sck".to_string());
+        }
+        Ok(ids)
+    }
+
+    fn describe(&self) -> anyhow::Result<Vec<DeviceDescription>> {
+        describe_output_devices()
+    }
+
+    /// `SpeakerInput` takes a sink's monitor source for the sink itself
+    #[cfg(target_os = "linux")]
+    fn canonical_id(&self, id: &str) -> String {
+        id.strip_suffix(".monitor").unwrap_or(id).to_string()
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+    fn open(&mut self, device: &str, status: &StatusReporter) -> anyhow::Result<Box<dyn AudioSource>> {
+        let input = SpeakerInput::new(Some(device.to_string()), status.clone())?;
+        // WASAPI only knows whether the loopback works once its thread is
+        // up, parec whether it could be spawned
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        let stream = input.stream()?;
+        #[cfg(target_os = "macos")]
+        let stream = input.stream();
+        Ok(Box::new(stream))
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    fn open(&mut self, _device: &str, _status: &StatusReporter) -> anyhow::Result<Box<dyn AudioSource>> {
+        Err(anyhow::anyhow!("Unsupported platform"))
+    }
+}
@@ -9,6 +9,43 @@ use ringbuf::{traits::{Producer, Split}, HeapProd, HeapRb, HeapCons};
 // keep for compatibility
 use cidre::core_audio as ca;
 
+use crate::capture_status::{StatusCode, StatusReporter};
+use crate::device_watch::DeviceDescription;
+use crate::stats::OverflowCounter;
+use crate::wakeup::DataNotifier;
+
+/// UID of the default output device
+pub fn default_output_device() -> Option<String> {
+    let device = ca::System::default_output_device().ok()?;
+    device.uid().ok().map(|uid| uid.to_string())
+}
+
+/// Output devices with their current format (the HAL works in f32)
+pub fn describe_output_devices() -> Result<Vec<DeviceDescription>> {
+    let default = default_output_device();
+    let mut list = Vec::new();
+    for device in ca::System::devices()? {
+        let Ok(cfg) = device.output_stream_cfg() else { continue };
+        if cfg.number_buffers() == 0 {
+            continue;
+        }
+        let uid = device.uid().map(|u| u.to_string()).unwrap_or_default();
+        if uid.is_empty() {
+            continue;
+        }
+        list.push(DeviceDescription {
+            is_default: default.as_ref() == Some(&uid),
+            name: device.name().map(|n| n.to_string()).unwrap_or_default(),
+            id: uid,
+            sample_rates: device.actual_sample_rate().ok().map(|rate| rate as u32).into_iter().collect(),
+            channels: cfg.list().buffers[0].number_channels as u16,
+            sample_formats: vec!["f32".to_string()],
+            host_api: "CoreAudio".to_string(),
+        });
+    }
+    Ok(list)
+}
+
 pub fn list_output_devices() -> Result<Vec<(String, String)>> {
     let all_devices = ca::System::devices()?;
     let mut list = Vec::new();
@@ -28,6 +65,8 @@ pub fn list_output_devices() -> Result<Vec<(String, String)>> {
 
 pub struct AudioHandlerInner {
     producer: HeapProd<f32>,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
 }
 
 define_obj_type!(
@@ -73,10 +112,12 @@ impl sc::stream::OutputImpl for AudioHandler {
                         unsafe {
                             let slice = std::slice::from_raw_parts(data_ptr, float_count);
                             // Push audio to ring buffer
-                            let _pushed = inner.producer.push_slice(slice);
+                            let pushed = inner.producer.push_slice(slice);
+                            inner.overflow.record(float_count, pushed);
                         }
                     }
                 }
+                inner.notifier.notify();
             }
             Err(e) => {
                 println!("[SystemAudio-SCK] Failed to get audio buffer: {:?}", e);
@@ -171,7 +212,7 @@ impl SpeakerInput {
         self.cfg.sample_rate() as f64
     }
 
-    pub fn stream(self) -> SpeakerStream {
+    pub fn stream(self, status: StatusReporter) -> SpeakerStream {
         let buffer_size = 1024 * 128;
         let rb = HeapRb::<f32>::new(buffer_size);
         let (producer, consumer) = rb.split();
@@ -179,7 +220,9 @@ impl SpeakerInput {
         let stream = sc::Stream::new(&self.filter, &self.cfg);
         
         // Initialize handler
-        let inner = AudioHandlerInner { producer };
+        let notifier = DataNotifier::new();
+        let overflow = OverflowCounter::new();
+        let inner = AudioHandlerInner { producer, notifier: notifier.clone(), overflow: overflow.clone() };
         let handler = AudioHandler::with(inner);
         
         let queue = dispatch::Queue::serial_with_ar_pool();
@@ -203,6 +246,10 @@ impl SpeakerInput {
             if let Some(e) = err {
                 println!("[SpeakerInput] ERROR: Stream start FAILED: {:?}", e);
                 println!("[SpeakerInput] Check Screen Recording permission in System Settings!");
+                status.fail(
+                    StatusCode::PermissionDenied,
+                    format!("ScreenCaptureKit stream failed to start (Screen Recording permission?): {:?}", e),
+                );
                 error_clone.store(2, Ordering::SeqCst);
             } else {
                 println!("[SpeakerInput] ✅ Stream started successfully!");
@@ -232,6 +279,8 @@ impl SpeakerInput {
             _handler: handler,
             _filter: self.filter,
             _cfg: self.cfg,
+            notifier,
+            overflow,
         }
     }
 }
@@ -242,6 +291,8 @@ pub struct SpeakerStream {
     _handler: arc::R<AudioHandler>,
     _filter: arc::R<sc::ContentFilter>,
     _cfg: arc::R<sc::StreamCfg>,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
 }
 
 impl SpeakerStream {
@@ -252,6 +303,14 @@ impl SpeakerStream {
     pub fn take_consumer(&mut self) -> Option<HeapCons<f32>> {
         self.consumer.take()
     }
+
+    pub fn data_notifier(&self) -> DataNotifier {
+        self.notifier.clone()
+    }
+
+    pub fn overflow_counter(&self) -> OverflowCounter {
+        self.overflow.clone()
+    }
 }
 
 impl Drop for SpeakerStream {
@@ -1,12 +1,19 @@
 // Ported logic
 use anyhow::Result;
+use ringbuf::{traits::{Producer, Split}, HeapCons, HeapProd, HeapRb};
 use std::collections::VecDeque;
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::time::Duration;
-use tracing::error;
 use wasapi::{get_default_device, DeviceCollection, Direction, SampleType, StreamMode, WaveFormat};
 
+use crate::audio_config::RING_BUFFER_SAMPLES;
+use crate::audio_source::AudioSource;
+use crate::capture_status::{StatusCode, StatusReporter};
+use crate::device_watch::DeviceDescription;
+use crate::stats::OverflowCounter;
+use crate::wakeup::DataNotifier;
+
 struct WakerState {
     // waker: Option<Waker>, // Not used in NAPI context directly same way
     shutdown: bool,
@@ -14,31 +21,57 @@ struct WakerState {
 
 pub struct SpeakerInput {
     device_id: Option<String>,
+    status: StatusReporter,
 }
 
 pub struct SpeakerStream {
-    sample_queue: Arc<Mutex<VecDeque<f32>>>,
+    consumer: Option<HeapCons<f32>>,
     waker_state: Arc<Mutex<WakerState>>,
     capture_thread: Option<thread::JoinHandle<()>>,
     actual_sample_rate: u32,
+    notifier: DataNotifier,
+    overflow: OverflowCounter,
 }
 
 impl SpeakerStream {
     pub fn sample_rate(&self) -> u32 {
         self.actual_sample_rate
     }
-    
-    // Read available samples
-    pub fn read_chunk(&mut self, max_samples: usize) -> Vec<f32> {
-        let mut queue = self.sample_queue.lock().unwrap();
-        let count = std::cmp::min(queue.len(), max_samples);
-        let mut samples = Vec::with_capacity(count);
-        for _ in 0..count {
-            if let Some(s) = queue.pop_front() {
-                samples.push(s);
-            }
-        }
-        samples
+
+    pub fn take_consumer(&mut self) -> Option<HeapCons<f32>> {
+        self.consumer.take()
+    }
+}
+
+/// The loopback client runs from `stream()` until drop, so start/stop are no-ops
+impl AudioSource for SpeakerStream {
+    fn sample_rate(&self) -> u32 {
+        self.actual_sample_rate
+    }
+
+    /// The client is initialized mono (autoconvert)
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn take_consumer(&mut self) -> Option<HeapCons<f32>> {
+        self.consumer.take()
+    }
+
+    fn data_notifier(&self) -> DataNotifier {
+        self.notifier.clone()
+    }
+
+    fn overflow_counter(&self) -> OverflowCounter {
+        self.overflow.clone()
+    }
+
+    fn start(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        Ok(())
     }
 }
 
@@ -76,51 +109,104 @@ pub fn list_output_devices() -> Result<Vec<(String, String)>> {
     Ok(list)
 }
 
+/// Id of the default render device
+pub fn default_output_device() -> Option<String> {
+    get_default_device(&Direction::Render).ok().and_then(|device| device.get_id().ok())
+}
+
+/// Render devices with their shared-mode mix format (the only format loopback runs at)
+pub fn describe_output_devices() -> Result<Vec<DeviceDescription>> {
+    let default = default_output_device();
+    let collection = DeviceCollection::new(&Direction::Render)?;
+    let count = collection.get_nbr_devices()?;
+    let mut list = Vec::new();
+
+    for i in 0..count {
+        let Ok(device) = collection.get_device_at_index(i) else { continue };
+        let id = device.get_id().unwrap_or_default();
+        if id.is_empty() {
+            continue;
+        }
+        let mix_format = device.get_iaudioclient().and_then(|client| client.get_mixformat()).ok();
+        let sample_format = |format: &WaveFormat| match format.get_subformat() {
+            Ok(SampleType::Float) => format!("f{}", format.get_bitspersample()),
+            _ => format!("i{}", format.get_validbitspersample()),
+        };
+        list.push(DeviceDescription {
+            is_default: default.as_ref() == Some(&id),
+            name: device.get_friendlyname().unwrap_or_default(),
+            id,
+            sample_rates: mix_format.iter().map(|f| f.get_samplespersec()).collect(),
+            channels: mix_format.as_ref().map_or(0, |f| f.get_nchannels()),
+            sample_formats: mix_format.iter().map(sample_format).collect(),
+            host_api: "WASAPI".to_string(),
+        });
+    }
+    Ok(list)
+}
+
 impl SpeakerInput {
-    pub fn new(device_id: Option<String>) -> Result<Self> {
+    /// `status` receives initialization failures and capture timeouts
+    pub fn new(device_id: Option<String>, status: StatusReporter) -> Result<Self> {
         let device_id = device_id.filter(|id| !id.is_empty() && id != "default");
-        Ok(Self { device_id })
+        Ok(Self { device_id, status })
     }
 
-    pub fn stream(self) -> SpeakerStream {
-        let sample_queue = Arc::new(Mutex::new(VecDeque::new()));
+    /// Fails if the loopback client does not come up
+    pub fn stream(self) -> Result<SpeakerStream> {
+        let rb = HeapRb::<f32>::new(RING_BUFFER_SAMPLES);
+        let (producer, consumer) = rb.split();
         let waker_state = Arc::new(Mutex::new(WakerState {
             shutdown: false,
         }));
         let (init_tx, init_rx) = mpsc::channel();
 
-        let queue_clone = sample_queue.clone();
         let waker_clone = waker_state.clone();
+        let notifier = DataNotifier::new();
+        let notifier_clone = notifier.clone();
+        let overflow = OverflowCounter::new();
+        let overflow_clone = overflow.clone();
         let device_id = self.device_id;
+        let status = self.status;
 
         let capture_thread = thread::spawn(move || {
-            if let Err(e) = Self::capture_audio_loop(queue_clone, waker_clone, init_tx, device_id) {
-                error!("Audio capture loop failed: {}", e);
+            if let Err(e) = Self::capture_audio_loop(producer, notifier_clone, overflow_clone, status, waker_clone, init_tx, device_id) {
+                eprintln!("[WasapiLoopback] Audio capture loop failed: {}", e);
             }
         });
 
         let actual_sample_rate = match init_rx.recv_timeout(Duration::from_secs(5)) {
             Ok(Ok(rate)) => rate,
             Ok(Err(e)) => {
-                error!("Audio initialization failed: {}", e);
-                44100
+                eprintln!("[WasapiLoopback] Audio initialization failed: {}", e);
+                let _ = capture_thread.join();
+                return Err(anyhow::anyhow!("WASAPI loopback initialization failed: {}", e));
             }
             Err(_) => {
-                error!("Audio initialization timeout");
-                44100
+                eprintln!("[WasapiLoopback] Audio initialization timeout");
+                // Not joined: the thread exits on its own if it ever gets going
+                if let Ok(mut state) = waker_state.lock() {
+                    state.shutdown = true;
+                }
+                return Err(anyhow::anyhow!("WASAPI loopback initialization timed out"));
             }
         };
 
-        SpeakerStream {
-            sample_queue,
+        Ok(SpeakerStream {
+            consumer: Some(consumer),
             waker_state,
             capture_thread: Some(capture_thread),
             actual_sample_rate,
-        }
+            notifier,
+            overflow,
+        })
     }
 
     fn capture_audio_loop(
-        sample_queue: Arc<Mutex<VecDeque<f32>>>,
+        mut producer: HeapProd<f32>,
+        notifier: DataNotifier,
+        overflow: OverflowCounter,
+        status: StatusReporter,
         waker_state: Arc<Mutex<WakerState>>,
         init_tx: mpsc::Sender<Result<u32>>,
         device_id: Option<String>,
@@ -165,13 +251,14 @@ impl SpeakerInput {
                     }
 
                     if h_event.wait_for_event(3000).is_err() {
-                        error!("Timeout error, stopping capture");
+                        eprintln!("[WasapiLoopback] Timeout error, stopping capture");
+                        status.fail(StatusCode::StreamStalled, "No WASAPI loopback event for 3s, capture stopped");
                         break;
                     }
 
                     let mut temp_queue = VecDeque::new();
                     if let Err(e) = render_client.read_from_device_to_deque(&mut temp_queue) {
-                        error!("Failed to read audio data: {}", e);
+                        eprintln!("[WasapiLoopback] Failed to read audio data: {}", e);
                         continue;
                     }
 
@@ -192,13 +279,9 @@ impl SpeakerInput {
                     }
 
                     if !samples.is_empty() {
-                         let mut queue = sample_queue.lock().unwrap();
-                         let max_buffer_size = 131072; // 128KB
-                         queue.extend(samples.iter());
-                         if queue.len() > max_buffer_size {
-                             let to_drop = queue.len() - max_buffer_size;
-                             queue.drain(0..to_drop);
-                         }
+                        let pushed = producer.push_slice(&samples);
+                        overflow.record(samples.len(), pushed);
+                        notifier.notify();
                     }
                 }
             }
@@ -0,0 +1,349 @@
+// Speech Detectors - does this frame contain speech?
+//
+// The SilenceSuppressor asks its SpeechDetector about every frame; the
+// detector is chosen per capture (`speechDetector` option):
+//
+// - RmsDetector: frame level against a fixed threshold. Cheap, but any
+//   loud sound (music, typing, HVAC) opens the gate and quiet talkers
+//   stay below it.
+// - SpectralDetector: level above an adaptive per-band noise floor, plus
+//   spectral shape:
+//   - Band energy: 8 log-spaced bands over 150-4000Hz, each compared with
+//     its noise floor (minimum of the smoothed band power over the last
+//     ~1s, which speech pauses keep low but stationary sounds fill)
+//   - Spectral flatness: voiced speech is harmonic (peaky spectrum),
+//     fans, hiss and keyboard clicks are flat
+//   - Zero-crossing rate: broadband clicks and hiss cross zero far more
+//     often than voiced speech
+// - NeuralDetector (cargo feature "neural-vad"): Silero-style ONNX model,
+//   see neural_vad.rs
+//
+// The spectral and neural detectors are level independent, so they need no
+// per-profile threshold and keep working behind AGC.
+
+use std::sync::Arc;
+
+use realfft::num_complex::Complex;
+use realfft::{RealFftPlanner, RealToComplex};
+
+/// Which detector a suppressor uses
+#[derive(Debug, Clone)]
+pub enum SpeechDetectorKind {
+    Rms,
+    Spectral,
+    /// Shares one loaded model between the capture's detectors
+    #[cfg(feature = "neural-vad")]
+    Neural(Arc<crate::neural_vad::SileroModel>),
+}
+
+impl SpeechDetectorKind {
+    /// Parse "rms" | "spectral" ("neural" needs a model, see neural_vad)
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "rms" => Some(Self::Rms),
+            "spectral" => Some(Self::Spectral),
+            _ => None,
+        }
+    }
+
+    /// New detector instance; `rms_threshold` is used by Rms only
+    pub fn create(&self, sample_rate: u32, rms_threshold: f32) -> Box<dyn SpeechDetector> {
+        match self {
+            Self::Rms => Box::new(RmsDetector::new(rms_threshold)),
+            Self::Spectral => Box::new(SpectralDetector::new(sample_rate)),
+            #[cfg(feature = "neural-vad")]
+            Self::Neural(model) => Box::new(crate::neural_vad::NeuralDetector::new(model.clone())),
+        }
+    }
+}
+
+/// Per-frame speech decision behind the SilenceSuppressor
+pub trait SpeechDetector: Send {
+    /// `rms`: frame RMS on the i16 scale, as computed by the suppressor
+    fn is_speech(&mut self, frame: &[i16], rms: f32) -> bool;
+
+    /// New RMS threshold from the suppressor's adaptive mode
+    /// (ignored by level-independent detectors)
+    fn set_threshold(&mut self, _threshold_rms: f32) {}
+}
+
+/// Fixed RMS threshold (the original gate)
+pub struct RmsDetector {
+    threshold: f32,
+}
+
+impl RmsDetector {
+    pub fn new(threshold: f32) -> Self {
+        Self { threshold }
+    }
+}
+
+impl SpeechDetector for RmsDetector {
+    fn is_speech(&mut self, _frame: &[i16], rms: f32) -> bool {
+        rms >= self.threshold
+    }
+
+    fn set_threshold(&mut self, threshold_rms: f32) {
+        self.threshold = threshold_rms;
+    }
+}
+
+/// Frequency range the bands cover (voiced speech energy)
+const SPEECH_LOW_HZ: f32 = 150.0;
+const SPEECH_HIGH_HZ: f32 = 4000.0;
+
+const BAND_COUNT: usize = 8;
+
+/// Time constant of the band power the floor is tracked on
+const POWER_SMOOTHING_MS: f32 = 40.0;
+
+/// Noise floor = minimum over this window (sub-windows of a quarter each)
+const FLOOR_WINDOW_MS: f32 = 1000.0;
+const FLOOR_SUBWINDOWS: usize = 4;
+
+/// The tracked minimum sits below the mean noise power
+const FLOOR_BIAS: f32 = 2.0;
+
+/// Mean band level above the floor needed for speech
+const MIN_SNR_DB: f32 = 4.0;
+
+/// Flatness above this is noise-like (0 = pure tone, 1 = white noise)
+const MAX_FLATNESS: f32 = 0.35;
+
+/// Zero crossings per sample above this are noise-like
+const MAX_ZERO_CROSSING_RATE: f32 = 0.3;
+
+/// Frames quieter than this are never speech (~-70dBFS)
+const MIN_RMS: f32 = 10.0;
+
+/// Adaptive spectral / statistical detector
+pub struct SpectralDetector {
+    sample_rate: u32,
+    /// Set up on the first frame (frame length decides the FFT size)
+    analysis: Option<Analysis>,
+}
+
+struct Analysis {
+    frame_samples: usize,
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    time_buffer: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    /// Bin range of each band, and of the whole speech range
+    bands: Vec<(usize, usize)>,
+    speech_bins: (usize, usize),
+    power_smoothing: f32,
+    smoothed: [f32; BAND_COUNT],
+    /// Minimum of each finished sub-window, and of the current one
+    subwindow_minima: Vec<[f32; BAND_COUNT]>,
+    current_minimum: [f32; BAND_COUNT],
+    subwindow_frames: usize,
+    frames_in_subwindow: usize,
+    frames: u64,
+}
+
+impl SpectralDetector {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1),
+            analysis: None,
+        }
+    }
+}
+
+impl SpeechDetector for SpectralDetector {
+    fn is_speech(&mut self, frame: &[i16], rms: f32) -> bool {
+        if frame.is_empty() {
+            return false;
+        }
+        let sample_rate = self.sample_rate;
+        let analysis = match &mut self.analysis {
+            Some(analysis) if analysis.frame_samples == frame.len() => analysis,
+            slot => slot.insert(Analysis::new(sample_rate, frame.len())),
+        };
+        let features = analysis.process(frame);
+
+        rms >= MIN_RMS
+            && features.snr_db >= MIN_SNR_DB
+            && features.flatness <= MAX_FLATNESS
+            && features.zero_crossing_rate <= MAX_ZERO_CROSSING_RATE
+    }
+}
+
+struct Features {
+    /// Mean level of the bands above their noise floor, dB (>= 0)
+    snr_db: f32,
+    flatness: f32,
+    zero_crossing_rate: f32,
+}
+
+impl Analysis {
+    fn new(sample_rate: u32, frame_samples: usize) -> Self {
+        let fft_size = frame_samples.next_power_of_two().max(64);
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(fft_size);
+        let spectrum = fft.make_output_vec();
+        let window = (0..frame_samples)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * i as f32 / frame_samples as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+
+        let bin_hz = sample_rate as f32 / fft_size as f32;
+        let last_bin = fft_size / 2;
+        let to_bin = |hz: f32| ((hz / bin_hz).round() as usize).clamp(1, last_bin);
+        let low = to_bin(SPEECH_LOW_HZ);
+        let high = to_bin(SPEECH_HIGH_HZ.min(sample_rate as f32 / 2.0)).max(low + 1);
+        // Log-spaced edges, each band at least one bin wide
+        let ratio = (high as f32 / low as f32).powf(1.0 / BAND_COUNT as f32);
+        let mut bands = Vec::with_capacity(BAND_COUNT);
+        let mut start = low;
+        for band in 1..=BAND_COUNT {
+            let edge = (low as f32 * ratio.powi(band as i32)).round() as usize;
+            let end = edge.max(start + 1).min(last_bin + 1);
+            bands.push((start.min(end - 1), end));
+            start = end;
+        }
+
+        let frame_ms = frame_samples as f32 * 1000.0 / sample_rate as f32;
+        let subwindow_ms = FLOOR_WINDOW_MS / FLOOR_SUBWINDOWS as f32;
+        Self {
+            frame_samples,
+            fft,
+            window,
+            time_buffer: vec![0.0; fft_size],
+            spectrum,
+            bands,
+            speech_bins: (low, high),
+            power_smoothing: (-frame_ms / POWER_SMOOTHING_MS).exp(),
+            smoothed: [0.0; BAND_COUNT],
+            subwindow_minima: Vec::with_capacity(FLOOR_SUBWINDOWS),
+            current_minimum: [f32::MAX; BAND_COUNT],
+            subwindow_frames: ((subwindow_ms / frame_ms).round() as usize).max(1),
+            frames_in_subwindow: 0,
+            frames: 0,
+        }
+    }
+
+    fn process(&mut self, frame: &[i16]) -> Features {
+        // Zero-crossing rate on the raw samples
+        let crossings = frame.windows(2)
+            .filter(|pair| (pair[0] >= 0) != (pair[1] >= 0))
+            .count();
+        let zero_crossing_rate = crossings as f32 / frame.len() as f32;
+
+        // Power spectrum
+        for (i, slot) in self.time_buffer.iter_mut().enumerate() {
+            *slot = frame.get(i).map_or(0.0, |&s| s as f32 / 32768.0 * self.window[i]);
+        }
+        if self.fft.process(&mut self.time_buffer, &mut self.spectrum).is_err() {
+            return Features { snr_db: 0.0, flatness: 1.0, zero_crossing_rate };
+        }
+
+        // Spectral flatness over the speech range
+        let (low, high) = self.speech_bins;
+        let mut log_sum = 0.0f64;
+        let mut sum = 0.0f64;
+        for bin in &self.spectrum[low..high] {
+            let power = bin.norm_sqr() as f64 + 1e-12;
+            log_sum += power.ln();
+            sum += power;
+        }
+        let count = (high - low) as f64;
+        let flatness = ((log_sum / count).exp() / (sum / count)) as f32;
+
+        // Band levels against the adaptive floor
+        let floor = self.current_floor();
+        let mut snr_db = 0.0;
+        for (band, &(start, end)) in self.bands.iter().enumerate() {
+            let power = self.spectrum[start..end].iter().map(|c| c.norm_sqr()).sum::<f32>();
+            let noise = (floor[band] * FLOOR_BIAS).max(1e-10);
+            snr_db += (10.0 * (power / noise).log10()).max(0.0);
+
+            self.smoothed[band] = if self.frames == 0 {
+                power
+            } else {
+                self.power_smoothing * self.smoothed[band] + (1.0 - self.power_smoothing) * power
+            };
+            self.current_minimum[band] = self.current_minimum[band].min(self.smoothed[band]);
+        }
+        self.frames += 1;
+        self.advance_subwindow();
+
+        Features {
+            snr_db: snr_db / BAND_COUNT as f32,
+            flatness,
+            zero_crossing_rate,
+        }
+    }
+
+    /// Noise floor per band from the frames before this one
+    fn current_floor(&self) -> [f32; BAND_COUNT] {
+        let mut floor = self.current_minimum;
+        for minima in &self.subwindow_minima {
+            for (f, m) in floor.iter_mut().zip(minima) {
+                *f = f.min(*m);
+            }
+        }
+        floor
+    }
+
+    fn advance_subwindow(&mut self) {
+        self.frames_in_subwindow += 1;
+        if self.frames_in_subwindow < self.subwindow_frames {
+            return;
+        }
+        if self.subwindow_minima.len() == FLOOR_SUBWINDOWS {
+            self.subwindow_minima.remove(0);
+        }
+        self.subwindow_minima.push(self.current_minimum);
+        self.current_minimum = [f32::MAX; BAND_COUNT];
+        self.frames_in_subwindow = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::silence_suppression::calculate_rms;
+    use crate::test_fixtures::labeled_set;
+
+    /// Fraction of frames classified like their label, per clip
+    fn accuracy(mut make: impl FnMut() -> Box<dyn SpeechDetector>) -> Vec<(&'static str, f32)> {
+        labeled_set()
+            .into_iter()
+            .map(|clip| {
+                let mut detector = make();
+                let correct = clip.samples.chunks_exact(320)
+                    .zip(&clip.labels)
+                    .filter(|(frame, &label)| detector.is_speech(frame, calculate_rms(frame)) == label)
+                    .count();
+                (clip.name, correct as f32 / clip.labels.len() as f32)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_spectral_beats_rms_on_labeled_fixtures() {
+        let rms = accuracy(|| Box::new(RmsDetector::new(100.0)));
+        let spectral = accuracy(|| Box::new(SpectralDetector::new(16_000)));
+
+        let mean = |scores: &[(&str, f32)]| scores.iter().map(|(_, a)| a).sum::<f32>() / scores.len() as f32;
+        assert!(mean(&spectral) >= 0.85, "spectral mean {:.3}", mean(&spectral));
+        assert!(mean(&spectral) >= mean(&rms) + 0.2, "rms {:.3}, spectral {:.3}", mean(&rms), mean(&spectral));
+        for ((name, r), (_, s)) in rms.iter().zip(&spectral) {
+            // Never meaningfully worse than RMS, clip by clip
+            assert!(*s >= r - 0.05, "{}: rms {:.3}, spectral {:.3}", name, r, s);
+        }
+    }
+
+    #[test]
+    fn test_odd_frame_sizes() {
+        // 10ms at 8kHz and 60ms at 48kHz: speech range clipped to Nyquist
+        for (sample_rate, frame_samples) in [(8000, 80), (48_000, 2880)] {
+            let mut detector = SpectralDetector::new(sample_rate);
+            let silence = vec![0i16; frame_samples];
+            assert!(!(0..20).any(|_| detector.is_speech(&silence, 0.0)));
+        }
+    }
+}
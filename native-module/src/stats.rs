@@ -0,0 +1,172 @@
+// Capture Statistics - lock-free values shared with JS
+//
+// The DSP thread publishes with Relaxed atomic stores (no locks on the
+// audio path); getStats() on the capture classes snapshots them.
+//
+// Counters cover the current (or last) start(): CapturePipeline::start
+// resets them. Samples dropped on ring-buffer overflow are counted by the
+// source's producer in an OverflowCounter and published by the DSP thread.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Samples a producer could not push because the ring buffer was full.
+/// Cloneable; safe to update from an audio callback.
+#[derive(Clone, Default)]
+pub struct OverflowCounter {
+    dropped: Arc<AtomicU64>,
+    /// The producer gave up after continuous overflow
+    abandoned: Arc<AtomicBool>,
+}
+
+impl OverflowCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a push of `offered` samples of which `pushed` fit
+    pub fn record(&self, offered: usize, pushed: usize) {
+        if pushed < offered {
+            self.dropped.fetch_add((offered - pushed) as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The producer stopped pushing for good; the DSP thread reports it.
+    /// Returns false if it had already been abandoned.
+    pub fn abandon(&self) -> bool {
+        !self.abandoned.swap(true, Ordering::AcqRel)
+    }
+
+    pub fn abandoned(&self) -> bool {
+        self.abandoned.load(Ordering::Acquire)
+    }
+}
+
+/// Live statistics of one capture, shared with its DSP thread
+#[derive(Default)]
+pub struct PipelineStats {
+    /// Current AGC gain in dB, stored as f32 bits
+    agc_gain_db: AtomicU32,
+    /// Speech detector time per frame in ms, stored as f32 bits
+    speech_detector_ms: AtomicU32,
+    /// Silence gate noise floor and threshold (RMS), stored as f32 bits
+    noise_floor_rms: AtomicU32,
+    speech_threshold_rms: AtomicU32,
+    frames_emitted: AtomicU64,
+    keepalive_frames: AtomicU64,
+    suppressed_frames: AtomicU64,
+    samples_dropped: AtomicU64,
+    /// Ring buffer fill (0..1) before the last drain, stored as f32 bits
+    ring_buffer_fill: AtomicU32,
+    input_sample_rate: AtomicU32,
+    /// Smoothed time from a drain to its frames being emitted, ms as f32 bits
+    dsp_latency_ms: AtomicU32,
+}
+
+impl PipelineStats {
+    pub fn set_agc_gain_db(&self, gain_db: f32) {
+        self.agc_gain_db.store(gain_db.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn agc_gain_db(&self) -> f32 {
+        f32::from_bits(self.agc_gain_db.load(Ordering::Relaxed))
+    }
+
+    pub fn set_speech_detector_ms(&self, ms: f32) {
+        self.speech_detector_ms.store(ms.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn speech_detector_ms(&self) -> f32 {
+        f32::from_bits(self.speech_detector_ms.load(Ordering::Relaxed))
+    }
+
+    pub fn set_silence_gate(&self, noise_floor_rms: f32, speech_threshold_rms: f32) {
+        self.noise_floor_rms.store(noise_floor_rms.to_bits(), Ordering::Relaxed);
+        self.speech_threshold_rms.store(speech_threshold_rms.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Reset the counters for a new start() from a source at `input_sample_rate`
+    pub fn start_session(&self, input_sample_rate: u32) {
+        self.input_sample_rate.store(input_sample_rate, Ordering::Relaxed);
+        for counter in [&self.frames_emitted, &self.keepalive_frames, &self.suppressed_frames, &self.samples_dropped] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.ring_buffer_fill.store(0.0f32.to_bits(), Ordering::Relaxed);
+        self.dsp_latency_ms.store(0.0f32.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The source changed rate mid-session (device switch)
+    pub fn set_input_sample_rate(&self, input_sample_rate: u32) {
+        self.input_sample_rate.store(input_sample_rate, Ordering::Relaxed);
+    }
+
+    pub fn set_frame_counts(&self, emitted: u64, keepalives: u64, suppressed: u64) {
+        self.frames_emitted.store(emitted, Ordering::Relaxed);
+        self.keepalive_frames.store(keepalives, Ordering::Relaxed);
+        self.suppressed_frames.store(suppressed, Ordering::Relaxed);
+    }
+
+    /// Ring buffer state seen by the DSP thread before a drain
+    pub fn set_ring_buffer(&self, occupied: usize, capacity: usize, samples_dropped: u64) {
+        let fill = occupied as f32 / capacity.max(1) as f32;
+        self.ring_buffer_fill.store(fill.to_bits(), Ordering::Relaxed);
+        self.samples_dropped.store(samples_dropped, Ordering::Relaxed);
+    }
+
+    pub fn set_dsp_latency_ms(&self, ms: f32) {
+        self.dsp_latency_ms.store(ms.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn dsp_latency_ms(&self) -> f32 {
+        f32::from_bits(self.dsp_latency_ms.load(Ordering::Relaxed))
+    }
+
+    pub fn snapshot(&self) -> CaptureStats {
+        CaptureStats {
+            agc_gain_db: self.agc_gain_db() as f64,
+            speech_detector_ms: self.speech_detector_ms() as f64,
+            noise_floor_rms: f32::from_bits(self.noise_floor_rms.load(Ordering::Relaxed)) as f64,
+            speech_threshold_rms: f32::from_bits(self.speech_threshold_rms.load(Ordering::Relaxed)) as f64,
+            frames_emitted: self.frames_emitted.load(Ordering::Relaxed) as f64,
+            keepalive_frames: self.keepalive_frames.load(Ordering::Relaxed) as f64,
+            suppressed_frames: self.suppressed_frames.load(Ordering::Relaxed) as f64,
+            samples_dropped: self.samples_dropped.load(Ordering::Relaxed) as f64,
+            ring_buffer_fill: f32::from_bits(self.ring_buffer_fill.load(Ordering::Relaxed)) as f64,
+            input_sample_rate: self.input_sample_rate.load(Ordering::Relaxed),
+            dsp_latency_ms: self.dsp_latency_ms() as f64,
+        }
+    }
+}
+
+#[napi(object)]
+pub struct CaptureStats {
+    /// Current AGC gain in dB (0 when AGC is off).
+    /// Staying near the configured maxGainDb means the input is very quiet.
+    pub agc_gain_db: f64,
+    /// Average CPU time the speech detector spends per frame, ms.
+    /// Compare with frameMs: the neural detector must stay well below it.
+    pub speech_detector_ms: f64,
+    /// Background level the silence gate measures, RMS i16 scale
+    pub noise_floor_rms: f64,
+    /// Speech threshold the silence gate applies (fixed, or floor + margin)
+    pub speech_threshold_rms: f64,
+    /// Frames passed to the callback since start(), keepalives included
+    pub frames_emitted: f64,
+    /// Generated silence frames among framesEmitted
+    pub keepalive_frames: f64,
+    /// Frames the silence gate dropped
+    pub suppressed_frames: f64,
+    /// Input samples lost because the ring buffer was full.
+    /// Growing means the DSP thread cannot keep up.
+    pub samples_dropped: f64,
+    /// Ring buffer fill level before the last drain (0..1)
+    pub ring_buffer_fill: f64,
+    /// Rate the device delivers, Hz (0 before start())
+    pub input_sample_rate: u32,
+    /// Average time from draining the ring buffer to emitting its frames, ms
+    pub dsp_latency_ms: f64,
+}
@@ -0,0 +1,161 @@
+// Stream Alignment - put independently clocked captures on one timeline
+//
+// Each source delivers frames with capture timestamps (CaptureClock). The
+// aligner places every frame at its timestamp on a shared output timeline
+// at the output sample rate:
+//
+// - Gaps (device stalled, capture restarted) larger than GAP_MS are filled
+//   with silence; overlaps that large drop the early samples
+// - Smaller timing errors beyond DRIFT_DEADBAND_MS are clock drift and are
+//   corrected by one sample per frame (repeat or drop), which is inaudible
+// - A source that delivers nothing for longer than the lag limit (stopped
+//   or never started) is rendered as silence so the others keep flowing
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Timing error treated as a gap / overlap rather than drift
+/// (drift correction keeps real clock skew well below this)
+const GAP_MS: f64 = 5.0;
+
+/// Timing error tolerated before drift correction kicks in
+const DRIFT_DEADBAND_MS: f64 = 1.0;
+
+#[derive(Default)]
+struct AlignedSource {
+    /// Samples from timeline position `written` onwards
+    queue: VecDeque<i16>,
+    started: bool,
+}
+
+pub struct StreamAligner {
+    sample_rate: f64,
+    sources: Vec<AlignedSource>,
+    /// Timeline origin: the first timestamp seen from any source
+    origin: Option<Instant>,
+    /// Timeline samples already drained
+    written: u64,
+    max_lag: usize,
+    gap: i64,
+    deadband: i64,
+}
+
+impl StreamAligner {
+    /// `max_lag`: samples one source may run ahead of a silent one
+    pub fn new(sources: usize, sample_rate: u32, max_lag: usize) -> Self {
+        let ms = |ms: f64| (ms * sample_rate as f64 / 1000.0).round() as i64;
+        Self {
+            sample_rate: sample_rate as f64,
+            sources: (0..sources).map(|_| AlignedSource::default()).collect(),
+            origin: None,
+            written: 0,
+            max_lag,
+            gap: ms(GAP_MS),
+            deadband: ms(DRIFT_DEADBAND_MS).max(1),
+        }
+    }
+
+    /// Add a frame whose first sample was captured at `timestamp`
+    pub fn push(&mut self, source: usize, frame: &[i16], timestamp: Instant) {
+        let origin = *self.origin.get_or_insert(timestamp);
+        let position = if timestamp >= origin {
+            ((timestamp - origin).as_secs_f64() * self.sample_rate).round() as i64
+        } else {
+            -((origin - timestamp).as_secs_f64() * self.sample_rate).round() as i64
+        };
+
+        let gap = self.gap;
+        let deadband = self.deadband;
+        let written = self.written as i64;
+        let state = &mut self.sources[source];
+        let end = written + state.queue.len() as i64;
+        let error = position - end;
+
+        let mut frame = frame;
+        if !state.started || error.abs() > gap {
+            // (Re)start or discontinuity: jump straight to the timestamp
+            state.started = true;
+            if error > 0 {
+                state.queue.extend(std::iter::repeat_n(0, error as usize));
+            } else {
+                frame = &frame[((-error) as usize).min(frame.len())..];
+            }
+        } else if error > deadband && !frame.is_empty() {
+            // Source clock slow: stretch by one sample
+            state.queue.push_back(frame[0]);
+        } else if error < -deadband && !frame.is_empty() {
+            // Source clock fast: skip one sample
+            frame = &frame[1..];
+        }
+        state.queue.extend(frame);
+    }
+
+    /// Drain aligned samples, one Vec per source, all the same length.
+    /// `flush` drains everything that is buffered.
+    pub fn drain(&mut self, flush: bool) -> Vec<Vec<i16>> {
+        let longest = self.sources.iter().map(|s| s.queue.len()).max().unwrap_or(0);
+        let shortest = self.sources.iter().map(|s| s.queue.len()).min().unwrap_or(0);
+        let count = if flush {
+            longest
+        } else {
+            shortest.max(longest.saturating_sub(self.max_lag))
+        };
+
+        self.written += count as u64;
+        self.sources.iter_mut()
+            .map(|s| (0..count).map(|_| s.queue.pop_front().unwrap_or(0)).collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(base: Instant, ms: u64) -> Instant {
+        base + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_aligns_sources_by_timestamp() {
+        let base = Instant::now();
+        let mut aligner = StreamAligner::new(2, 16_000, 8000);
+        // Source 1 starts 100ms after source 0
+        for i in 0..20u64 {
+            aligner.push(0, &[1; 320], at(base, i * 20));
+            if i >= 5 {
+                aligner.push(1, &[2; 320], at(base, i * 20));
+            }
+        }
+        let out = aligner.drain(false);
+        assert_eq!(out[0].len(), 20 * 320);
+        assert!(out[1][..1600].iter().all(|&s| s == 0));
+        assert!(out[1][1600..].iter().all(|&s| s == 2));
+    }
+
+    #[test]
+    fn test_gap_filled_with_silence() {
+        let base = Instant::now();
+        let mut aligner = StreamAligner::new(1, 16_000, 8000);
+        aligner.push(0, &[5; 320], at(base, 0));
+        // 200ms stall, then the next frame
+        aligner.push(0, &[7; 320], at(base, 220));
+        let out = aligner.drain(true).remove(0);
+        assert_eq!(out.len(), 320 + 3200 + 320);
+        assert!(out[320..3520].iter().all(|&s| s == 0));
+        assert!(out[3520..].iter().all(|&s| s == 7));
+    }
+
+    #[test]
+    fn test_silent_source_does_not_block() {
+        let base = Instant::now();
+        let mut aligner = StreamAligner::new(2, 16_000, 8000);
+        for i in 0..50u64 {
+            aligner.push(0, &[3; 320], at(base, i * 20));
+        }
+        let out = aligner.drain(false);
+        assert_eq!(out[0].len(), 50 * 320 - 8000);
+        assert!(out[1].iter().all(|&s| s == 0));
+    }
+}
@@ -1,11 +1,52 @@
-// Streaming Linear Resampler
-// Zero-latency, zero-lookahead linear interpolation
+// Streaming Resampler
 // Compliant with real-time audio requirements
+//
+// QUALITY PRESETS:
+// - Fast: linear interpolation. Zero latency, but no anti-alias filter,
+//   so downsampling folds everything above the output Nyquist back into
+//   the speech band.
+// - Balanced / High: rational polyphase windowed-sinc (Kaiser) filter.
+//   The low-pass cuts just below the lower Nyquist frequency; latency is
+//   half the filter length (well under the 21ms of the FFT-based
+//   `resampler::Resampler`).
 
-/// Streaming resampler using linear interpolation
-/// - Zero algorithmic latency (vs 21ms for FFT)
-/// - Stateful fractional position for seamless streaming
-/// - Converts f32 input to i16 output at 16kHz
+use std::f64::consts::PI;
+
+/// Resampler quality preset, chosen per capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResamplerQuality {
+    /// Linear interpolation, no anti-alias filter
+    Fast,
+    /// ~60 dB stopband, ~0.5ms latency at 48kHz
+    Balanced,
+    /// ~90 dB stopband, ~1.6ms latency at 48kHz
+    High,
+}
+
+impl ResamplerQuality {
+    /// Parse "fast" | "balanced" | "high"
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "fast" => Some(Self::Fast),
+            "balanced" => Some(Self::Balanced),
+            "high" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    /// (zero crossings per side, passband fraction of Nyquist, stopband dB)
+    fn filter_params(self) -> (f64, f64, f64) {
+        match self {
+            Self::Fast => (0.0, 0.0, 0.0),
+            Self::Balanced => (8.0, 0.90, 60.0),
+            Self::High => (24.0, 0.94, 90.0),
+        }
+    }
+}
+
+/// Streaming resampler: linear interpolation or polyphase windowed-sinc
+/// - Stateful across calls for seamless streaming
+/// - Converts f32 input to i16 output at the output rate
 pub struct StreamingResampler {
     /// Ratio of input sample rate to output sample rate
     /// e.g., 48000/16000 = 3.0
@@ -16,19 +57,37 @@ pub struct StreamingResampler {
     prev_sample: f32,
     /// Whether we've received any samples yet
     initialized: bool,
+    /// Anti-aliased path (Balanced / High); linear interpolation when None
+    polyphase: Option<PolyphaseFilter>,
+    filtered: Vec<f32>,
 }
 
 impl StreamingResampler {
-    /// Create a new streaming resampler
+    /// Create a new linear-interpolation resampler (ResamplerQuality::Fast)
     /// 
     /// # Arguments
     /// * `input_sample_rate` - Source sample rate (e.g., 48000)
     /// * `output_sample_rate` - Target sample rate (always 16000 for STT)
     pub fn new(input_sample_rate: f64, output_sample_rate: f64) -> Self {
+        Self::with_quality(input_sample_rate, output_sample_rate, ResamplerQuality::Fast)
+    }
+
+    /// Create a resampler with the given quality preset
+    pub fn with_quality(input_sample_rate: f64, output_sample_rate: f64, quality: ResamplerQuality) -> Self {
         let ratio = input_sample_rate / output_sample_rate;
+        let polyphase = match quality {
+            ResamplerQuality::Fast => None,
+            _ => PolyphaseFilter::new(input_sample_rate as u32, output_sample_rate as u32, quality),
+        };
         println!(
-            "[StreamingResampler] Created: {}Hz -> {}Hz (ratio: {:.4}, linear interpolation)",
-            input_sample_rate, output_sample_rate, ratio
+            "[StreamingResampler] Created: {}Hz -> {}Hz (ratio: {:.4}, {})",
+            input_sample_rate,
+            output_sample_rate,
+            ratio,
+            match &polyphase {
+                Some(filter) => format!("{:?} polyphase sinc, {} taps/phase", quality, filter.taps),
+                None => "linear interpolation".to_string(),
+            }
         );
         
         Self {
@@ -36,9 +95,16 @@ impl StreamingResampler {
             fractional_pos: 0.0,
             prev_sample: 0.0,
             initialized: false,
+            polyphase,
+            filtered: Vec::new(),
         }
     }
 
+    /// Algorithmic latency in output samples
+    pub fn latency_samples(&self) -> f64 {
+        self.polyphase.as_ref().map_or(0.0, |filter| filter.latency_samples())
+    }
+
     /// Resample a chunk of f32 audio to i16 at 16kHz
     /// 
     /// Uses linear interpolation between samples.
@@ -54,6 +120,14 @@ impl StreamingResampler {
             return Vec::new();
         }
 
+        if let Some(filter) = self.polyphase.as_mut() {
+            self.filtered.clear();
+            filter.process(input, &mut self.filtered);
+            return self.filtered.iter()
+                .map(|&s| (s * 32767.0).clamp(-32768.0, 32767.0) as i16)
+                .collect();
+        }
+
         // Estimate output size (slightly over-allocate for safety)
         let estimated_output = ((input.len() as f64 / self.ratio) + 2.0) as usize;
         let mut output = Vec::with_capacity(estimated_output);
@@ -115,9 +189,144 @@ impl StreamingResampler {
         self.fractional_pos = 0.0;
         self.prev_sample = 0.0;
         self.initialized = false;
+        if let Some(filter) = self.polyphase.as_mut() {
+            filter.reset();
+        }
     }
 }
 
+/// Rational polyphase FIR: upsample by `up`, low-pass, downsample by `down`
+///
+/// Only the taps that touch non-zero upsampled samples are evaluated, so
+/// each output costs `taps` multiply-adds.
+struct PolyphaseFilter {
+    up: usize,
+    down: usize,
+    /// Taps per phase
+    taps: usize,
+    /// coeffs[phase * taps + k] multiplies input[base - k]
+    coeffs: Vec<f32>,
+    /// Input history; the first `taps - 1` samples precede the stream
+    history: Vec<f32>,
+    /// Index into `history` of the newest input the next output uses
+    base: usize,
+    phase: usize,
+    /// Prototype filter length at the upsampled rate
+    length: usize,
+}
+
+impl PolyphaseFilter {
+    /// None when the rates are equal (nothing to filter) or invalid
+    fn new(input_rate: u32, output_rate: u32, quality: ResamplerQuality) -> Option<Self> {
+        if input_rate == 0 || output_rate == 0 || input_rate == output_rate {
+            return None;
+        }
+        let g = gcd(input_rate as usize, output_rate as usize);
+        let up = output_rate as usize / g;
+        let down = input_rate as usize / g;
+
+        // Cutoff relative to the upsampled rate, just below the lower Nyquist
+        let (zero_crossings, passband, stopband_db) = quality.filter_params();
+        let cutoff = passband * 0.5 / up.max(down) as f64;
+        let taps = ((zero_crossings / cutoff) / up as f64).ceil() as usize;
+        let length = taps * up;
+
+        let beta = kaiser_beta(stopband_db);
+        let center = (length - 1) as f64 / 2.0;
+        let mut coeffs = vec![0.0f32; length];
+        for n in 0..length {
+            let t = n as f64 - center;
+            let window = bessel_i0(beta * (1.0 - (t / (center + 1.0)).powi(2)).max(0.0).sqrt())
+                / bessel_i0(beta);
+            let value = 2.0 * cutoff * sinc(2.0 * cutoff * t) * window * up as f64;
+            // Polyphase layout: prototype tap n = phase + k * up
+            let (phase, k) = (n % up, n / up);
+            coeffs[phase * taps + k] = value as f32;
+        }
+
+        Some(Self {
+            up,
+            down,
+            taps,
+            coeffs,
+            history: vec![0.0; taps - 1],
+            base: taps - 1,
+            phase: 0,
+            length,
+        })
+    }
+
+    fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        self.history.extend_from_slice(input);
+
+        while self.base < self.history.len() {
+            let coeffs = &self.coeffs[self.phase * self.taps..][..self.taps];
+            let window = &self.history[self.base + 1 - self.taps..=self.base];
+            let acc: f32 = coeffs.iter().zip(window.iter().rev()).map(|(c, x)| c * x).sum();
+            output.push(acc);
+
+            self.phase += self.down;
+            self.base += self.phase / self.up;
+            self.phase %= self.up;
+        }
+
+        // Keep only the history the next output still needs
+        let consumed = (self.base + 1 - self.taps).min(self.history.len());
+        self.history.drain(..consumed);
+        self.base -= consumed;
+    }
+
+    /// Group delay of the linear-phase prototype, in output samples
+    fn latency_samples(&self) -> f64 {
+        (self.length - 1) as f64 / 2.0 / self.down as f64
+    }
+
+    fn reset(&mut self) {
+        self.history.clear();
+        self.history.resize(self.taps - 1, 0.0);
+        self.base = self.taps - 1;
+        self.phase = 0;
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-12 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Kaiser window beta for a given stopband attenuation
+fn kaiser_beta(attenuation_db: f64) -> f64 {
+    if attenuation_db > 50.0 {
+        0.1102 * (attenuation_db - 8.7)
+    } else if attenuation_db > 21.0 {
+        0.5842 * (attenuation_db - 21.0).powf(0.4) + 0.07886 * (attenuation_db - 21.0)
+    } else {
+        0.0
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind (power series)
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_sq = (x / 2.0) * (x / 2.0);
+    for k in 1..50 {
+        term *= half_sq / (k * k) as f64;
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,4 +362,83 @@ mod tests {
         // Output should be consistent
         assert!((out1.len() as i32 - out2.len() as i32).abs() <= 1);
     }
+
+    fn tone(freq: f64, rate: f64, seconds: f64, amplitude: f32) -> Vec<f32> {
+        (0..(rate * seconds) as usize)
+            .map(|i| amplitude * (2.0 * PI * freq * i as f64 / rate).sin() as f32)
+            .collect()
+    }
+
+    /// Output level relative to the input tone, in dB (after settling)
+    fn tone_gain_db(quality: ResamplerQuality, freq: f64, input_rate: f64) -> f64 {
+        let mut resampler = StreamingResampler::with_quality(input_rate, 16000.0, quality);
+        let input = tone(freq, input_rate, 1.0, 0.5);
+        let output: Vec<i16> = input.chunks(480).flat_map(|c| resampler.resample(c)).collect();
+        let settled = &output[800..];
+        let power: f64 = settled.iter().map(|&s| (s as f64 / 32767.0).powi(2)).sum::<f64>()
+            / settled.len() as f64;
+        10.0 * (power / (0.5f64 * 0.5 / 2.0)).log10()
+    }
+
+    #[test]
+    fn test_aliasing_rejection() {
+        // 11kHz and 13kHz at 48kHz fold to 5kHz and 3kHz at 16kHz
+        for freq in [11000.0, 13000.0] {
+            let fast = tone_gain_db(ResamplerQuality::Fast, freq, 48000.0);
+            let balanced = tone_gain_db(ResamplerQuality::Balanced, freq, 48000.0);
+            let high = tone_gain_db(ResamplerQuality::High, freq, 48000.0);
+            assert!(fast > -3.0, "linear interpolation is expected to alias: {}Hz {:.1} dB", freq, fast);
+            assert!(balanced < -55.0, "{}Hz balanced alias {:.1} dB", freq, balanced);
+            assert!(high < -75.0, "{}Hz high alias {:.1} dB", freq, high);
+        }
+
+        // Non-integer ratio (44.1kHz)
+        let alias = tone_gain_db(ResamplerQuality::High, 12000.0, 44100.0);
+        assert!(alias < -75.0, "44.1kHz alias {:.1} dB", alias);
+    }
+
+    #[test]
+    fn test_passband_flat() {
+        for quality in [ResamplerQuality::Balanced, ResamplerQuality::High] {
+            for freq in [300.0, 1000.0, 3400.0, 6000.0] {
+                let gain = tone_gain_db(quality, freq, 48000.0);
+                assert!(gain.abs() < 0.5, "{:?} {}Hz gain {:.2} dB", quality, freq, gain);
+            }
+        }
+    }
+
+    #[test]
+    fn test_latency_matches_impulse_response() {
+        for (quality, max_ms) in [(ResamplerQuality::Balanced, 1.0), (ResamplerQuality::High, 2.0)] {
+            let mut resampler = StreamingResampler::with_quality(48000.0, 16000.0, quality);
+            let mut input = vec![0.0f32; 4800];
+            input[1200] = 1.0; // output sample 400
+            let output = resampler.resample(&input);
+
+            let peak = output.iter().enumerate().max_by_key(|(_, s)| s.abs()).unwrap().0;
+            let measured = peak as f64 - 400.0;
+            let reported = resampler.latency_samples();
+            assert!((measured - reported).abs() <= 1.0,
+                "{:?}: latency {:.2} samples (measured {})", quality, reported, measured);
+            assert!(reported / 16.0 < max_ms, "{:?}: latency {:.2} samples", quality, reported);
+        }
+        assert_eq!(StreamingResampler::new(48000.0, 16000.0).latency_samples(), 0.0);
+    }
+
+    #[test]
+    fn test_chunking_invariant() {
+        let input = tone(440.0, 44100.0, 0.5, 0.5);
+        let mut whole = StreamingResampler::with_quality(44100.0, 16000.0, ResamplerQuality::High);
+        let expected = whole.resample(&input);
+
+        let mut chunked = StreamingResampler::with_quality(44100.0, 16000.0, ResamplerQuality::High);
+        let mut output = Vec::new();
+        for (i, chunk) in input.chunks(97).enumerate() {
+            // Vary chunk sizes, including single samples
+            let (a, b) = chunk.split_at(i % chunk.len().max(1));
+            output.extend(chunked.resample(a));
+            output.extend(chunked.resample(b));
+        }
+        assert_eq!(output, expected);
+    }
 }
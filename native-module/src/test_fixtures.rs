@@ -0,0 +1,235 @@
+// Synthetic Audio Fixtures for Offline DSP Tests
+//
+// Deterministic 16kHz signals standing in for recorded fixtures:
+// - speech: voiced harmonics shaped by two formants, syllable envelopes
+//   and pauses (the parts STT and the gates care about)
+// - fan: stationary low-pass noise with a blade-rate hum
+// - keyboard: short broadband clicks at irregular intervals
+// - music: sustained harmonic chords, the classic false trigger of VADs
+//
+// `labeled_set` combines them into clips with per-frame speech labels
+// for detector accuracy tests.
+//
+// Every generator takes a seed, so a failing test reproduces exactly.
+
+use std::f32::consts::PI;
+
+use crate::audio_config::SAMPLE_RATE;
+
+/// Small deterministic PRNG (xorshift64*), independent of `rand` versions
+pub struct Noise(u64);
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    /// Uniform in [-1, 1)
+    pub fn next(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let bits = self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40;
+        bits as f32 / (1u64 << 23) as f32 - 1.0
+    }
+}
+
+fn samples_for(seconds: f32) -> usize {
+    (seconds * SAMPLE_RATE as f32) as usize
+}
+
+/// Speech-like signal: ~4 syllables/s with pauses, peak around 0.3 FS
+pub fn speech(seconds: f32, seed: u64) -> Vec<f32> {
+    labeled_speech(seconds, seed).0
+}
+
+/// `speech` plus a per-sample mask of where syllables are audible
+pub fn labeled_speech(seconds: f32, seed: u64) -> (Vec<f32>, Vec<bool>) {
+    let mut rng = Noise::new(seed);
+    let len = samples_for(seconds);
+    let mut out = vec![0.0f32; len];
+    let mut active = vec![false; len];
+
+    let mut pos = 0;
+    let mut phase = 0.0f32;
+    while pos < len {
+        // Syllable of 120-280ms, followed by a 40-400ms gap
+        let syllable = samples_for(0.2 + 0.08 * rng.next());
+        let gap = samples_for(0.22 + 0.18 * rng.next());
+        let f0_start = 150.0 + 50.0 * rng.next();
+        let f0_end = f0_start * (1.0 + 0.15 * rng.next());
+        let formant1 = 600.0 + 200.0 * rng.next();
+        let formant2 = 1700.0 + 500.0 * rng.next();
+
+        for i in 0..syllable.min(len - pos) {
+            let t = i as f32 / syllable as f32;
+            let envelope = (PI * t).sin().powf(0.7);
+            let f0 = f0_start + (f0_end - f0_start) * t;
+            phase += 2.0 * PI * f0 / SAMPLE_RATE as f32;
+
+            let mut sample = 0.0;
+            let mut harmonic = 1;
+            while f0 * harmonic as f32 <= 4000.0 {
+                let freq = f0 * harmonic as f32;
+                let weight = formant_weight(freq, formant1) + 0.5 * formant_weight(freq, formant2);
+                sample += weight * (phase * harmonic as f32).sin() / harmonic as f32;
+                harmonic += 1;
+            }
+            out[pos + i] = 0.3 * envelope * sample;
+            active[pos + i] = envelope >= 0.25;
+        }
+        pos += syllable + gap;
+    }
+
+    normalize_peak(&mut out, 0.3);
+    (out, active)
+}
+
+fn formant_weight(freq: f32, formant: f32) -> f32 {
+    let bandwidth = 150.0;
+    1.0 / (1.0 + ((freq - formant) / bandwidth).powi(2))
+}
+
+/// Fan noise: low-passed white noise plus a 120Hz hum, RMS ~0.05 FS
+pub fn fan(seconds: f32, seed: u64) -> Vec<f32> {
+    let mut rng = Noise::new(seed);
+    let mut state = 0.0f32;
+    let mut out: Vec<f32> = (0..samples_for(seconds))
+        .map(|i| {
+            state = 0.9 * state + 0.1 * rng.next();
+            let hum = (2.0 * PI * 120.0 * i as f32 / SAMPLE_RATE as f32).sin();
+            state * 4.0 + 0.1 * hum
+        })
+        .collect();
+    normalize_rms(&mut out, 0.05);
+    out
+}
+
+/// Keyboard clatter: decaying broadband clicks every 80-250ms
+pub fn keyboard(seconds: f32, seed: u64) -> Vec<f32> {
+    let mut rng = Noise::new(seed);
+    let len = samples_for(seconds);
+    let mut out = vec![0.0f32; len];
+
+    let mut pos = samples_for(0.05);
+    while pos < len {
+        let amplitude = 0.15 + 0.1 * rng.next();
+        for i in 0..samples_for(0.008).min(len - pos) {
+            let decay = (-(i as f32) / 20.0).exp();
+            out[pos + i] += amplitude * decay * rng.next();
+        }
+        pos += samples_for(0.165 + 0.085 * rng.next());
+    }
+    out
+}
+
+/// Chords of three harmonic notes, each held 1.5-2.5s, peak ~0.2 FS
+pub fn music(seconds: f32, seed: u64) -> Vec<f32> {
+    let mut rng = Noise::new(seed);
+    let len = samples_for(seconds);
+    let mut out = vec![0.0f32; len];
+
+    let mut pos = 0;
+    while pos < len {
+        let duration = samples_for(2.0 + 0.5 * rng.next());
+        let root = 220.0 * 2f32.powf((5.0 * rng.next()).round() / 12.0);
+        let notes = [root, root * 1.26, root * 1.5];
+        for i in 0..duration.min(len - pos) {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let attack = (t / 0.03).min(1.0);
+            let mut sample = 0.0;
+            for note in notes {
+                for harmonic in 1..=5 {
+                    sample += (2.0 * PI * note * harmonic as f32 * t).sin() / harmonic as f32;
+                }
+            }
+            out[pos + i] = attack * sample;
+        }
+        pos += duration;
+    }
+
+    normalize_peak(&mut out, 0.2);
+    out
+}
+
+/// Audio clip with ground truth for speech detectors
+pub struct LabeledClip {
+    pub name: &'static str,
+    pub samples: Vec<i16>,
+    /// One label per `frame_samples` frame: true = speech
+    pub labels: Vec<bool>,
+}
+
+/// Clips covering what the gates get wrong: quiet talkers, speech in
+/// noise, and loud non-speech (HVAC, typing, music). 20ms frame labels.
+pub fn labeled_set() -> Vec<LabeledClip> {
+    let frame = samples_for(0.02);
+    let seconds = 8.0;
+    let none = vec![false; samples_for(seconds)];
+    // Microphone self-noise around -80dBFS
+    let mut hiss = Noise::new(90);
+    let room_tone: Vec<f32> = (0..samples_for(seconds)).map(|_| 0.0002 * hiss.next()).collect();
+
+    let (talk, talking) = labeled_speech(seconds, 11);
+    let (quiet, quiet_talking) = labeled_speech(seconds, 12);
+    let quiet: Vec<f32> = quiet.iter().zip(&room_tone).map(|(s, n)| 0.03 * s + n).collect();
+    let (noisy, noisy_talking) = labeled_speech(seconds, 13);
+
+    let clips = [
+        ("clean speech", talk, talking),
+        ("quiet speech", quiet, quiet_talking),
+        ("speech in fan noise", mix(&noisy, &fan(seconds, 14), 10.0), noisy_talking),
+        ("fan", fan(seconds, 15), none.clone()),
+        ("typing", keyboard(seconds, 16), none.clone()),
+        ("music", music(seconds, 17), none.clone()),
+        ("room tone", room_tone.clone(), none),
+    ];
+
+    clips.into_iter()
+        .map(|(name, samples, active)| LabeledClip {
+            name,
+            samples: to_i16(&samples),
+            labels: active.chunks(frame)
+                .filter(|chunk| chunk.len() == frame)
+                .map(|chunk| chunk.iter().filter(|&&a| a).count() * 2 >= frame)
+                .collect(),
+        })
+        .collect()
+}
+
+/// Scale `noise` so speech-to-noise ratio is `snr_db`, then add it
+pub fn mix(speech: &[f32], noise: &[f32], snr_db: f32) -> Vec<f32> {
+    let scale = (energy(speech) / energy(noise) / 10f32.powf(snr_db / 10.0)).sqrt();
+    speech.iter().zip(noise).map(|(s, n)| s + scale * n).collect()
+}
+
+/// Convert to i16 frames as the pipeline produces them
+pub fn to_i16(samples: &[f32]) -> Vec<i16> {
+    samples.iter()
+        .map(|&s| (s * 32767.0).clamp(-32768.0, 32767.0) as i16)
+        .collect()
+}
+
+pub fn energy(samples: &[f32]) -> f32 {
+    samples.iter().map(|s| s * s).sum()
+}
+
+/// SNR of `processed` against `clean`, in dB
+pub fn snr_db(clean: &[f32], processed: &[f32]) -> f32 {
+    let error: f32 = clean.iter().zip(processed).map(|(c, p)| (c - p) * (c - p)).sum();
+    10.0 * (energy(clean) / error.max(1e-12)).log10()
+}
+
+fn normalize_peak(samples: &mut [f32], peak: f32) {
+    let max = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    if max > 0.0 {
+        samples.iter_mut().for_each(|s| *s *= peak / max);
+    }
+}
+
+fn normalize_rms(samples: &mut [f32], rms: f32) {
+    let current = (energy(samples) / samples.len().max(1) as f32).sqrt();
+    if current > 0.0 {
+        samples.iter_mut().for_each(|s| *s *= rms / current);
+    }
+}
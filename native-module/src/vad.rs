@@ -8,10 +8,21 @@
 // - Showing "speaking" indicator in UI
 // - Detecting utterance boundaries
 // - Optional stream management (not used currently)
+//
+// Hangover is measured in stream time (samples seen at the configured
+// output rate), like the silence suppressor.
+//
+// State changes are also reported as VadEvents (speech start / end with
+// the stream position and peak levels), which the pipeline forwards to JS.
+//
+// Speech is decided by the RMS start / end thresholds, or, when the capture
+// uses a SpeechDetector (spectral, neural), by the decision the silence
+// suppressor's detector made for the same frame (update_with_decision), so
+// the detector runs once per frame.
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 
-use crate::audio_config::{VAD_START_RMS, VAD_END_RMS, VAD_HANGOVER_MS};
+use crate::audio_config::{SAMPLE_RATE, VAD_START_RMS, VAD_END_RMS, VAD_HANGOVER_MS};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VadState {
@@ -20,6 +31,57 @@ pub enum VadState {
     Hangover,
 }
 
+/// VAD thresholds (RMS, i16 scale) and timing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VadConfig {
+    /// Level that starts speech
+    pub start_threshold: f32,
+    /// Level below which speech starts to end
+    pub end_threshold: f32,
+    /// Quiet time before speech is over
+    pub hangover: Duration,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            start_threshold: VAD_START_RMS,
+            end_threshold: VAD_END_RMS,
+            hangover: Duration::from_millis(VAD_HANGOVER_MS as u64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VadEventKind {
+    SpeechStart,
+    SpeechEnd,
+}
+
+impl VadEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VadEventKind::SpeechStart => "speechStart",
+            VadEventKind::SpeechEnd => "speechEnd",
+        }
+    }
+}
+
+/// A speech start or end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VadEvent {
+    pub kind: VadEventKind,
+    /// Stream sample where speech started / ended
+    pub sample: u64,
+    /// Largest absolute sample: of the onset chunk for a start,
+    /// of the whole utterance for an end
+    pub peak_level: u16,
+    /// Largest chunk RMS over the same span
+    pub peak_rms: f32,
+    /// Utterance length (zero for a start)
+    pub duration: Duration,
+}
+
 /// Voice Activity Detector for UI indication
 /// Does NOT gate audio - only reports state
 pub struct VadIndicator {
@@ -28,18 +90,49 @@ pub struct VadIndicator {
     end_threshold: f32,
     hangover_duration_ms: u128,
     hangover_start_time: u128,
+    /// Sample rate of the chunks, used to measure time in stream samples
+    sample_rate: u32,
+    samples_seen: u64,
     pub last_rms: f32,
+    /// Current utterance: first sample, sample where it went quiet, peaks
+    utterance_start: u64,
+    utterance_end: u64,
+    peak_level: u16,
+    peak_rms: f32,
+    event: Option<VadEvent>,
+}
+
+impl Default for VadIndicator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VadIndicator {
     pub fn new() -> Self {
+        Self::with_sample_rate(SAMPLE_RATE)
+    }
+
+    /// VAD for chunks at a configured output rate
+    pub fn with_sample_rate(sample_rate: u32) -> Self {
+        Self::with_config(VadConfig::default(), sample_rate)
+    }
+
+    pub fn with_config(config: VadConfig, sample_rate: u32) -> Self {
         Self {
             state: VadState::Idle,
-            start_threshold: VAD_START_RMS,
-            end_threshold: VAD_END_RMS,
-            hangover_duration_ms: VAD_HANGOVER_MS,
+            start_threshold: config.start_threshold,
+            end_threshold: config.end_threshold,
+            hangover_duration_ms: config.hangover.as_millis(),
             hangover_start_time: 0,
+            sample_rate: sample_rate.max(1),
+            samples_seen: 0,
             last_rms: 0.0,
+            utterance_start: 0,
+            utterance_end: 0,
+            peak_level: 0,
+            peak_rms: 0.0,
+            event: None,
         }
     }
 
@@ -47,31 +140,70 @@ impl VadIndicator {
     /// Returns current state for UI display
     /// DOES NOT affect audio flow to STT
     pub fn update(&mut self, chunk: &[i16]) -> VadState {
+        self.advance(chunk, None)
+    }
+
+    /// `update` with a speech decision made elsewhere
+    /// (thresholds unused, hangover kept)
+    pub fn update_with_decision(&mut self, chunk: &[i16], speech: bool) -> VadState {
+        self.advance(chunk, Some(speech))
+    }
+
+    fn advance(&mut self, chunk: &[i16], decision: Option<bool>) -> VadState {
         let rms = self.calculate_rms(chunk);
+        let chunk_peak = chunk.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
         self.last_rms = rms;
+        let chunk_start = self.samples_seen;
+        self.samples_seen += chunk.len() as u64;
         let now = self.current_time_ms();
+        self.event = None;
+        let (starts, ends) = match decision {
+            Some(speech) => (speech, !speech),
+            None => (rms > self.start_threshold, rms < self.end_threshold),
+        };
 
         match self.state {
             VadState::Idle => {
-                if rms > self.start_threshold {
+                if starts {
                     self.state = VadState::Speech;
                     println!("[VAD-UI] Speech detected (RMS: {})", rms as i32);
+                    self.utterance_start = chunk_start;
+                    self.peak_level = chunk_peak;
+                    self.peak_rms = rms;
+                    self.event = Some(VadEvent {
+                        kind: VadEventKind::SpeechStart,
+                        sample: chunk_start,
+                        peak_level: chunk_peak,
+                        peak_rms: rms,
+                        duration: Duration::ZERO,
+                    });
                 }
             }
             VadState::Speech => {
-                if rms < self.end_threshold {
+                self.track_peaks(chunk_peak, rms);
+                if ends {
                     self.state = VadState::Hangover;
                     self.hangover_start_time = now;
+                    self.utterance_end = chunk_start;
                 }
             }
             VadState::Hangover => {
-                if rms > self.start_threshold {
+                if starts {
                     self.state = VadState::Speech;
+                    self.track_peaks(chunk_peak, rms);
                 } else {
                     let time_in_hangover = now - self.hangover_start_time;
                     if time_in_hangover > self.hangover_duration_ms {
                         self.state = VadState::Idle;
                         println!("[VAD-UI] Speech ended");
+                        let length = self.utterance_end - self.utterance_start;
+                        self.event = Some(VadEvent {
+                            kind: VadEventKind::SpeechEnd,
+                            sample: self.utterance_end,
+                            peak_level: self.peak_level,
+                            peak_rms: self.peak_rms,
+                            duration: Duration::from_secs_f64(length as f64 / self.sample_rate as f64),
+                        });
                     }
                 }
             }
@@ -80,6 +212,16 @@ impl VadIndicator {
         self.state
     }
 
+    /// Speech start / end caused by the last `update`
+    pub fn take_event(&mut self) -> Option<VadEvent> {
+        self.event.take()
+    }
+
+    fn track_peaks(&mut self, chunk_peak: u16, rms: f32) {
+        self.peak_level = self.peak_level.max(chunk_peak);
+        self.peak_rms = self.peak_rms.max(rms);
+    }
+
     /// Check if currently in speech state (for UI)
     pub fn is_speech(&self) -> bool {
         matches!(self.state, VadState::Speech | VadState::Hangover)
@@ -87,6 +229,7 @@ impl VadIndicator {
 
     pub fn reset(&mut self) {
         self.state = VadState::Idle;
+        self.event = None;
     }
 
     fn calculate_rms(&self, data: &[i16]) -> f32 {
@@ -113,11 +256,9 @@ impl VadIndicator {
         (sum / count as f32).sqrt()
     }
 
+    /// Stream time at the end of the chunks seen so far
     fn current_time_ms(&self) -> u128 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis()
+        self.samples_seen as u128 * 1000 / self.sample_rate as u128
     }
 }
 
@@ -138,3 +279,47 @@ impl VadGate {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hangover_in_stream_time() {
+        // 8kHz, 10ms chunks: hangover of 500ms = 50 quiet chunks
+        let mut vad = VadIndicator::with_sample_rate(8000);
+        assert_eq!(vad.update(&[1000; 80]), VadState::Speech);
+
+        let quiet = [0i16; 80];
+        let chunks_until_idle = (1..=100)
+            .find(|_| vad.update(&quiet) == VadState::Idle)
+            .unwrap();
+        assert!((50..=52).contains(&chunks_until_idle), "{}", chunks_until_idle);
+    }
+
+    #[test]
+    fn test_events_carry_positions_and_peaks() {
+        let config = VadConfig { hangover: Duration::from_millis(100), ..VadConfig::default() };
+        let mut vad = VadIndicator::with_config(config, 16_000);
+        let mut events = Vec::new();
+        // 200ms quiet, 300ms speech peaking at 4000, 500ms quiet (20ms chunks)
+        for i in 0..50 {
+            let chunk = match i {
+                10..25 => vec![if i == 17 { 4000 } else { 1000 }; 320],
+                _ => vec![0; 320],
+            };
+            vad.update(&chunk);
+            events.extend(vad.take_event());
+        }
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, VadEventKind::SpeechStart);
+        assert_eq!(events[0].sample, 3200);
+        assert_eq!(events[0].peak_level, 1000);
+        assert_eq!(events[1].kind, VadEventKind::SpeechEnd);
+        assert_eq!(events[1].sample, 8000);
+        assert_eq!(events[1].peak_level, 4000);
+        assert_eq!(events[1].peak_rms, 4000.0);
+        assert_eq!(events[1].duration, Duration::from_millis(300));
+    }
+}
@@ -0,0 +1,141 @@
+// DSP Thread Wakeup - Real-Time Safe Producer Signal
+//
+// Replaces busy-polling the ring buffer every 1ms. Producers (audio callbacks,
+// reader threads) call `notify()` after pushing samples; the DSP thread parks
+// in `wait_timeout()` until the next push.
+//
+// REAL-TIME SAFETY (producer side):
+// - Only atomics and `Mutex::try_lock` (never blocks, never allocates)
+// - `Thread::unpark` is a single futex/ulock wake, and is only issued when the
+//   DSP thread has announced it is about to park
+//
+// A wakeup is never lost, but can be delayed by at most one wait timeout:
+// both sides publish with SeqCst before checking the other's flag, and an
+// unpark that races ahead of park is remembered. If `notify()` misses the
+// `try_lock` (the DSP thread is re-registering), it skips the unpark; the
+// pending flag stays set and the waiter sees it when its timeout expires.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+struct NotifierInner {
+    /// Set by the producer, cleared by the waiter
+    pending: AtomicBool,
+    /// Waiter is parked (or about to park)
+    waiting: AtomicBool,
+    /// Thread to unpark. Producer only ever try_locks it.
+    waiter: Mutex<Option<Thread>>,
+}
+
+/// Cloneable handle shared between one producer and one waiting thread
+#[derive(Clone)]
+pub struct DataNotifier {
+    inner: Arc<NotifierInner>,
+}
+
+impl Default for DataNotifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DataNotifier {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(NotifierInner {
+                pending: AtomicBool::new(false),
+                waiting: AtomicBool::new(false),
+                waiter: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Signal that data is available. Safe to call from an audio callback.
+    pub fn notify(&self) {
+        let inner = &*self.inner;
+        inner.pending.store(true, Ordering::SeqCst);
+        if inner.waiting.load(Ordering::SeqCst) {
+            // The lock is only held while a DSP thread (re)registers.
+            // If we miss it, the waiter's timeout covers us.
+            if let Ok(waiter) = inner.waiter.try_lock() {
+                if let Some(thread) = waiter.as_ref() {
+                    thread.unpark();
+                }
+            }
+        }
+    }
+
+    /// Make the calling thread the one woken by `notify()`
+    pub fn register_current_thread(&self) {
+        let mut waiter = self.inner.waiter.lock().unwrap_or_else(|e| e.into_inner());
+        *waiter = Some(thread::current());
+    }
+
+    /// Park until `notify()` or `timeout` elapses
+    ///
+    /// Returns true if data was signalled. Must be called from the
+    /// registered thread.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let inner = &*self.inner;
+        if inner.pending.swap(false, Ordering::AcqRel) {
+            return true;
+        }
+
+        inner.waiting.store(true, Ordering::SeqCst);
+        // Re-check after announcing, so a notify() in between is not lost
+        if !inner.pending.load(Ordering::SeqCst) {
+            thread::park_timeout(timeout);
+        }
+        inner.waiting.store(false, Ordering::SeqCst);
+
+        inner.pending.swap(false, Ordering::AcqRel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_pending_notify_returns_immediately() {
+        let notifier = DataNotifier::new();
+        notifier.register_current_thread();
+        notifier.notify();
+
+        let start = Instant::now();
+        assert!(notifier.wait_timeout(Duration::from_secs(5)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_cross_thread_wakeup() {
+        let notifier = DataNotifier::new();
+        notifier.register_current_thread();
+
+        let producer = notifier.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            producer.notify();
+        });
+
+        let start = Instant::now();
+        let mut woken = false;
+        while !woken && start.elapsed() < Duration::from_secs(5) {
+            woken = notifier.wait_timeout(Duration::from_secs(5));
+        }
+        handle.join().unwrap();
+
+        assert!(woken);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_timeout_without_notify() {
+        let notifier = DataNotifier::new();
+        notifier.register_current_thread();
+        assert!(!notifier.wait_timeout(Duration::from_millis(5)));
+    }
+}